@@ -0,0 +1,246 @@
+// What rays that miss every shape see. Environment images are
+// equirectangular: x covers the full turn around the y axis with the image
+// center looking down +z, and y runs from straight up to straight down.
+//
+// For image based lighting the path tracer samples bright parts of the
+// environment directly. Pixels are chosen in proportion to their luminance
+// times sin(theta), which undoes the stretching of the poles.
+
+use crate::float::consts::PI;
+use crate::float::Float;
+
+use crate::canvas;
+use crate::color;
+use crate::random;
+use crate::vector3;
+
+pub enum Background {
+    Constant {
+        color: color::Color,
+    },
+    // Blends from bottom (looking straight down) to top (straight up).
+    Gradient {
+        bottom: color::Color,
+        top: color::Color,
+    },
+    Environment {
+        image: canvas::Canvas,
+        strength: Float,
+        // Running totals of pixel weights: rows[y] sums every row above y,
+        // columns[y * (width + 1) + x] the pixels left of x in row y.
+        rows: Vec<Float>,
+        columns: Vec<Float>,
+    },
+}
+
+pub fn constant(color: &color::Color) -> Background {
+    return Background::Constant { color: *color };
+}
+
+pub fn gradient(bottom: &color::Color, top: &color::Color) -> Background {
+    return Background::Gradient {
+        bottom: *bottom,
+        top: *top,
+    };
+}
+
+pub fn environment(image: canvas::Canvas, strength: Float) -> Background {
+    let (w, h) = (image.width(), image.height());
+    let mut rows = Vec::with_capacity(h + 1);
+    let mut columns = Vec::with_capacity(h * (w + 1));
+    rows.push(0.0);
+    for y in 0..h {
+        let sin_theta = ((y as Float + 0.5) / h as Float * PI).sin();
+        let mut sum = 0.0;
+        columns.push(0.0);
+        for x in 0..w {
+            sum += color::luminance(&canvas::pixel_at(&image, x, y)).max(0.0) * sin_theta;
+            columns.push(sum);
+        }
+        rows.push(rows[y] + sum);
+    }
+    return Background::Environment {
+        image,
+        strength,
+        rows,
+        columns,
+    };
+}
+
+pub fn radiance(bg: &Background, direction: &vector3::Vector3) -> color::Color {
+    match bg {
+        Background::Constant { color } => return *color,
+        Background::Gradient { bottom, top } => {
+            let t = 0.5 * (vector3::normalize(direction).y + 1.0);
+            return color::add(&color::multiply(bottom, 1.0 - t), &color::multiply(top, t));
+        }
+        Background::Environment {
+            image, strength, ..
+        } => {
+            let (x, y) = pixel(image, direction);
+            return color::multiply(&canvas::pixel_at(image, x, y), *strength);
+        }
+    }
+}
+
+// Solid angle density of sample choosing direction. Zero for backgrounds
+// that are not sampled, so bounces alone account for them.
+pub fn pdf(bg: &Background, direction: &vector3::Vector3) -> Float {
+    match bg {
+        Background::Environment {
+            image,
+            rows,
+            columns,
+            ..
+        } => {
+            let total = rows[rows.len() - 1];
+            let d = vector3::normalize(direction);
+            let sin_theta = (1.0 - d.y * d.y).max(0.0).sqrt();
+            if total <= 0.0 || sin_theta == 0.0 {
+                return 0.0;
+            }
+            let (w, h) = (image.width(), image.height());
+            let (x, y) = pixel(image, &d);
+            let row = y * (w + 1);
+            let weight = columns[row + x + 1] - columns[row + x];
+            return weight / total * (w * h) as Float / (2.0 * PI * PI * sin_theta);
+        }
+        _ => return 0.0,
+    }
+}
+
+// A direction towards the environment and its pdf, or None when there is
+// nothing to sample.
+pub fn sample(bg: &Background, rng: &mut random::Random) -> Option<(vector3::Vector3, Float)> {
+    match bg {
+        Background::Environment {
+            image,
+            rows,
+            columns,
+            ..
+        } => {
+            let total = rows[rows.len() - 1];
+            if total <= 0.0 {
+                return None;
+            }
+            let w = image.width();
+            let y = find(rows, random::next_float(rng) * total);
+            let row = &columns[y * (w + 1)..(y + 1) * (w + 1)];
+            let x = find(row, random::next_float(rng) * row[w]);
+            let u = (x as Float + random::next_float(rng)) / w as Float;
+            let v = (y as Float + random::next_float(rng)) / image.height() as Float;
+            let direction = direction(u, v);
+            let p = pdf(bg, &direction);
+            if p <= 0.0 {
+                return None;
+            }
+            return Some((direction, p));
+        }
+        _ => return None,
+    }
+}
+
+// Index i with cdf[i] <= value < cdf[i + 1], skipping empty intervals.
+fn find(cdf: &[Float], value: Float) -> usize {
+    let i = cdf.partition_point(|c| *c <= value);
+    return i.clamp(1, cdf.len() - 1) - 1;
+}
+
+fn direction(u: Float, v: Float) -> vector3::Vector3 {
+    let theta = v * PI;
+    let phi = (u - 0.5) * 2.0 * PI;
+    return vector3::vector3(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    );
+}
+
+fn pixel(image: &canvas::Canvas, direction: &vector3::Vector3) -> (usize, usize) {
+    let d = vector3::normalize(direction);
+    let u = 0.5 + d.x.atan2(d.z) / (2.0 * PI);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+    let x = ((u * image.width() as Float) as usize).min(image.width() - 1);
+    let y = ((v * image.height() as Float) as usize).min(image.height() - 1);
+    return (x, y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    #[test]
+    fn should_blend_gradient_by_height() {
+        let bg = gradient(&color::color(0.0, 0.0, 0.0), &color::color(1.0, 1.0, 1.0));
+        let up = radiance(&bg, &vector3::vector3(0.0, 2.0, 0.0));
+        let level = radiance(&bg, &vector3::vector3(1.0, 0.0, 0.0));
+        assert!(color::equals(&up, &color::color(1.0, 1.0, 1.0)));
+        assert!(color::equals(&level, &color::color(0.5, 0.5, 0.5)));
+        let c = constant(&color::color(0.2, 0.3, 0.4));
+        assert!(color::equals(
+            &radiance(&c, &vector3::vector3(0.0, -1.0, 0.0)),
+            &color::color(0.2, 0.3, 0.4)
+        ));
+        assert!(pdf(&c, &vector3::vector3(0.0, 1.0, 0.0)) == 0.0);
+    }
+
+    #[test]
+    fn should_map_directions_to_pixels() {
+        let mut image = canvas::canvas(4, 2);
+        canvas::write_pixel(&mut image, 2, 0, &color::color(1.0, 0.0, 0.0));
+        canvas::write_pixel(&mut image, 0, 1, &color::color(0.0, 1.0, 0.0));
+        let bg = environment(image, 2.0);
+        // +z and up lands in the top half, just right of center.
+        let ahead = radiance(&bg, &vector3::vector3(0.1, 0.5, 1.0));
+        assert!(color::equals(&ahead, &color::color(2.0, 0.0, 0.0)));
+        // -z and down wraps around to the left edge of the bottom half.
+        let behind = radiance(&bg, &vector3::vector3(-0.1, -0.5, -1.0));
+        assert!(color::equals(&behind, &color::color(0.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn should_sample_bright_regions() {
+        let mut image = canvas::canvas(8, 4);
+        for x in 0..8 {
+            for y in 0..4 {
+                canvas::write_pixel(&mut image, x, y, &color::color(0.01, 0.01, 0.01));
+            }
+        }
+        canvas::write_pixel(&mut image, 5, 1, &color::color(100.0, 100.0, 100.0));
+        let bg = environment(image, 1.0);
+        let mut rng = random::random(3);
+        let mut bright = 0;
+        for _ in 0..1000 {
+            let (d, p) = sample(&bg, &mut rng).unwrap();
+            assert!(float::equals(p, pdf(&bg, &d)));
+            if radiance(&bg, &d).red > 1.0 {
+                bright += 1;
+            }
+        }
+        assert!(bright > 990);
+    }
+
+    #[test]
+    fn should_integrate_pdf_to_one() {
+        let mut image = canvas::canvas(16, 8);
+        for x in 0..16 {
+            for y in 0..8 {
+                let v = (x * y) as Float;
+                canvas::write_pixel(&mut image, x, y, &color::color(v, v, v));
+            }
+        }
+        let bg = environment(image, 1.0);
+        // Average pdf over uniformly spread directions, times 4 pi.
+        let mut rng = random::random(9);
+        let mut sum = 0.0;
+        let n = 20000;
+        for _ in 0..n {
+            let z = 1.0 - 2.0 * random::next_float(&mut rng);
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * random::next_float(&mut rng);
+            sum += pdf(&bg, &vector3::vector3(r * phi.cos(), z, r * phi.sin()));
+        }
+        assert!((sum / n as Float * 4.0 * PI - 1.0).abs() < 0.03);
+    }
+}
@@ -0,0 +1,547 @@
+// Scattering functions for the path tracer. Every BSDF can be evaluated for
+// a pair of directions and importance sampled, and both agree on the pdf.
+// Directions point away from the surface: wo towards the viewer, wi towards
+// the light. normal is the shape's outward normal; only the dielectric cares
+// which side it is on, the others are two-sided.
+//
+// Microfacet lobes use the GGX (Trowbridge-Reitz) distribution with the
+// separable Smith shadowing term. roughness is perceptual, alpha = r^2.
+
+use crate::float::consts::PI;
+use crate::float::Float;
+
+use crate::color;
+use crate::material;
+use crate::normal3;
+use crate::random;
+use crate::tuple;
+use crate::vector3;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Bsdf {
+    // Ideal diffuse, albedo = material color * diffuse.
+    Lambertian,
+    // GGX specular reflection with Schlick Fresnel, using the material color
+    // as the reflectance at normal incidence.
+    Microfacet {
+        roughness: Float,
+    },
+    // Metal with a complex index of refraction eta + ik per color channel.
+    Conductor {
+        roughness: Float,
+        eta: color::Color,
+        k: color::Color,
+    },
+    // Rough glass using the material's refractive index. Transmission is
+    // tinted by the material color.
+    Dielectric {
+        roughness: Float,
+    },
+}
+
+pub struct Sample {
+    pub direction: vector3::Vector3,
+    // f * |cos| / pdf, what the path throughput gets multiplied by.
+    pub weight: color::Color,
+    pub pdf: Float,
+}
+
+pub fn lambertian() -> Bsdf {
+    return Bsdf::Lambertian;
+}
+
+pub fn microfacet(roughness: Float) -> Bsdf {
+    return Bsdf::Microfacet { roughness };
+}
+
+pub fn conductor(roughness: Float, eta: &color::Color, k: &color::Color) -> Bsdf {
+    return Bsdf::Conductor {
+        roughness,
+        eta: *eta,
+        k: *k,
+    };
+}
+
+pub fn dielectric(roughness: Float) -> Bsdf {
+    return Bsdf::Dielectric { roughness };
+}
+
+pub fn evaluate(
+    m: &material::Material,
+    normal: &normal3::Normal3,
+    wo: &vector3::Vector3,
+    wi: &vector3::Vector3,
+) -> color::Color {
+    let f = frame(normal);
+    return evaluate_local(m, &to_local(&f, wo), &to_local(&f, wi));
+}
+
+pub fn pdf(
+    m: &material::Material,
+    normal: &normal3::Normal3,
+    wo: &vector3::Vector3,
+    wi: &vector3::Vector3,
+) -> Float {
+    let f = frame(normal);
+    return pdf_local(m, &to_local(&f, wo), &to_local(&f, wi));
+}
+
+pub fn sample(
+    m: &material::Material,
+    normal: &normal3::Normal3,
+    wo: &vector3::Vector3,
+    rng: &mut random::Random,
+) -> Option<Sample> {
+    let f = frame(normal);
+    let lo = to_local(&f, wo);
+    if lo.z == 0.0 {
+        return None;
+    }
+    let li = match m.bsdf {
+        Bsdf::Lambertian => {
+            let mut d = cosine_local(rng);
+            if lo.z < 0.0 {
+                d.z = -d.z;
+            }
+            d
+        }
+        Bsdf::Microfacet { roughness } | Bsdf::Conductor { roughness, .. } => {
+            let h = with_sign(&sample_half(alpha(roughness), rng), lo.z);
+            let d = reflect(&lo, &h);
+            if d.z * lo.z <= 0.0 {
+                return None;
+            }
+            d
+        }
+        Bsdf::Dielectric { roughness } => {
+            let h = sample_half(alpha(roughness), rng);
+            let fr = fresnel_dielectric(tuple::dot(&lo, &h), 1.0, m.refractive_index);
+            let h = with_sign(&h, lo.z);
+            if random::next_float(rng) < fr {
+                let d = reflect(&lo, &h);
+                if d.z * lo.z <= 0.0 {
+                    return None;
+                }
+                d
+            } else {
+                let (eta_i, eta_t) = if lo.z > 0.0 {
+                    (1.0, m.refractive_index)
+                } else {
+                    (m.refractive_index, 1.0)
+                };
+                refract(&lo, &h, eta_i / eta_t)?
+            }
+        }
+    };
+    let p = pdf_local(m, &lo, &li);
+    if p <= 0.0 {
+        return None;
+    }
+    let value = evaluate_local(m, &lo, &li);
+    return Some(Sample {
+        direction: to_world(&f, &li),
+        weight: color::multiply(&value, li.z.abs() / p),
+        pdf: p,
+    });
+}
+
+fn evaluate_local(m: &material::Material, lo: &tuple::Tuple, li: &tuple::Tuple) -> color::Color {
+    let black = color::color(0.0, 0.0, 0.0);
+    let same_side = lo.z * li.z > 0.0;
+    match m.bsdf {
+        Bsdf::Lambertian => {
+            if !same_side {
+                return black;
+            }
+            return color::multiply(&m.color, m.diffuse / PI);
+        }
+        Bsdf::Microfacet { roughness } | Bsdf::Conductor { roughness, .. } => {
+            if !same_side {
+                return black;
+            }
+            let a = alpha(roughness);
+            let h = tuple::normalize(&tuple::add(lo, li));
+            let h = with_sign(&h, 1.0);
+            let cos = tuple::dot(lo, &h).abs();
+            let fr = match m.bsdf {
+                Bsdf::Conductor { eta, k, .. } => fresnel_conductor(cos, &eta, &k),
+                _ => schlick(cos, &m.color),
+            };
+            let s = ggx(&h, a) * smith(lo, li, a) / (4.0 * (lo.z * li.z).abs());
+            return color::multiply(&fr, s);
+        }
+        Bsdf::Dielectric { roughness } => {
+            if lo.z == 0.0 || li.z == 0.0 {
+                return black;
+            }
+            let a = alpha(roughness);
+            let (h, eta) = dielectric_half(lo, li, m.refractive_index, same_side);
+            let fr = fresnel_dielectric(tuple::dot(lo, &h), 1.0, m.refractive_index);
+            let d = ggx(&h, a) * smith(lo, li, a);
+            if same_side {
+                let s = fr * d / (4.0 * (lo.z * li.z).abs());
+                return color::color(s, s, s);
+            }
+            let oh = tuple::dot(lo, &h);
+            let ih = tuple::dot(li, &h);
+            if oh * ih >= 0.0 {
+                return black;
+            }
+            let denom = oh + eta * ih;
+            // Radiance is compressed into a smaller solid angle on the way
+            // into a denser medium; 1 / eta^2 cancels the eta^2 on top.
+            let s = (1.0 - fr) * (d * ih * oh / (li.z * lo.z * denom * denom)).abs();
+            return color::multiply(&m.color, s);
+        }
+    }
+}
+
+fn pdf_local(m: &material::Material, lo: &tuple::Tuple, li: &tuple::Tuple) -> Float {
+    let same_side = lo.z * li.z > 0.0;
+    match m.bsdf {
+        Bsdf::Lambertian => {
+            if !same_side {
+                return 0.0;
+            }
+            return li.z.abs() / PI;
+        }
+        Bsdf::Microfacet { roughness } | Bsdf::Conductor { roughness, .. } => {
+            if !same_side {
+                return 0.0;
+            }
+            let h = with_sign(&tuple::normalize(&tuple::add(lo, li)), 1.0);
+            return ggx(&h, alpha(roughness)) * h.z / (4.0 * tuple::dot(lo, &h).abs());
+        }
+        Bsdf::Dielectric { roughness } => {
+            if lo.z == 0.0 || li.z == 0.0 {
+                return 0.0;
+            }
+            let (h, eta) = dielectric_half(lo, li, m.refractive_index, same_side);
+            let fr = fresnel_dielectric(tuple::dot(lo, &h), 1.0, m.refractive_index);
+            let ph = ggx(&h, alpha(roughness)) * h.z;
+            if same_side {
+                return fr * ph / (4.0 * tuple::dot(lo, &h).abs());
+            }
+            let oh = tuple::dot(lo, &h);
+            let ih = tuple::dot(li, &h);
+            if oh * ih >= 0.0 {
+                return 0.0;
+            }
+            let denom = oh + eta * ih;
+            return (1.0 - fr) * ph * (eta * eta * ih / (denom * denom)).abs();
+        }
+    }
+}
+
+// Half vector on the +z side, and the ratio of indices across the surface
+// (transmitted over incident, as seen from wo).
+fn dielectric_half(
+    lo: &tuple::Tuple,
+    li: &tuple::Tuple,
+    ior: Float,
+    same_side: bool,
+) -> (tuple::Tuple, Float) {
+    let eta = if lo.z > 0.0 { ior } else { 1.0 / ior };
+    let h = if same_side {
+        tuple::add(lo, li)
+    } else {
+        tuple::add(lo, &tuple::multiply(li, eta))
+    };
+    return (with_sign(&tuple::normalize(&h), 1.0), eta);
+}
+
+fn alpha(roughness: Float) -> Float {
+    return (roughness * roughness).max(0.001);
+}
+
+// GGX normal distribution; integrates to one when weighted by cos(theta_h).
+pub fn ggx(h: &tuple::Tuple, a: Float) -> Float {
+    if h.z <= 0.0 {
+        return 0.0;
+    }
+    let a2 = a * a;
+    let d = h.z * h.z * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn smith_g1(v: &tuple::Tuple, a: Float) -> Float {
+    let cos2 = v.z * v.z;
+    if cos2 == 0.0 {
+        return 0.0;
+    }
+    let tan2 = (1.0 - cos2) / cos2;
+    return 2.0 / (1.0 + (1.0 + a * a * tan2).sqrt());
+}
+
+fn smith(lo: &tuple::Tuple, li: &tuple::Tuple, a: Float) -> Float {
+    return smith_g1(lo, a) * smith_g1(li, a);
+}
+
+// Microfacet normal distributed as ggx * cos(theta_h).
+fn sample_half(a: Float, rng: &mut random::Random) -> tuple::Tuple {
+    let u = random::next_float(rng);
+    let phi = 2.0 * PI * random::next_float(rng);
+    let tan2 = a * a * u / (1.0 - u);
+    let cos = 1.0 / (1.0 + tan2).sqrt();
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    return tuple::vector(sin * phi.cos(), sin * phi.sin(), cos);
+}
+
+fn cosine_local(rng: &mut random::Random) -> tuple::Tuple {
+    let u = random::next_float(rng);
+    let phi = 2.0 * PI * random::next_float(rng);
+    let r = u.sqrt();
+    return tuple::vector(r * phi.cos(), r * phi.sin(), (1.0 - u).sqrt());
+}
+
+// A random unit vector in the hemisphere around normal, more likely near the
+// normal in proportion to the cosine of the angle from it.
+pub fn cosine_hemisphere(normal: &normal3::Normal3, rng: &mut random::Random) -> vector3::Vector3 {
+    return to_world(&frame(normal), &cosine_local(rng));
+}
+
+fn with_sign(v: &tuple::Tuple, sign: Float) -> tuple::Tuple {
+    if v.z * sign < 0.0 {
+        return tuple::negate(v);
+    }
+    return *v;
+}
+
+fn reflect(v: &tuple::Tuple, h: &tuple::Tuple) -> tuple::Tuple {
+    return tuple::subtract(&tuple::multiply(h, 2.0 * tuple::dot(v, h)), v);
+}
+
+// Snell's law for v leaving through a surface with normal h on v's side,
+// with eta the ratio of incident over transmitted index. None on total
+// internal reflection.
+fn refract(v: &tuple::Tuple, h: &tuple::Tuple, eta: Float) -> Option<tuple::Tuple> {
+    let h = if tuple::dot(v, h) < 0.0 {
+        tuple::negate(h)
+    } else {
+        *h
+    };
+    let cos_i = tuple::dot(v, &h);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let t = tuple::add(
+        &tuple::multiply(&tuple::negate(v), eta),
+        &tuple::multiply(&h, eta * cos_i - cos_t),
+    );
+    return Some(tuple::normalize(&t));
+}
+
+fn schlick(cos: Float, f0: &color::Color) -> color::Color {
+    let t = (1.0 - cos).clamp(0.0, 1.0).powi(5);
+    let white = color::color(1.0, 1.0, 1.0);
+    return color::add(f0, &color::multiply(&color::subtract(&white, f0), t));
+}
+
+// Unpolarized reflectance between media with indices eta_i (the side cos is
+// measured on when positive) and eta_t.
+pub fn fresnel_dielectric(cos: Float, eta_i: Float, eta_t: Float) -> Float {
+    let mut cos_i = cos.clamp(-1.0, 1.0);
+    let (mut eta_i, mut eta_t) = (eta_i, eta_t);
+    if cos_i < 0.0 {
+        std::mem::swap(&mut eta_i, &mut eta_t);
+        cos_i = -cos_i;
+    }
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).max(0.0).sqrt();
+    let parallel = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let perpendicular = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    return (parallel * parallel + perpendicular * perpendicular) / 2.0;
+}
+
+// Reflectance of a conductor with complex index eta + ik, per channel.
+pub fn fresnel_conductor(cos: Float, eta: &color::Color, k: &color::Color) -> color::Color {
+    let channel = |eta: Float, k: Float| {
+        let c2 = cos * cos;
+        let s2 = 1.0 - c2;
+        let t0 = eta * eta - k * k - s2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t1 = a2b2 + c2;
+        let t2 = 2.0 * cos * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = c2 * a2b2 + s2 * s2;
+        let t4 = t2 * s2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        return (rs + rp) / 2.0;
+    };
+    return color::color(
+        channel(eta.red, k.red),
+        channel(eta.green, k.green),
+        channel(eta.blue, k.blue),
+    );
+}
+
+struct Frame {
+    tangent: vector3::Vector3,
+    bitangent: vector3::Vector3,
+    normal: vector3::Vector3,
+}
+
+fn frame(normal: &normal3::Normal3) -> Frame {
+    let normal = normal3::to_vector(normal);
+    let helper = if normal.x.abs() > 0.9 {
+        vector3::vector3(0.0, 1.0, 0.0)
+    } else {
+        vector3::vector3(1.0, 0.0, 0.0)
+    };
+    let tangent = vector3::normalize(&vector3::cross(&helper, &normal));
+    return Frame {
+        tangent,
+        bitangent: vector3::cross(&normal, &tangent),
+        normal,
+    };
+}
+
+// World directions in the frame are local ones with the normal along z.
+fn to_local(f: &Frame, v: &vector3::Vector3) -> tuple::Tuple {
+    return tuple::vector(
+        vector3::dot(v, &f.tangent),
+        vector3::dot(v, &f.bitangent),
+        vector3::dot(v, &f.normal),
+    );
+}
+
+fn to_world(f: &Frame, v: &tuple::Tuple) -> vector3::Vector3 {
+    return f.tangent * v.x + f.bitangent * v.y + f.normal * v.z;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    fn with(b: Bsdf) -> material::Material {
+        let mut m = material::material();
+        m.bsdf = b;
+        return m;
+    }
+
+    fn up() -> normal3::Normal3 {
+        return normal3::normal3(0.0, 1.0, 0.0);
+    }
+
+    fn straight_up() -> vector3::Vector3 {
+        return normal3::to_vector(&up());
+    }
+
+    #[test]
+    fn should_evaluate_lambertian() {
+        let m = material::material();
+        let wo = vector3::normalize(&vector3::vector3(1.0, 1.0, 0.0));
+        let wi = vector3::normalize(&vector3::vector3(0.0, 1.0, 1.0));
+        let f = evaluate(&m, &up(), &wo, &wi);
+        assert!(float::equals(f.red, 0.9 / PI));
+        let below = vector3::vector3(0.0, -1.0, 0.0);
+        assert!(float::equals(evaluate(&m, &up(), &wo, &below).red, 0.0));
+        assert!(float::equals(pdf(&m, &up(), &wo, &straight_up()), 1.0 / PI));
+    }
+
+    #[test]
+    fn should_sample_hemisphere_around_normal() {
+        let mut rng = random::random(5);
+        let n = normal3::normalize(&normal3::normal3(1.0, 1.0, 0.0));
+        let mut mean_cos = 0.0;
+        for _ in 0..1000 {
+            let d = cosine_hemisphere(&n, &mut rng);
+            assert!(float::equals(vector3::magnitude(&d), 1.0));
+            let cos = normal3::dot(&n, &d);
+            assert!(cos >= 0.0);
+            mean_cos += cos / 1000.0;
+        }
+        // The mean cosine of a cosine-weighted hemisphere is 2/3.
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.03);
+    }
+
+    #[test]
+    fn should_normalize_ggx() {
+        for a in [0.1, 0.5, 1.0] {
+            let steps = 20000;
+            let mut sum = 0.0;
+            for i in 0..steps {
+                let theta = (i as Float + 0.5) / steps as Float * PI / 2.0;
+                let h = tuple::vector(theta.sin(), 0.0, theta.cos());
+                sum += ggx(&h, a) * theta.cos() * theta.sin() * (PI / 2.0 / steps as Float);
+            }
+            assert!((sum * 2.0 * PI - 1.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn should_match_sample_weights_to_evaluation() {
+        let mut rng = random::random(7);
+        let wo = vector3::normalize(&vector3::vector3(0.3, 1.0, -0.2));
+        let gold = conductor(
+            0.4,
+            &color::color(0.143, 0.374, 1.442),
+            &color::color(3.983, 2.385, 1.603),
+        );
+        for b in [lambertian(), microfacet(0.3), gold, dielectric(0.3)] {
+            let mut m = with(b);
+            m.refractive_index = 1.5;
+            for _ in 0..50 {
+                if let Some(s) = sample(&m, &up(), &wo, &mut rng) {
+                    let f = evaluate(&m, &up(), &wo, &s.direction);
+                    let p = pdf(&m, &up(), &wo, &s.direction);
+                    let cos = normal3::dot(&up(), &s.direction).abs();
+                    assert!((s.pdf - p).abs() < 1e-6 * p.max(1.0));
+                    assert!((s.weight.red - f.red * cos / p).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_conserve_energy_for_smooth_white_mirror() {
+        let mut m = with(microfacet(0.1));
+        m.color = color::color(1.0, 1.0, 1.0);
+        let mut rng = random::random(11);
+        let mut sum = 0.0;
+        for _ in 0..2000 {
+            if let Some(s) = sample(&m, &up(), &straight_up(), &mut rng) {
+                sum += s.weight.red;
+            }
+        }
+        let mean = sum / 2000.0;
+        assert!(mean > 0.95 && mean <= 1.0 + 1e-9);
+    }
+
+    #[test]
+    fn should_compute_fresnel_reflectance() {
+        assert!(float::equals(fresnel_dielectric(1.0, 1.0, 1.5), 0.04));
+        // Grazing from inside glass is totally reflected.
+        assert!(float::equals(fresnel_dielectric(-0.1, 1.0, 1.5), 1.0));
+        let eta = color::color(0.2, 1.0, 1.5);
+        let k = color::color(3.0, 0.0, 0.0);
+        let r = fresnel_conductor(1.0, &eta, &k);
+        let normal = |n: Float, k: Float| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        assert!(float::equals(r.red, normal(0.2, 3.0)));
+        assert!(float::equals(r.green, 0.0));
+        assert!(float::equals(r.blue, 0.04));
+    }
+
+    #[test]
+    fn should_mostly_transmit_through_smooth_glass() {
+        let mut m = with(dielectric(0.05));
+        m.refractive_index = 1.5;
+        let mut rng = random::random(13);
+        let mut through = 0;
+        for _ in 0..1000 {
+            let s = sample(&m, &up(), &straight_up(), &mut rng).unwrap();
+            if s.direction.y < 0.0 {
+                through += 1;
+                assert!(s.direction.y < -0.99);
+            }
+        }
+        assert!(through > 930 && through < 990);
+    }
+}
@@ -0,0 +1,311 @@
+// Surface detail without extra geometry: both kinds of perturbation tilt
+// the object space normal and leave the surface itself where it is.
+//
+// Bumps are height fields over object space. The normal leans away from
+// the direction the height rises in, as if the surface had been pushed out
+// along it by the height.
+//
+// Normal maps store a tangent space normal per texel, x along increasing u,
+// y along increasing v and z out of the surface, each mapped from [-1, 1]
+// to [0, 1]. The tangent frame is worked out from how the texture's uv
+// mapping changes across the surface, or from the texture coordinates of a
+// shape that has its own.
+
+use crate::color;
+use crate::float::Float;
+use crate::texture;
+use crate::tuple;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bump {
+    // Perlin noise; scale shrinks the features, amplitude deepens them.
+    Noise { scale: Float, amplitude: Float },
+    // sin(frequency * x) + sin(frequency * y) + sin(frequency * z).
+    Waves { frequency: Float, amplitude: Float },
+}
+
+// Step for the finite differences below.
+const EPSILON: Float = 0.0001;
+
+pub fn noise(scale: Float, amplitude: Float) -> Bump {
+    return Bump::Noise { scale, amplitude };
+}
+
+pub fn waves(frequency: Float, amplitude: Float) -> Bump {
+    return Bump::Waves {
+        frequency,
+        amplitude,
+    };
+}
+
+pub fn height(b: &Bump, p: &tuple::Tuple) -> Float {
+    match b {
+        Bump::Noise { scale, amplitude } => {
+            return amplitude * perlin(p.x * scale, p.y * scale, p.z * scale);
+        }
+        Bump::Waves {
+            frequency,
+            amplitude,
+        } => {
+            let f = frequency;
+            return amplitude * ((f * p.x).sin() + (f * p.y).sin() + (f * p.z).sin());
+        }
+    }
+}
+
+// The object space normal at p, tilted by the height field's slope.
+pub fn perturb(b: &Bump, p: &tuple::Tuple, normal: &tuple::Tuple) -> tuple::Tuple {
+    let n = tuple::normalize(normal);
+    let slope = |axis: tuple::Tuple| {
+        let ahead = height(b, &tuple::add(p, &tuple::multiply(&axis, EPSILON)));
+        let behind = height(b, &tuple::subtract(p, &tuple::multiply(&axis, EPSILON)));
+        return (ahead - behind) / (2.0 * EPSILON);
+    };
+    let gradient = tuple::vector(
+        slope(tuple::vector(1.0, 0.0, 0.0)),
+        slope(tuple::vector(0.0, 1.0, 0.0)),
+        slope(tuple::vector(0.0, 0.0, 1.0)),
+    );
+    // Only the part of the slope along the surface tilts it.
+    let along = tuple::subtract(&gradient, &tuple::multiply(&n, tuple::dot(&gradient, &n)));
+    return tuple::normalize(&tuple::subtract(&n, &along));
+}
+
+// Unit vectors along increasing u and v at p, with the normal's component
+// removed. None where the mapping is degenerate, such as at the poles of a
+// spherical map.
+pub fn tangents(
+    mapping: texture::Mapping,
+    p: &tuple::Tuple,
+    normal: &tuple::Tuple,
+) -> Option<(tuple::Tuple, tuple::Tuple)> {
+    let n = tuple::normalize(normal);
+    let (a, b) = orthonormal(&n);
+    // Texture coordinates wrap, so take the shorter way round.
+    let rate = |axis: &tuple::Tuple| {
+        let (u1, v1) = texture::uv_at(mapping, &tuple::add(p, &tuple::multiply(axis, EPSILON)));
+        let (u0, v0) = texture::uv_at(
+            mapping,
+            &tuple::subtract(p, &tuple::multiply(axis, EPSILON)),
+        );
+        let (du, dv) = (u1 - u0, v1 - v0);
+        return (
+            (du - du.round()) / (2.0 * EPSILON),
+            (dv - dv.round()) / (2.0 * EPSILON),
+        );
+    };
+    let (du_a, dv_a) = rate(&a);
+    let (du_b, dv_b) = rate(&b);
+    let det = du_a * dv_b - du_b * dv_a;
+    if det.abs() < 1e-9 {
+        return None;
+    }
+    // Inverting the uv rates gives how the point moves per unit of u and v.
+    let dpdu = tuple::add(
+        &tuple::multiply(&a, dv_b / det),
+        &tuple::multiply(&b, -dv_a / det),
+    );
+    let dpdv = tuple::add(
+        &tuple::multiply(&a, -du_b / det),
+        &tuple::multiply(&b, du_a / det),
+    );
+    return frame(&dpdu, &dpdv, &n);
+}
+
+// Unit tangent and bitangent from dpdu and dpdv, how the point moves per
+// unit of u and v, made perpendicular to unit normal n. None where u runs
+// along the normal.
+pub fn frame(
+    dpdu: &tuple::Tuple,
+    dpdv: &tuple::Tuple,
+    n: &tuple::Tuple,
+) -> Option<(tuple::Tuple, tuple::Tuple)> {
+    let along = tuple::subtract(dpdu, &tuple::multiply(n, tuple::dot(dpdu, n)));
+    if tuple::magnitude(&along) < 1e-9 * tuple::magnitude(dpdu) {
+        return None;
+    }
+    let t = tuple::normalize(&along);
+    // Keep the mapping's handedness, which may be mirrored.
+    let mut bitangent = tuple::cross(n, &t);
+    if tuple::dot(&bitangent, dpdv) < 0.0 {
+        bitangent = tuple::negate(&bitangent);
+    }
+    return Some((t, bitangent));
+}
+
+// The object space normal at p, replaced by the one stored in the normal
+// map there.
+pub fn normal_map(map: &texture::Texture, p: &tuple::Tuple, normal: &tuple::Tuple) -> tuple::Tuple {
+    let n = tuple::normalize(normal);
+    let (t, b) = match tangents(map.mapping, p, &n) {
+        Some(frame) => frame,
+        None => return n,
+    };
+    return apply(&texture::color_at(map, p), &t, &b, &n);
+}
+
+// Like normal_map, for a shape with its own texture coordinates: (u, v) is
+// where to read the map and dpdu and dpdv set the tangent frame.
+pub fn normal_map_uv(
+    map: &texture::Texture,
+    (u, v): (Float, Float),
+    (dpdu, dpdv): (tuple::Tuple, tuple::Tuple),
+    normal: &tuple::Tuple,
+) -> tuple::Tuple {
+    let n = tuple::normalize(normal);
+    let (t, b) = match frame(&dpdu, &dpdv, &n) {
+        Some(frame) => frame,
+        None => return n,
+    };
+    return apply(&texture::color_at_uv(map, u, v), &t, &b, &n);
+}
+
+// The tangent space normal stored as c, in the frame t, b, n.
+fn apply(c: &color::Color, t: &tuple::Tuple, b: &tuple::Tuple, n: &tuple::Tuple) -> tuple::Tuple {
+    let (x, y, z) = (2.0 * c.red - 1.0, 2.0 * c.green - 1.0, 2.0 * c.blue - 1.0);
+    let mapped = tuple::add(
+        &tuple::add(&tuple::multiply(t, x), &tuple::multiply(b, y)),
+        &tuple::multiply(n, z),
+    );
+    if tuple::magnitude(&mapped) == 0.0 {
+        return *n;
+    }
+    return tuple::normalize(&mapped);
+}
+
+// Two unit vectors perpendicular to n and to each other.
+fn orthonormal(n: &tuple::Tuple) -> (tuple::Tuple, tuple::Tuple) {
+    let helper = if n.x.abs() > 0.9 {
+        tuple::vector(0.0, 1.0, 0.0)
+    } else {
+        tuple::vector(1.0, 0.0, 0.0)
+    };
+    let a = tuple::normalize(&tuple::cross(n, &helper));
+    return (a, tuple::cross(n, &a));
+}
+
+// Ken Perlin's improved noise, roughly in [-1, 1] and zero at every lattice
+// point. Gradients come from hashing the lattice coordinates rather than a
+// permutation table.
+pub fn perlin(x: Float, y: Float, z: Float) -> Float {
+    let (xi, yi, zi) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - xi, y - yi, z - zi);
+    let (xi, yi, zi) = (xi as i64, yi as i64, zi as i64);
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        let g = gradient(xi + dx, yi + dy, zi + dz);
+        return g.0 * (fx - dx as Float) + g.1 * (fy - dy as Float) + g.2 * (fz - dz as Float);
+    };
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let lerp = |a: Float, b: Float, t: Float| a + t * (b - a);
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+    return lerp(lerp(x00, x10, v), lerp(x01, x11, v), w);
+}
+
+fn fade(t: Float) -> Float {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+// One of the twelve cube edge directions Perlin's improved noise uses.
+fn gradient(x: i64, y: i64, z: i64) -> (Float, Float, Float) {
+    const EDGES: [(Float, Float, Float); 12] = [
+        (1.0, 1.0, 0.0),
+        (-1.0, 1.0, 0.0),
+        (1.0, -1.0, 0.0),
+        (-1.0, -1.0, 0.0),
+        (1.0, 0.0, 1.0),
+        (-1.0, 0.0, 1.0),
+        (1.0, 0.0, -1.0),
+        (-1.0, 0.0, -1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, -1.0, 1.0),
+        (0.0, 1.0, -1.0),
+        (0.0, -1.0, -1.0),
+    ];
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9)
+        ^ (z as u64).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    h = h.wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    h ^= h >> 32;
+    return EDGES[(h % 12) as usize];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas;
+    use crate::color;
+    use crate::float;
+
+    #[test]
+    fn should_vanish_on_lattice_and_stay_bounded() {
+        assert!(perlin(3.0, -2.0, 7.0) == 0.0);
+        let mut nonzero = false;
+        for i in 0..1000 {
+            let t = i as Float * 0.137;
+            let n = perlin(t, t * 0.7 + 0.3, -t * 1.3);
+            assert!(n.abs() <= 1.5);
+            assert!(n == perlin(t, t * 0.7 + 0.3, -t * 1.3));
+            nonzero = nonzero || n.abs() > 0.1;
+        }
+        assert!(nonzero);
+    }
+
+    #[test]
+    fn should_tilt_normal_against_slope() {
+        let n = tuple::vector(0.0, 1.0, 0.0);
+        let p = tuple::point(0.0, 1.0, 0.0);
+        // Flat height fields leave the normal alone.
+        let flat = perturb(&waves(3.0, 0.0), &p, &n);
+        assert!(tuple::equals(&flat, &n));
+        // At the origin the waves rise by 0.1 per unit along x and z.
+        let tilted = perturb(&waves(1.0, 0.1), &p, &n);
+        let expected = tuple::normalize(&tuple::vector(-0.1, 1.0, -0.1));
+        assert!(tuple::equals(&tilted, &expected));
+    }
+
+    #[test]
+    fn should_derive_tangents_from_uvs() {
+        // Seen from -z, spherical u grows towards +x and v upwards.
+        let p = tuple::point(0.0, 0.0, -1.0);
+        let n = tuple::vector(0.0, 0.0, -1.0);
+        let (t, b) = tangents(texture::Mapping::Spherical, &p, &n).unwrap();
+        assert!(tuple::equals(&t, &tuple::vector(1.0, 0.0, 0.0)));
+        assert!(tuple::equals(&b, &tuple::vector(0.0, 1.0, 0.0)));
+        // Planar maps put u along x and v along z.
+        let up = tuple::vector(0.0, 1.0, 0.0);
+        let (t, b) = tangents(texture::Mapping::Planar, &tuple::point(0.3, 0.0, 0.6), &up).unwrap();
+        assert!(tuple::equals(&t, &tuple::vector(1.0, 0.0, 0.0)));
+        assert!(tuple::equals(&b, &tuple::vector(0.0, 0.0, 1.0)));
+        // The pole of a sphere has no usable frame.
+        let pole = tuple::point(0.0, 1.0, 0.0);
+        assert!(tangents(texture::Mapping::Spherical, &pole, &up).is_none());
+    }
+
+    #[test]
+    fn should_read_normals_from_map() {
+        let mut image = canvas::canvas(1, 1);
+        canvas::write_pixel(&mut image, 0, 0, &color::color(0.5, 0.5, 1.0));
+        let mut map = texture::texture(image, texture::Mapping::Spherical);
+        let p = tuple::point(0.0, 0.0, -1.0);
+        let n = tuple::vector(0.0, 0.0, -1.0);
+        assert!(tuple::equals(&normal_map(&map, &p, &n), &n));
+        // Pointing fully along u.
+        canvas::write_pixel(&mut map.image, 0, 0, &color::color(1.0, 0.5, 0.5));
+        let along_u = normal_map(&map, &p, &n);
+        assert!(tuple::equals(&along_u, &tuple::vector(1.0, 0.0, 0.0)));
+        let half = Float::sqrt(2.0) / 2.0;
+        canvas::write_pixel(
+            &mut map.image,
+            0,
+            0,
+            &color::color(0.5, 0.5 + half / 2.0, 0.5 + half / 2.0),
+        );
+        let leaning = normal_map(&map, &p, &n);
+        assert!(float::equals(leaning.y, half));
+        assert!(float::equals(leaning.z, -half));
+    }
+}
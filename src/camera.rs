@@ -0,0 +1,627 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::canvas;
+use crate::color;
+use crate::filter;
+use crate::float;
+use crate::float::Float;
+use crate::lens;
+use crate::matrix4;
+use crate::point3;
+use crate::progress;
+use crate::random;
+use crate::ray;
+use crate::sampler;
+use crate::stats;
+use crate::vector3;
+
+pub struct Camera {
+    pub hsize: usize,
+    pub vsize: usize,
+    pub field_of_view: Float,
+    pub transform: matrix4::Matrix4,
+    pub pixel_size: Float,
+    pub half_width: Float,
+    pub half_height: Float,
+    pub samples: usize,
+    pub max_samples: usize,
+    pub tolerance: Float,
+    pub sampler: sampler::Sampler,
+    pub filter: filter::Filter,
+    // Thin lens: an aperture of 0 is a pinhole with everything in focus.
+    // Otherwise rays start across an aperture this wide and converge on the
+    // plane focal_distance in front of the camera.
+    pub aperture: Float,
+    pub focal_distance: Float,
+    pub aperture_shape: lens::Aperture,
+    // Rays are spread over this interval of time, blurring shapes in
+    // motion. Equal times freeze the scene at that instant.
+    pub shutter_open: Float,
+    pub shutter_close: Float,
+    // How far new rays start off a surface, relative to the size of the
+    // coordinates there (see ray::offset_distance).
+    pub ray_epsilon: Float,
+    pub seed: u64,
+    pub threads: usize,
+    pub progress: bool,
+}
+
+pub fn camera(hsize: usize, vsize: usize, field_of_view: Float) -> Camera {
+    let mut c = Camera {
+        hsize,
+        vsize,
+        field_of_view,
+        transform: matrix4::IDENTITY,
+        pixel_size: 0.0,
+        half_width: 0.0,
+        half_height: 0.0,
+        samples: 1,
+        max_samples: 0,
+        tolerance: 0.005,
+        sampler: sampler::Sampler::Grid,
+        filter: filter::box_filter(),
+        aperture: 0.0,
+        focal_distance: 1.0,
+        aperture_shape: lens::disc(),
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+        ray_epsilon: float::RAY_EPSILON,
+        seed: 0,
+        threads: 1,
+        progress: false,
+    };
+    set_size(&mut c, hsize, vsize);
+    return c;
+}
+
+// The field of view spans the longer side, so changing the resolution keeps
+// the framing as long as the aspect ratio stays the same.
+pub fn set_size(camera: &mut Camera, hsize: usize, vsize: usize) {
+    let half_view = (camera.field_of_view / 2.0).tan();
+    let aspect = hsize as Float / vsize as Float;
+    if aspect >= 1.0 {
+        camera.half_width = half_view;
+        camera.half_height = half_view / aspect;
+    } else {
+        camera.half_width = half_view * aspect;
+        camera.half_height = half_view;
+    }
+    camera.hsize = hsize;
+    camera.vsize = vsize;
+    camera.pixel_size = (camera.half_width * 2.0) / hsize as Float;
+}
+
+// The scene parser rejects views that cannot be inverted, such as looking
+// from a point to itself.
+fn inverse_transform(camera: &Camera) -> matrix4::Matrix4 {
+    return matrix4::inverse(&camera.transform).expect("camera transform is not invertible");
+}
+
+pub fn ray_for_pixel(camera: &Camera, px: usize, py: usize) -> ray::Ray {
+    return ray_for_sample(camera, px as Float + 0.5, py as Float + 0.5);
+}
+
+// x and y are continuous canvas coordinates, so (0.5, 0.5) is the center of
+// the top left pixel. The ray leaves from the center of the lens.
+pub fn ray_for_sample(camera: &Camera, x: Float, y: Float) -> ray::Ray {
+    let inv = inverse_transform(camera);
+    return cast(camera, &inv, x, y, (0.0, 0.0), camera.shutter_open);
+}
+
+// lens is a point on the unit lens, as from lens::point.
+fn cast(
+    camera: &Camera,
+    inv: &matrix4::Matrix4,
+    x: Float,
+    y: Float,
+    lens: (Float, Float),
+    time: Float,
+) -> ray::Ray {
+    let world_x = camera.half_width - x * camera.pixel_size;
+    let world_y = camera.half_height - y * camera.pixel_size;
+    let radius = camera.aperture / 2.0;
+    // Camera space is scaled so the canvas lies at z = -1; what is seen
+    // through it at the focal distance stays sharp.
+    let (pixel, origin) = if radius > 0.0 {
+        let d = camera.focal_distance;
+        (
+            point3::point3(world_x * d, world_y * d, -d),
+            point3::point3(lens.0 * radius, lens.1 * radius, 0.0),
+        )
+    } else {
+        (point3::point3(world_x, world_y, -1.0), point3::ORIGIN)
+    };
+    let pixel = matrix4::multiply_point(inv, &pixel);
+    let origin = matrix4::multiply_point(inv, &origin);
+    let direction = vector3::normalize(&(pixel - origin));
+    return ray::ray_at_time(&origin, &direction, time);
+}
+
+// Each pixel is the filter-weighted average of camera.samples rays spread
+// over the filter's footprint around the pixel center. trace also gets the
+// pixel's random number generator, for integrators that sample randomly.
+pub fn render<F>(camera: &Camera, trace: F) -> canvas::Canvas
+where
+    F: Fn(&ray::Ray, &mut random::Random) -> color::Color + Sync,
+{
+    return render_with_sample_counts(camera, trace).0;
+}
+
+// Second canvas shows how many samples each pixel took, as a fraction of the
+// largest budget (white means the pixel used every sample it could).
+//
+// Rows are handed out to camera.threads workers. Every pixel seeds its own
+// random numbers, so the image does not depend on the thread count.
+pub fn render_with_sample_counts<F>(camera: &Camera, trace: F) -> (canvas::Canvas, canvas::Canvas)
+where
+    F: Fn(&ray::Ray, &mut random::Random) -> color::Color + Sync,
+{
+    let mut image = canvas::canvas(camera.hsize, camera.vsize);
+    let mut counts = canvas::canvas(camera.hsize, camera.vsize);
+    let inv = inverse_transform(camera);
+    let budget = sampler::sample_count(&camera.sampler, camera.samples).max(camera.max_samples);
+    let next_row = AtomicUsize::new(0);
+    let report = if camera.progress {
+        Some(progress::progress(camera.vsize))
+    } else {
+        None
+    };
+    let rows = thread::scope(|scope| {
+        let mut workers = Vec::new();
+        for _ in 0..camera.threads.max(1) {
+            workers.push(scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let y = next_row.fetch_add(1, Ordering::Relaxed);
+                    if y >= camera.vsize {
+                        return done;
+                    }
+                    let mut row = Vec::with_capacity(camera.hsize);
+                    for x in 0..camera.hsize {
+                        row.push(render_pixel(camera, &inv, x, y, &trace));
+                    }
+                    done.push((y, row));
+                    stats::flush();
+                    if let Some(p) = &report {
+                        progress::step(p);
+                    }
+                }
+            }));
+        }
+        let mut rows = Vec::with_capacity(camera.vsize);
+        for w in workers {
+            rows.extend(w.join().unwrap());
+        }
+        return rows;
+    });
+    for (y, row) in rows {
+        for (x, (c, n)) in row.into_iter().enumerate() {
+            let level = n as Float / budget as Float;
+            canvas::write_pixel(&mut image, x, y, &c);
+            canvas::write_pixel(&mut counts, x, y, &color::color(level, level, level));
+        }
+    }
+    return (image, counts);
+}
+
+// Samples are taken in batches of camera.samples. After each batch the
+// standard error of the pixel's luminance is checked, and sampling stops once
+// it drops below camera.tolerance or camera.max_samples is reached. Batches
+// after the first are cut short so the budget is never exceeded.
+fn render_pixel<F>(
+    camera: &Camera,
+    inv: &matrix4::Matrix4,
+    x: usize,
+    y: usize,
+    trace: &F,
+) -> (color::Color, usize)
+where
+    F: Fn(&ray::Ray, &mut random::Random) -> color::Color,
+{
+    let mut rng = random::for_pixel(camera.seed, x, y);
+    let extent = 2.0 * filter::radius(&camera.filter);
+    let mut sum = color::color(0.0, 0.0, 0.0);
+    let mut total = 0.0;
+    let mut n = 0;
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    let mut batch_sampler = camera.sampler;
+    let mut first = true;
+    loop {
+        let mut pixel_samples = sampler::samples(&batch_sampler, camera.samples, &mut rng);
+        if !first {
+            pixel_samples.truncate(camera.max_samples - n);
+        }
+        first = false;
+        let before = n;
+        let lens_samples = lens_samples(camera, &batch_sampler, pixel_samples.len(), &mut rng);
+        let times = shutter_times(camera, pixel_samples.len(), &mut rng);
+        for (i, (u, v)) in pixel_samples.into_iter().enumerate() {
+            let dx = (u - 0.5) * extent;
+            let dy = (v - 0.5) * extent;
+            let weight = filter::weight(&camera.filter, dx, dy);
+            if weight == 0.0 {
+                continue;
+            }
+            n += 1;
+            let r = cast(
+                camera,
+                inv,
+                x as Float + 0.5 + dx,
+                y as Float + 0.5 + dy,
+                lens_samples[i],
+                times[i],
+            );
+            stats::primary_ray();
+            let c = trace(&r, &mut rng);
+            sum = color::add(&sum, &color::multiply(&c, weight));
+            total += weight;
+
+            let l = color::luminance(&c);
+            let delta = l - mean;
+            mean += delta / n as Float;
+            m2 += delta * (l - mean);
+        }
+        // A filter that gives every sample of a batch zero weight would give
+        // the next batch none either.
+        if n >= camera.max_samples || n == before {
+            break;
+        }
+        // One sample says nothing about the variance, so keep going.
+        if n >= 2 {
+            let error = (m2 / (n - 1) as Float / n as Float).sqrt();
+            if error <= camera.tolerance {
+                break;
+            }
+        }
+        // Repeating a regular grid would add no new information.
+        if batch_sampler == sampler::Sampler::Grid {
+            batch_sampler = sampler::Sampler::Jittered;
+        }
+    }
+    if total == 0.0 {
+        return (color::color(0.0, 0.0, 0.0), n);
+    }
+    return (color::multiply(&sum, 1.0 / total), n);
+}
+
+// Points on the unit lens, one per pixel sample. They are stratified like
+// the pixel samples but shuffled, so that where a ray crosses the pixel says
+// nothing about where it crosses the lens. A pinhole draws no random numbers
+// and so renders exactly as before lenses existed.
+fn lens_samples(
+    camera: &Camera,
+    s: &sampler::Sampler,
+    count: usize,
+    rng: &mut random::Random,
+) -> Vec<(Float, Float)> {
+    if camera.aperture <= 0.0 {
+        return vec![(0.0, 0.0); count];
+    }
+    let mut v = sampler::samples(s, camera.samples, rng);
+    for i in (1..v.len()).rev() {
+        let j = (random::next_u64(rng) % (i as u64 + 1)) as usize;
+        v.swap(i, j);
+    }
+    return v
+        .iter()
+        .map(|(u, v)| lens::point(&camera.aperture_shape, *u, *v))
+        .collect();
+}
+
+// One time per pixel sample, jittered within equal slices of the shutter
+// interval and shuffled like the lens samples. An instantaneous shutter
+// draws no random numbers.
+fn shutter_times(camera: &Camera, count: usize, rng: &mut random::Random) -> Vec<Float> {
+    let (open, close) = (camera.shutter_open, camera.shutter_close);
+    if close <= open {
+        return vec![open; count];
+    }
+    let mut v: Vec<Float> = (0..count)
+        .map(|i| (i as Float + random::next_float(rng)) / count as Float)
+        .collect();
+    for i in (1..v.len()).rev() {
+        let j = (random::next_u64(rng) % (i as u64 + 1)) as usize;
+        v.swap(i, j);
+    }
+    return v.iter().map(|t| open + t * (close - open)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::consts::PI;
+    use crate::matrix;
+    use crate::transform;
+
+    // Page 101
+    #[test]
+    fn should_construct_camera() {
+        let c = camera(160, 120, PI / 2.0);
+        assert!(c.hsize == 160);
+        assert!(c.vsize == 120);
+        assert!(float::equals(c.field_of_view, PI / 2.0));
+        assert!(matrix::equals(&c.transform, &matrix4::IDENTITY));
+        assert!(c.samples == 1);
+    }
+
+    // Page 101
+    #[test]
+    fn should_calculate_pixel_size_horizontal() {
+        let c = camera(200, 125, PI / 2.0);
+        assert!(float::equals(c.pixel_size, 0.01));
+    }
+
+    // Page 101
+    #[test]
+    fn should_calculate_pixel_size_vertical() {
+        let c = camera(125, 200, PI / 2.0);
+        assert!(float::equals(c.pixel_size, 0.01));
+    }
+
+    // Page 103
+    #[test]
+    fn should_cast_ray_through_center() {
+        let c = camera(201, 101, PI / 2.0);
+        let r = ray_for_pixel(&c, 100, 50);
+        assert!(point3::equals(&r.origin, &point3::point3(0.0, 0.0, 0.0)));
+        assert!(vector3::equals(
+            &r.direction,
+            &vector3::vector3(0.0, 0.0, -1.0)
+        ));
+    }
+
+    // Page 103
+    #[test]
+    fn should_cast_ray_through_corner() {
+        let c = camera(201, 101, PI / 2.0);
+        let r = ray_for_pixel(&c, 0, 0);
+        assert!(point3::equals(&r.origin, &point3::point3(0.0, 0.0, 0.0)));
+        assert!(vector3::equals(
+            &r.direction,
+            &vector3::vector3(0.66519, 0.33259, -0.66851)
+        ));
+    }
+
+    // Page 103
+    #[test]
+    fn should_cast_ray_when_transformed() {
+        let mut c = camera(201, 101, PI / 2.0);
+        c.transform = matrix::multiply(
+            &transform::rotation_y(PI / 4.0),
+            &transform::translation(0.0, -2.0, 5.0),
+        );
+        let r = ray_for_pixel(&c, 100, 50);
+        let two: Float = 2.0;
+        assert!(point3::equals(&r.origin, &point3::point3(0.0, 2.0, -5.0)));
+        assert!(vector3::equals(
+            &r.direction,
+            &vector3::vector3(two.sqrt() / 2.0, 0.0, -two.sqrt() / 2.0)
+        ));
+    }
+
+    #[test]
+    fn should_normalize_filter_weights() {
+        let white = color::color(1.0, 1.0, 1.0);
+        for f in [
+            filter::box_filter(),
+            filter::tent(),
+            filter::gaussian(),
+            filter::mitchell(),
+        ] {
+            let mut c = camera(4, 3, PI / 2.0);
+            c.samples = 16;
+            c.sampler = sampler::Sampler::Jittered;
+            c.filter = f;
+            let image = render(&c, |_, _| white);
+            assert!(color::equals(&canvas::pixel_at(&image, 2, 1), &white));
+        }
+    }
+
+    #[test]
+    fn should_antialias_edges() {
+        // Left half of the view is white, right half black; the pixel
+        // straddling the boundary should come out grey.
+        let mut c = camera(3, 1, PI / 2.0);
+        c.samples = 64;
+        c.sampler = sampler::Sampler::Grid;
+        let image = render(&c, |r, _| {
+            if r.direction.x > 0.0 {
+                color::color(1.0, 1.0, 1.0)
+            } else {
+                color::color(0.0, 0.0, 0.0)
+            }
+        });
+        assert!(float::equals(canvas::pixel_at(&image, 0, 0).red, 1.0));
+        assert!(float::equals(canvas::pixel_at(&image, 1, 0).red, 0.5));
+        assert!(float::equals(canvas::pixel_at(&image, 2, 0).red, 0.0));
+    }
+
+    #[test]
+    fn should_render_deterministically() {
+        let mut c = camera(5, 5, PI / 2.0);
+        c.samples = 4;
+        c.sampler = sampler::Sampler::Jittered;
+        c.seed = 9;
+        let trace = |r: &ray::Ray, _: &mut random::Random| {
+            color::color(r.direction.x.abs(), r.direction.y.abs(), 0.0)
+        };
+        let a = render(&c, trace);
+        let b = render(&c, trace);
+        for x in 0..5 {
+            for y in 0..5 {
+                assert!(color::equals(
+                    &canvas::pixel_at(&a, x, y),
+                    &canvas::pixel_at(&b, x, y)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn should_not_refine_without_budget() {
+        let mut c = camera(3, 3, PI / 2.0);
+        c.samples = 4;
+        let (_, counts) =
+            render_with_sample_counts(&c, |r, _| color::color(r.direction.x, 0.0, 0.0));
+        for x in 0..3 {
+            for y in 0..3 {
+                assert!(float::equals(canvas::pixel_at(&counts, x, y).red, 1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn should_stop_early_on_flat_pixels() {
+        let mut c = camera(3, 1, PI / 2.0);
+        c.samples = 4;
+        c.max_samples = 64;
+        c.sampler = sampler::Sampler::Jittered;
+        let (image, counts) = render_with_sample_counts(&c, |r, _| {
+            if r.direction.x > 0.0 {
+                color::color(1.0, 1.0, 1.0)
+            } else {
+                color::color(0.0, 0.0, 0.0)
+            }
+        });
+        assert!(float::equals(
+            canvas::pixel_at(&counts, 0, 0).red,
+            4.0 / 64.0
+        ));
+        assert!(float::equals(canvas::pixel_at(&counts, 1, 0).red, 1.0));
+        assert!(float::equals(
+            canvas::pixel_at(&counts, 2, 0).red,
+            4.0 / 64.0
+        ));
+        assert!((canvas::pixel_at(&image, 1, 0).red - 0.5).abs() < 0.15);
+    }
+
+    #[test]
+    fn should_refine_single_samples() {
+        let mut c = camera(1, 1, PI / 2.0);
+        c.samples = 1;
+        c.max_samples = 10;
+        c.tolerance = 0.0;
+        let noisy = |_: &ray::Ray, rng: &mut random::Random| {
+            let v = random::next_float(rng);
+            color::color(v, v, v)
+        };
+        let (_, counts) = render_with_sample_counts(&c, noisy);
+        assert!(float::equals(canvas::pixel_at(&counts, 0, 0).red, 1.0));
+        // A flat pixel stops as soon as there is a variance to check.
+        let (_, counts) = render_with_sample_counts(&c, |_, _| color::color(1.0, 1.0, 1.0));
+        assert!(float::equals(
+            canvas::pixel_at(&counts, 0, 0).red,
+            2.0 / 10.0
+        ));
+    }
+
+    #[test]
+    fn should_not_exceed_sample_budget() {
+        // Batches of 4 never land on a budget of 10.
+        let mut c = camera(1, 1, PI / 2.0);
+        c.samples = 4;
+        c.max_samples = 10;
+        c.tolerance = 0.0;
+        c.sampler = sampler::Sampler::Jittered;
+        let (_, counts) = render_with_sample_counts(&c, |_, rng| {
+            let v = random::next_float(rng);
+            color::color(v, v, v)
+        });
+        assert!(float::equals(canvas::pixel_at(&counts, 0, 0).red, 1.0));
+    }
+
+    #[test]
+    fn should_render_same_image_on_any_thread_count() {
+        let mut c = camera(7, 5, PI / 2.0);
+        c.samples = 4;
+        c.sampler = sampler::Sampler::Jittered;
+        let trace = |r: &ray::Ray, _: &mut random::Random| {
+            color::color(r.direction.x.abs(), r.direction.y.abs(), 0.0)
+        };
+        let a = render(&c, trace);
+        c.threads = 3;
+        let b = render(&c, trace);
+        for x in 0..7 {
+            for y in 0..5 {
+                assert!(color::equals(
+                    &canvas::pixel_at(&a, x, y),
+                    &canvas::pixel_at(&b, x, y)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn should_focus_at_focal_distance() {
+        let mut c = camera(201, 101, PI / 2.0);
+        c.aperture = 0.5;
+        c.focal_distance = 3.0;
+        let inv = inverse_transform(&c);
+        for lens in [(1.0, 0.0), (0.0, -1.0), (-0.6, 0.8)] {
+            let r = cast(&c, &inv, 100.5, 50.5, lens, 0.0);
+            assert!(float::equals(r.origin.x, lens.0 * 0.25));
+            assert!(float::equals(r.origin.y, lens.1 * 0.25));
+            let t = -3.0 / r.direction.z;
+            assert!(point3::equals(
+                &ray::position(&r, t),
+                &point3::point3(0.0, 0.0, -3.0)
+            ));
+        }
+    }
+
+    #[test]
+    fn should_spread_rays_over_lens() {
+        // Rays leaving the right half of the lens see white.
+        let trace = |r: &ray::Ray, _: &mut random::Random| {
+            if r.origin.x > 0.0 {
+                color::color(1.0, 1.0, 1.0)
+            } else {
+                color::color(0.0, 0.0, 0.0)
+            }
+        };
+        let mut c = camera(1, 1, PI / 2.0);
+        c.samples = 256;
+        c.sampler = sampler::Sampler::Jittered;
+        let pinhole = render(&c, trace);
+        assert!(float::equals(canvas::pixel_at(&pinhole, 0, 0).red, 0.0));
+        for shape in [lens::disc(), lens::polygon(5)] {
+            c.aperture = 1.0;
+            c.aperture_shape = shape;
+            let blurred = render(&c, trace);
+            assert!((canvas::pixel_at(&blurred, 0, 0).red - 0.5).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn should_spread_rays_over_shutter() {
+        let mut c = camera(1, 1, PI / 2.0);
+        c.samples = 64;
+        c.shutter_open = 0.25;
+        c.shutter_close = 0.75;
+        let image = render(&c, |r, _| color::color(r.time, r.time, r.time));
+        assert!((canvas::pixel_at(&image, 0, 0).red - 0.5).abs() < 0.01);
+        // One time in each of the 64 slices of the half open shutter.
+        let mut rng = random::random(1);
+        let times = shutter_times(&c, 64, &mut rng);
+        for i in 0..64 {
+            let start = 0.25 + i as Float / 128.0;
+            let end = start + 1.0 / 128.0;
+            assert!(times.iter().filter(|t| **t >= start && **t < end).count() == 1);
+        }
+        c.shutter_close = 0.25;
+        assert!(shutter_times(&c, 3, &mut rng) == vec![0.25; 3]);
+    }
+
+    #[test]
+    fn should_keep_framing_when_resized() {
+        let mut c = camera(200, 125, PI / 2.0);
+        set_size(&mut c, 400, 250);
+        assert!(c.hsize == 400);
+        assert!(float::equals(c.pixel_size, 0.005));
+        let r = ray_for_pixel(&c, 0, 0);
+        let d = ray_for_sample(&camera(200, 125, PI / 2.0), 0.25, 0.25).direction;
+        assert!(vector3::equals(&r.direction, &d));
+    }
+}
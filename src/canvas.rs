@@ -1,5 +1,6 @@
 use crate::array2d;
 use crate::color;
+use crate::float::Float;
 
 pub type Canvas = array2d::Array2D<color::Color>;
 
@@ -15,13 +16,8 @@ pub fn write_pixel(canvas: &mut Canvas, x: usize, y: usize, color: &color::Color
     canvas[x][y] = *color;
 }
 
-pub fn color_normalize(mut c: f64) -> i32 {
-    if c < 0.0 {
-        c = 0.0;
-    } else if c > 1.0 {
-        c = 1.0;
-    }
-    return (c * 255.0).round() as i32;
+pub fn color_normalize(c: Float) -> i32 {
+    return (c.clamp(0.0, 1.0) * 255.0).round() as i32;
 }
 
 pub fn to_ppm(canvas: &Canvas) -> Vec<String> {
@@ -31,18 +27,16 @@ pub fn to_ppm(canvas: &Canvas) -> Vec<String> {
     v.push(String::from("255"));
     for y in 0..canvas.height() {
         let mut line = String::with_capacity(70);
-        let mut length = 0;
         for x in 0..canvas.width() {
-            if length % 5 == 0 {
-                if length != 0 {
+            if x % 5 == 0 {
+                if x != 0 {
                     v.push(line);
                     line = String::with_capacity(70);
                 }
             } else {
-                line.push_str(" ");
+                line.push(' ');
             }
-            length += 1;
-            let color = pixel_at(&canvas, x, y);
+            let color = pixel_at(canvas, x, y);
             line.push_str(&format!(
                 "{} {} {}",
                 color_normalize(color.red),
@@ -55,6 +49,152 @@ pub fn to_ppm(canvas: &Canvas) -> Vec<String> {
     return v;
 }
 
+// 8-bit RGB PNG. The image data is zlib-wrapped but stored uncompressed,
+// which keeps the encoder tiny at the cost of file size.
+pub fn to_png(canvas: &Canvas) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((canvas.width() * 3 + 1) * canvas.height());
+    for y in 0..canvas.height() {
+        raw.push(0);
+        for x in 0..canvas.width() {
+            let color = pixel_at(canvas, x, y);
+            raw.push(color_normalize(color.red) as u8);
+            raw.push(color_normalize(color.green) as u8);
+            raw.push(color_normalize(color.blue) as u8);
+        }
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(if blocks.peek().is_none() { 1 } else { 0 });
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(canvas.width() as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height() as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    return png;
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    return !crc;
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    return (b << 16) | a;
+}
+
+// Plain (P3) or binary (P6) PPM, scaled so the maximum value becomes 1.0.
+pub fn from_ppm(bytes: &[u8]) -> Result<Canvas, String> {
+    let mut pos = 0;
+    let magic = ppm_token(bytes, &mut pos)?;
+    if magic != "P3" && magic != "P6" {
+        return Err(format!("unsupported PPM type '{}'", magic));
+    }
+    let width = ppm_number(bytes, &mut pos)?;
+    let height = ppm_number(bytes, &mut pos)?;
+    let scale = ppm_number(bytes, &mut pos)?;
+    if width == 0 || height == 0 || scale == 0 || scale > 65535 {
+        return Err(String::from("invalid PPM header"));
+    }
+    let mut c = canvas(width, height);
+    // Binary samples start after exactly one whitespace byte.
+    pos += 1;
+    for y in 0..height {
+        for x in 0..width {
+            let mut rgb = [0.0; 3];
+            for channel in rgb.iter_mut() {
+                let value = if magic == "P3" {
+                    ppm_number(bytes, &mut pos)?
+                } else if scale < 256 {
+                    let b = *bytes.get(pos).ok_or("truncated PPM data")? as usize;
+                    pos += 1;
+                    b
+                } else {
+                    let hi = *bytes.get(pos).ok_or("truncated PPM data")? as usize;
+                    let lo = *bytes.get(pos + 1).ok_or("truncated PPM data")? as usize;
+                    pos += 2;
+                    hi << 8 | lo
+                };
+                *channel = value as Float / scale as Float;
+            }
+            write_pixel(&mut c, x, y, &color::color(rgb[0], rgb[1], rgb[2]));
+        }
+    }
+    return Ok(c);
+}
+
+// Next whitespace separated token, skipping # comments.
+fn ppm_token(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(String::from("truncated PPM data"));
+    }
+    return Ok(String::from_utf8_lossy(&bytes[start..*pos]).to_string());
+}
+
+fn ppm_number(bytes: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let token = ppm_token(bytes, pos)?;
+    match token.parse::<usize>() {
+        Ok(n) => return Ok(n),
+        Err(_) => return Err(format!("expected a number, found '{}'", token)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,10 +212,10 @@ mod tests {
             for h in 0..height {
                 println!("{}, {}", w, h);
                 let actual = &pixel_at(&c, w, h);
-                if !color::equals(&actual, &pixel) {
+                if !color::equals(actual, &pixel) {
                     println!("Wrong: {:?}", actual);
                 }
-                assert!(color::equals(&actual, &pixel));
+                assert!(color::equals(actual, &pixel));
             }
         }
     }
@@ -136,4 +276,73 @@ mod tests {
         assert!(lines[5] == "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153");
         assert!(lines[6] == "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153");
     }
+
+    #[test]
+    fn should_calculate_checksums() {
+        assert!(crc32(b"IEND") == 0xae42_6082);
+        assert!(adler32(b"Wikipedia") == 0x11e6_0398);
+    }
+
+    #[test]
+    fn should_generate_valid_png() {
+        let mut c = canvas(3, 2);
+        write_pixel(&mut c, 1, 0, &color::color(1.0, 0.5, 0.0));
+        let png = to_png(&c);
+        assert!(png[..8] == [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']);
+        assert!(png[12..16] == *b"IHDR");
+        assert!(png[16..20] == 3u32.to_be_bytes());
+        assert!(png[20..24] == 2u32.to_be_bytes());
+        assert!(
+            png[png.len() - 12..] == [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+        // Second pixel of the first scanline, after the filter byte.
+        let idat = 8 + 25 + 8;
+        let pixels = idat + 2 + 5 + 1;
+        assert!(png[pixels + 3..pixels + 6] == [255, 128, 0]);
+    }
+
+    // Bonus chapter: texture mapping
+    #[test]
+    fn should_reject_wrong_magic_number() {
+        assert!(from_ppm(b"P32\n1 1\n255\n0 0 0\n").is_err());
+    }
+
+    #[test]
+    fn should_read_ppm_pixels() {
+        let text = "P3\n\
+                    # a comment\n\
+                    4 3\n\
+                    255\n\
+                    255 127 0  0 127 255  127 255 0  255 255 255\n\
+                    0 0 0  255 0 0  0 255 0  0 0 255\n\
+                    255 255 0  0 255 255  255 0 255  127 127\n\
+                    127\n";
+        let c = from_ppm(text.as_bytes()).ok().unwrap();
+        assert!(c.width() == 4 && c.height() == 3);
+        let p = pixel_at(&c, 0, 0);
+        assert!(color::equals(&p, &color::color(1.0, 127.0 / 255.0, 0.0)));
+        assert!(color::equals(
+            &pixel_at(&c, 3, 2),
+            &color::color(127.0 / 255.0, 127.0 / 255.0, 127.0 / 255.0)
+        ));
+    }
+
+    #[test]
+    fn should_respect_ppm_scale() {
+        let c = from_ppm(b"P3\n2 1\n100\n100 100 100  50 50 50\n")
+            .ok()
+            .unwrap();
+        assert!(color::equals(
+            &pixel_at(&c, 1, 0),
+            &color::color(0.5, 0.5, 0.5)
+        ));
+        let mut binary = b"P6\n2 1\n255\n".to_vec();
+        binary.extend_from_slice(&[255, 0, 51, 0, 255, 0]);
+        let c = from_ppm(&binary).ok().unwrap();
+        assert!(color::equals(
+            &pixel_at(&c, 0, 0),
+            &color::color(1.0, 0.0, 0.2)
+        ));
+        assert!(from_ppm(b"P3\n2 1\n255\n1 2 3\n").is_err());
+    }
 }
@@ -0,0 +1,291 @@
+// Command-line options for the raytracer binary. Exit codes follow the BSD
+// sysexits convention so scripts can tell what went wrong.
+
+use crate::float::Float;
+
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_DATAERR: i32 = 65;
+pub const EXIT_NOINPUT: i32 = 66;
+pub const EXIT_CANTCREAT: i32 = 73;
+pub const EXIT_IOERR: i32 = 74;
+
+pub const USAGE: &str = "\
+Usage: raytracer [OPTIONS] [SCENE]
+
+Renders SCENE (a .yml scene file), or a built-in demo scene if none is given.
+
+Options:
+  -o, --output <PATH>          write the image to PATH instead of stdout
+  -f, --format <ppm|png>       image format (default: from the output
+                               extension, otherwise ppm)
+  -r, --resolution <WxH>       override the camera resolution, e.g. 640x480
+  -s, --samples <N>            samples per pixel
+  -j, --threads <N>            render threads (default: all cores)
+  -i, --integrator <NAME>      whitted (Phong shading, shadows, reflection
+                               and refraction; fast) or path (path tracing
+                               with indirect light; default whitted)
+      --sample-counts <PATH>   also write an image of how many samples each
+                               pixel took (white is the full budget)
+  -d, --max-depth <N>          most reflections and refractions, or bounces
+                               of a path, a ray may take (default 5)
+      --seed <N>               seed for sample placement (default 0)
+      --ray-epsilon <E>        how far new rays start off a surface, relative
+                               to the size of its coordinates (default 1e-9,
+                               or 1e-4 in f32 builds)
+  -q, --quiet                  no progress report or statistics on stderr
+  -h, --help                   print this help
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Ppm,
+    Png,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    Whitted,
+    Path,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub output: Option<String>,
+    pub format: Format,
+    pub resolution: Option<(usize, usize)>,
+    pub samples: Option<usize>,
+    pub threads: Option<usize>,
+    pub integrator: Integrator,
+    pub max_depth: usize,
+    pub seed: u64,
+    pub sample_counts: Option<String>,
+    pub ray_epsilon: Option<Float>,
+    pub quiet: bool,
+    pub help: bool,
+}
+
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut o = Options {
+        scene: None,
+        output: None,
+        format: Format::Ppm,
+        resolution: None,
+        samples: None,
+        threads: None,
+        integrator: Integrator::Whitted,
+        max_depth: 5,
+        seed: 0,
+        sample_counts: None,
+        ray_epsilon: None,
+        quiet: false,
+        help: false,
+    };
+    let mut format = None;
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        // Accept both "--samples 16" and "--samples=16".
+        let (name, inline) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n, Some(v)),
+            _ => (arg, None),
+        };
+        let takes_value = matches!(
+            name,
+            "-o" | "--output"
+                | "-f"
+                | "--format"
+                | "-r"
+                | "--resolution"
+                | "-s"
+                | "--samples"
+                | "-j"
+                | "--threads"
+                | "-i"
+                | "--integrator"
+                | "-d"
+                | "--max-depth"
+                | "--seed"
+                | "--sample-counts"
+                | "--ray-epsilon"
+        );
+        let value = if !takes_value {
+            if inline.is_some() {
+                return Err(format!("{} does not take a value", name));
+            }
+            ""
+        } else if let Some(v) = inline {
+            v
+        } else {
+            i += 1;
+            match args.get(i) {
+                Some(v) => v.as_str(),
+                None => return Err(format!("{} needs a value", name)),
+            }
+        };
+        match name {
+            "-h" | "--help" => o.help = true,
+            "-q" | "--quiet" => o.quiet = true,
+            "-o" | "--output" => o.output = Some(String::from(value)),
+            "-f" | "--format" => format = Some(parse_format(value)?),
+            "-r" | "--resolution" => o.resolution = Some(parse_resolution(value)?),
+            "-s" | "--samples" => o.samples = Some(parse_count(name, value)?),
+            "-j" | "--threads" => o.threads = Some(parse_count(name, value)?),
+            "-i" | "--integrator" => o.integrator = parse_integrator(value)?,
+            "-d" | "--max-depth" => match value.parse::<usize>() {
+                Ok(n) => o.max_depth = n,
+                Err(_) => return Err(format!("invalid {} '{}'", name, value)),
+            },
+            "--seed" => match value.parse::<u64>() {
+                Ok(n) => o.seed = n,
+                Err(_) => return Err(format!("invalid {} '{}'", name, value)),
+            },
+            "--sample-counts" => o.sample_counts = Some(String::from(value)),
+            "--ray-epsilon" => match value.parse::<Float>() {
+                Ok(e) if e > 0.0 && e.is_finite() => o.ray_epsilon = Some(e),
+                _ => return Err(format!("invalid {} '{}'", name, value)),
+            },
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(format!("unknown option {}", name))
+            }
+            _ => {
+                if o.scene.is_some() {
+                    return Err(format!("unexpected argument '{}'", name));
+                }
+                o.scene = Some(String::from(name));
+            }
+        }
+        i += 1;
+    }
+    o.format = match (format, &o.output) {
+        (Some(f), _) => f,
+        (None, Some(path)) if path.to_lowercase().ends_with(".png") => Format::Png,
+        _ => Format::Ppm,
+    };
+    return Ok(o);
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value.to_lowercase().as_str() {
+        "ppm" => return Ok(Format::Ppm),
+        "png" => return Ok(Format::Png),
+        _ => return Err(format!("unknown format '{}' (expected ppm or png)", value)),
+    }
+}
+
+fn parse_integrator(value: &str) -> Result<Integrator, String> {
+    match value.to_lowercase().as_str() {
+        "whitted" => return Ok(Integrator::Whitted),
+        "path" => return Ok(Integrator::Path),
+        _ => {
+            return Err(format!(
+                "unknown integrator '{}' (expected whitted or path)",
+                value
+            ))
+        }
+    }
+}
+
+fn parse_resolution(value: &str) -> Result<(usize, usize), String> {
+    if let Some((w, h)) = value.split_once(['x', 'X']) {
+        if let (Ok(w), Ok(h)) = (w.parse::<usize>(), h.parse::<usize>()) {
+            if w > 0 && h > 0 {
+                return Ok((w, h));
+            }
+        }
+    }
+    return Err(format!(
+        "invalid resolution '{}' (expected WIDTHxHEIGHT)",
+        value
+    ));
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => return Ok(n),
+        _ => {
+            return Err(format!(
+                "invalid {} '{}' (expected a positive number)",
+                name, value
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        return list.iter().map(|s| String::from(*s)).collect();
+    }
+
+    #[test]
+    fn should_default_to_demo_on_stdout() {
+        let o = parse(&args(&[])).unwrap();
+        assert!(o.scene.is_none());
+        assert!(o.output.is_none());
+        assert!(o.format == Format::Ppm);
+        assert!(o.max_depth == 5);
+        assert!(o.seed == 0);
+        assert!(o.integrator == Integrator::Whitted);
+        assert!(!o.quiet);
+    }
+
+    #[test]
+    fn should_parse_every_option() {
+        let o = parse(&args(&[
+            "-o",
+            "out.img",
+            "--format=png",
+            "scene.yml",
+            "-r",
+            "640x480",
+            "--samples",
+            "16",
+            "-j",
+            "4",
+            "--integrator=path",
+            "--max-depth=3",
+            "--seed",
+            "42",
+            "--sample-counts",
+            "counts.png",
+            "--ray-epsilon=1e-6",
+            "-q",
+        ]))
+        .unwrap();
+        assert!(o.scene == Some(String::from("scene.yml")));
+        assert!(o.output == Some(String::from("out.img")));
+        assert!(o.format == Format::Png);
+        assert!(o.resolution == Some((640, 480)));
+        assert!(o.samples == Some(16));
+        assert!(o.threads == Some(4));
+        assert!(o.integrator == Integrator::Path);
+        assert!(o.max_depth == 3);
+        assert!(o.seed == 42);
+        assert!(o.sample_counts == Some(String::from("counts.png")));
+        assert!(o.ray_epsilon == Some(1e-6));
+        assert!(o.quiet);
+    }
+
+    #[test]
+    fn should_pick_format_from_extension() {
+        assert!(parse(&args(&["-o", "a.PNG"])).unwrap().format == Format::Png);
+        assert!(parse(&args(&["-o", "a.ppm"])).unwrap().format == Format::Ppm);
+        assert!(parse(&args(&["-o", "a.png", "-f", "ppm"])).unwrap().format == Format::Ppm);
+    }
+
+    #[test]
+    fn should_reject_bad_arguments() {
+        assert!(parse(&args(&["--bogus"])).is_err());
+        assert!(parse(&args(&["-s"])).is_err());
+        assert!(parse(&args(&["-s", "0"])).is_err());
+        assert!(parse(&args(&["-r", "640"])).is_err());
+        assert!(parse(&args(&["--ray-epsilon", "0"])).is_err());
+        assert!(parse(&args(&["-f", "gif"])).is_err());
+        assert!(parse(&args(&["-i", "preview"])).is_err());
+        assert!(parse(&args(&["--help=yes"])).is_err());
+        assert!(parse(&args(&["a.yml", "b.yml"])).is_err());
+    }
+}
@@ -1,12 +1,16 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
 use crate::float;
+use crate::float::Float;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Color {
-    pub red: f64,
-    pub green: f64,
-    pub blue: f64,
+    pub red: Float,
+    pub green: Float,
+    pub blue: Float,
 }
 
-pub fn color(red: f64, green: f64, blue: f64) -> Color {
+pub fn color(red: Float, green: Float, blue: Float) -> Color {
     return Color { red, green, blue };
 }
 
@@ -32,7 +36,7 @@ pub fn subtract(a: &Color, b: &Color) -> Color {
     }
 }
 
-pub fn multiply(col: &Color, scalar: f64) -> Color {
+pub fn multiply(col: &Color, scalar: Float) -> Color {
     Color {
         red: col.red * scalar,
         green: col.green * scalar,
@@ -48,6 +52,98 @@ pub fn product(a: &Color, b: &Color) -> Color {
     }
 }
 
+pub fn luminance(c: &Color) -> Float {
+    return 0.2126 * c.red + 0.7152 * c.green + 0.0722 * c.blue;
+}
+
+// Operators for the functions above. Color * Color is product, the
+// component-wise filter used when light meets a surface.
+
+impl PartialEq for Color {
+    fn eq(&self, other: &Color) -> bool {
+        return equals(self, other);
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+    fn add(self, other: Color) -> Color {
+        return add(&self, &other);
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+    fn sub(self, other: Color) -> Color {
+        return subtract(&self, &other);
+    }
+}
+
+impl Mul<Float> for Color {
+    type Output = Color;
+    fn mul(self, scalar: Float) -> Color {
+        return multiply(&self, scalar);
+    }
+}
+
+impl Mul<Color> for Float {
+    type Output = Color;
+    fn mul(self, c: Color) -> Color {
+        return multiply(&c, self);
+    }
+}
+
+impl Mul for Color {
+    type Output = Color;
+    fn mul(self, other: Color) -> Color {
+        return product(&self, &other);
+    }
+}
+
+impl Div<Float> for Color {
+    type Output = Color;
+    fn div(self, scalar: Float) -> Color {
+        return multiply(&self, 1.0 / scalar);
+    }
+}
+
+impl Neg for Color {
+    type Output = Color;
+    fn neg(self) -> Color {
+        return multiply(&self, -1.0);
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        *self = add(self, &other);
+    }
+}
+
+impl SubAssign for Color {
+    fn sub_assign(&mut self, other: Color) {
+        *self = subtract(self, &other);
+    }
+}
+
+impl MulAssign<Float> for Color {
+    fn mul_assign(&mut self, scalar: Float) {
+        *self = multiply(self, scalar);
+    }
+}
+
+impl MulAssign for Color {
+    fn mul_assign(&mut self, other: Color) {
+        *self = product(self, &other);
+    }
+}
+
+impl DivAssign<Float> for Color {
+    fn div_assign(&mut self, scalar: Float) {
+        *self = multiply(self, 1.0 / scalar);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,7 +164,7 @@ mod tests {
         for red in 0..2 {
             for green in 0..2 {
                 for blue in 0..2 {
-                    let value = color(f64::from(red), f64::from(green), f64::from(blue));
+                    let value = color(red as Float, green as Float, blue as Float);
                     if red + green + blue > 0 {
                         assert!(!equals(&expected, &value));
                     } else {
@@ -117,4 +213,30 @@ mod tests {
         let expected = color(0.9, 0.2, 0.04);
         assert!(equals(&result, &expected));
     }
+
+    #[test]
+    fn should_calculate_luminance() {
+        assert!(float::equals(luminance(&color(1.0, 1.0, 1.0)), 1.0));
+        assert!(float::equals(luminance(&color(0.0, 1.0, 0.0)), 0.7152));
+    }
+
+    #[test]
+    fn should_apply_operators() {
+        let c1 = color(0.9, 0.6, 0.75);
+        let c2 = color(0.7, 0.1, 0.25);
+        assert!(c1 + c2 == color(1.6, 0.7, 1.0));
+        assert!(c1 - c2 == color(0.2, 0.5, 0.5));
+        assert!(c1 * 2.0 == color(1.8, 1.2, 1.5));
+        assert!(0.5 * c1 == c1 / 2.0);
+        assert!(c1 * c2 == product(&c1, &c2));
+        assert!(-c2 == color(-0.7, -0.1, -0.25));
+        let mut c = c1;
+        c += c2;
+        c -= c1;
+        c *= 2.0;
+        c /= 4.0;
+        c *= color(2.0, 0.0, 4.0);
+        assert!(c == color(0.7, 0.0, 0.5));
+        assert!(c1 != c2);
+    }
 }
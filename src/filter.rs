@@ -0,0 +1,125 @@
+// Pixel reconstruction filters. Weights are separable: w(dx, dy) = f(dx) * f(dy),
+// with dx and dy measured in pixels from the pixel center.
+
+use crate::float::Float;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Filter {
+    Box { radius: Float },
+    Tent { radius: Float },
+    Gaussian { radius: Float, alpha: Float },
+    Mitchell { radius: Float, b: Float, c: Float },
+}
+
+pub fn box_filter() -> Filter {
+    return Filter::Box { radius: 0.5 };
+}
+
+pub fn tent() -> Filter {
+    return Filter::Tent { radius: 1.0 };
+}
+
+pub fn gaussian() -> Filter {
+    return Filter::Gaussian {
+        radius: 1.5,
+        alpha: 2.0,
+    };
+}
+
+pub fn mitchell() -> Filter {
+    return Filter::Mitchell {
+        radius: 2.0,
+        b: 1.0 / 3.0,
+        c: 1.0 / 3.0,
+    };
+}
+
+pub fn radius(filter: &Filter) -> Float {
+    match filter {
+        Filter::Box { radius }
+        | Filter::Tent { radius }
+        | Filter::Gaussian { radius, .. }
+        | Filter::Mitchell { radius, .. } => return *radius,
+    }
+}
+
+pub fn weight(filter: &Filter, dx: Float, dy: Float) -> Float {
+    return weight_1d(filter, dx) * weight_1d(filter, dy);
+}
+
+fn weight_1d(filter: &Filter, d: Float) -> Float {
+    let d = d.abs();
+    match filter {
+        Filter::Box { radius } => {
+            if d <= *radius {
+                return 1.0;
+            }
+            return 0.0;
+        }
+        Filter::Tent { radius } => return (radius - d).max(0.0),
+        Filter::Gaussian { radius, alpha } => {
+            return ((-alpha * d * d).exp() - (-alpha * radius * radius).exp()).max(0.0);
+        }
+        Filter::Mitchell { radius, b, c } => return mitchell_1d(2.0 * d / radius, *b, *c),
+    }
+}
+
+fn mitchell_1d(x: Float, b: Float, c: Float) -> Float {
+    if x > 2.0 {
+        return 0.0;
+    }
+    if x > 1.0 {
+        return ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0;
+    }
+    return ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+        + (6.0 - 2.0 * b))
+        / 6.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    #[test]
+    fn should_weight_box_evenly() {
+        let f = box_filter();
+        assert!(float::equals(weight(&f, 0.0, 0.0), 1.0));
+        assert!(float::equals(weight(&f, 0.4, -0.4), 1.0));
+        assert!(float::equals(weight(&f, 0.6, 0.0), 0.0));
+    }
+
+    #[test]
+    fn should_weight_tent_linearly() {
+        let f = tent();
+        assert!(float::equals(weight(&f, 0.0, 0.0), 1.0));
+        assert!(float::equals(weight(&f, 0.5, 0.0), 0.5));
+        assert!(float::equals(weight(&f, 0.5, 0.5), 0.25));
+        assert!(float::equals(weight(&f, 1.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn should_fall_off_gaussian_to_zero_at_radius() {
+        let f = gaussian();
+        assert!(weight(&f, 0.0, 0.0) > weight(&f, 0.5, 0.0));
+        assert!(weight(&f, 0.5, 0.0) > weight(&f, 1.0, 0.0));
+        assert!(float::equals(weight(&f, radius(&f), 0.0), 0.0));
+    }
+
+    #[test]
+    fn should_have_negative_mitchell_lobes() {
+        let f = mitchell();
+        assert!(float::equals(
+            weight(&f, 0.0, 0.0),
+            (8.0 / 9.0) * (8.0 / 9.0)
+        ));
+        assert!(weight(&f, 1.5, 0.0) < 0.0);
+        assert!(float::equals(weight(&f, 2.0, 0.0), 0.0));
+        assert!(float::equals(weight(&f, 2.5, 0.0), 0.0));
+    }
+}
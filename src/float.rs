@@ -1,4 +1,116 @@
-const EPSILON: f64 = 0.00001;
-pub fn equals(a: f64, b: f64) -> bool {
+// Floating point comparison. equals uses a fixed absolute tolerance, which
+// suits the book's tests and values near 1 such as colors and unit vectors.
+// Anything whose size depends on the scene should use relative_equals or
+// ulps_equals instead.
+//
+// Float is the precision everything is computed in: f64 by default, or f32
+// when built with the f32 feature. Tolerances are looser to match.
+
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+#[cfg(not(feature = "f32"))]
+const EPSILON: Float = 0.00001;
+#[cfg(feature = "f32")]
+const EPSILON: Float = 0.0001;
+
+// How far, relative to the size of the coordinates involved, new rays start
+// off the surface they leave. See ray::offset_distance.
+#[cfg(not(feature = "f32"))]
+pub const RAY_EPSILON: Float = 1e-9;
+#[cfg(feature = "f32")]
+pub const RAY_EPSILON: Float = 0.0001;
+
+pub fn equals(a: Float, b: Float) -> bool {
     return (a - b).abs() < EPSILON;
 }
+
+// a and b differ by at most tolerance times the larger of the two.
+pub fn relative_equals(a: Float, b: Float, tolerance: Float) -> bool {
+    if a == b {
+        return true;
+    }
+    return (a - b).abs() <= tolerance * a.abs().max(b.abs());
+}
+
+// The number of representable values of Float from a to b, so adjacent
+// values are 1 apart. 0.0 and -0.0 are the same; NaN is as far from
+// anything as can be.
+pub fn ulps(a: Float, b: Float) -> u64 {
+    if a.is_nan() || b.is_nan() {
+        return u64::MAX;
+    }
+    return order(a).abs_diff(order(b));
+}
+
+// Reorders the bit patterns of negative numbers so that comparing the
+// integers compares the floats.
+#[cfg(not(feature = "f32"))]
+fn order(x: Float) -> i64 {
+    let bits = x.to_bits() as i64;
+    if bits < 0 {
+        return i64::MIN - bits;
+    }
+    return bits;
+}
+
+#[cfg(feature = "f32")]
+fn order(x: Float) -> i64 {
+    let bits = x.to_bits() as i32 as i64;
+    if bits < 0 {
+        return i32::MIN as i64 - bits;
+    }
+    return bits;
+}
+
+pub fn ulps_equals(a: Float, b: Float, max_ulps: u64) -> bool {
+    return ulps(a, b) <= max_ulps;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_compare_relative_to_size() {
+        assert!(relative_equals(1e6, 1e6 + 1.0, 1e-5));
+        assert!(!equals(1e6, 1e6 + 1.0));
+        assert!(!relative_equals(1e-6, 2e-6, 1e-5));
+        assert!(equals(1e-6, 2e-6));
+        assert!(relative_equals(0.0, 0.0, 0.0));
+        assert!(!relative_equals(0.0, 1e-30, 0.5));
+        assert!(relative_equals(-3.0, -3.00001, 1e-5));
+        assert!(!relative_equals(Float::NAN, Float::NAN, 1.0));
+    }
+
+    #[test]
+    fn should_count_ulps() {
+        assert!(ulps(1.0, 1.0) == 0);
+        assert!(ulps(1.0, 1.0 + Float::EPSILON) == 1);
+        assert!(ulps(0.0, -0.0) == 0);
+        // Across zero, through the smallest subnormals on either side.
+        let tiny = Float::from_bits(1);
+        assert!(ulps(-tiny, tiny) == 2);
+        assert!(ulps(Float::MAX, Float::INFINITY) == 1);
+        assert!(ulps(Float::NAN, 1.0) == u64::MAX);
+        // In f32 this sum happens to round to exactly 0.3.
+        if cfg!(not(feature = "f32")) {
+            assert!(ulps_equals(0.1 + 0.2, 0.3, 1));
+            assert!(!ulps_equals(0.1 + 0.2, 0.3, 0));
+        }
+        // The same number of steps means the same precision at any scale.
+        for scale in [1e-3, 1.0, 1e3, 1e6].iter() {
+            let x: Float = 0.7 * scale;
+            let y = Float::from_bits(x.to_bits() + 3);
+            assert!(ulps_equals(x, y, 3) && !ulps_equals(x, y, 2));
+            assert!(relative_equals(x, y, 4.0 * Float::EPSILON));
+        }
+    }
+}
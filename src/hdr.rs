@@ -0,0 +1,191 @@
+// Reads Radiance RGBE (.hdr) images, the usual format for HDR environment
+// maps. Scanlines may be flat or use the newer run-length encoding; only the
+// standard "-Y height +X width" orientation is supported.
+
+use crate::canvas;
+use crate::color;
+use crate::float::Float;
+
+pub fn parse(bytes: &[u8]) -> Result<canvas::Canvas, String> {
+    let mut pos = 0;
+    let magic = line(bytes, &mut pos)?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err(String::from("not a Radiance HDR file"));
+    }
+    loop {
+        let l = line(bytes, &mut pos)?;
+        if l.is_empty() {
+            break;
+        }
+        if let Some(format) = l.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported format '{}'", format));
+            }
+        }
+    }
+    let size = line(bytes, &mut pos)?;
+    let fields: Vec<&str> = size.split_whitespace().collect();
+    let (height, width) = match fields.as_slice() {
+        ["-Y", h, "+X", w] => match (h.parse::<usize>(), w.parse::<usize>()) {
+            (Ok(h), Ok(w)) if h > 0 && w > 0 => (h, w),
+            _ => return Err(format!("invalid size '{}'", size)),
+        },
+        _ => return Err(format!("unsupported orientation '{}'", size)),
+    };
+
+    let mut image = canvas::canvas(width, height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for y in 0..height {
+        read_scanline(bytes, &mut pos, &mut scanline)?;
+        for (x, rgbe) in scanline.iter().enumerate() {
+            canvas::write_pixel(&mut image, x, y, &decode(rgbe));
+        }
+    }
+    return Ok(image);
+}
+
+fn line(bytes: &[u8], pos: &mut usize) -> Result<String, String> {
+    let start = *pos;
+    while *pos < bytes.len() && bytes[*pos] != b'\n' {
+        *pos += 1;
+    }
+    if *pos >= bytes.len() {
+        return Err(String::from("unexpected end of header"));
+    }
+    let text = String::from_utf8_lossy(&bytes[start..*pos])
+        .trim()
+        .to_string();
+    *pos += 1;
+    return Ok(text);
+}
+
+fn byte(bytes: &[u8], pos: &mut usize) -> Result<u8, String> {
+    match bytes.get(*pos) {
+        Some(b) => {
+            *pos += 1;
+            return Ok(*b);
+        }
+        None => return Err(String::from("unexpected end of pixel data")),
+    }
+}
+
+fn read_scanline(bytes: &[u8], pos: &mut usize, out: &mut [[u8; 4]]) -> Result<(), String> {
+    let width = out.len();
+    let encoded = (8..32768).contains(&width)
+        && bytes.len() >= *pos + 4
+        && bytes[*pos] == 2
+        && bytes[*pos + 1] == 2
+        && bytes[*pos + 2] & 0x80 == 0;
+    if !encoded {
+        for pixel in out.iter_mut() {
+            for c in pixel.iter_mut() {
+                *c = byte(bytes, pos)?;
+            }
+        }
+        return Ok(());
+    }
+    let declared = ((bytes[*pos + 2] as usize) << 8) | bytes[*pos + 3] as usize;
+    if declared != width {
+        return Err(String::from("scanline width mismatch"));
+    }
+    *pos += 4;
+    // Each of the four components is run-length encoded separately.
+    for c in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = byte(bytes, pos)? as usize;
+            if count > 128 {
+                let run = count - 128;
+                let value = byte(bytes, pos)?;
+                if x + run > width {
+                    return Err(String::from("run overflows scanline"));
+                }
+                for pixel in out[x..x + run].iter_mut() {
+                    pixel[c] = value;
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(String::from("bad scanline run"));
+                }
+                for pixel in out[x..x + count].iter_mut() {
+                    pixel[c] = byte(bytes, pos)?;
+                }
+                x += count;
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn decode(rgbe: &[u8; 4]) -> color::Color {
+    if rgbe[3] == 0 {
+        return color::color(0.0, 0.0, 0.0);
+    }
+    let scale = Float::powi(2.0, rgbe[3] as i32 - 136);
+    return color::color(
+        rgbe[0] as Float * scale,
+        rgbe[1] as Float * scale,
+        rgbe[2] as Float * scale,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    fn header(width: usize, height: usize) -> Vec<u8> {
+        let text = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n-Y {} +X {}\n",
+            height, width
+        );
+        return text.into_bytes();
+    }
+
+    #[test]
+    fn should_read_flat_scanlines() {
+        let mut bytes = header(2, 1);
+        // 128 * 2^(129 - 136) = 1.0; a zero exponent is black.
+        bytes.extend_from_slice(&[128, 64, 0, 129, 255, 255, 255, 0]);
+        let image = parse(&bytes).unwrap();
+        assert!(image.width() == 2 && image.height() == 1);
+        let a = canvas::pixel_at(&image, 0, 0);
+        assert!(float::equals(a.red, 1.0));
+        assert!(float::equals(a.green, 0.5));
+        assert!(float::equals(a.blue, 0.0));
+        let b = canvas::pixel_at(&image, 1, 0);
+        assert!(float::equals(b.red, 0.0));
+    }
+
+    #[test]
+    fn should_read_run_length_encoded_scanlines() {
+        let mut bytes = header(8, 1);
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        // Red: a run of 8; green: 8 literals; blue: runs of 4 and 4;
+        // exponent: a run of 8.
+        bytes.extend_from_slice(&[128 + 8, 128]);
+        bytes.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        bytes.extend_from_slice(&[128 + 4, 0, 128 + 4, 255]);
+        bytes.extend_from_slice(&[128 + 8, 129]);
+        let image = parse(&bytes).unwrap();
+        let p = canvas::pixel_at(&image, 2, 0);
+        assert!(float::equals(p.red, 1.0));
+        assert!(float::equals(p.green, 0.25));
+        assert!(float::equals(p.blue, 0.0));
+        assert!(float::equals(
+            canvas::pixel_at(&image, 7, 0).blue,
+            255.0 / 128.0
+        ));
+    }
+
+    #[test]
+    fn should_reject_bad_files() {
+        assert!(parse(b"P3\n1 1\n255\n").is_err());
+        let mut bytes = header(2, 2);
+        bytes.extend_from_slice(&[128, 64, 0, 129]);
+        assert!(parse(&bytes).err().unwrap() == "unexpected end of pixel data");
+        let bytes = b"#?RADIANCE\n\n+Y 1 +X 1\n".to_vec();
+        assert!(parse(&bytes).is_err());
+    }
+}
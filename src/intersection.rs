@@ -1,13 +1,14 @@
+use crate::float::Float;
 use crate::shape;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Intersection {
-    pub t: f64,
+    pub t: Float,
     pub s: shape::Shape,
 }
 
-pub fn intersection(t: f64, s: &shape::Shape) -> Intersection {
-    return Intersection { t: t, s: *s };
+pub fn intersection(t: Float, s: &shape::Shape) -> Intersection {
+    return Intersection { t, s: *s };
 }
 
 pub fn intersections(list: &[Intersection]) -> Vec<Intersection> {
@@ -19,10 +20,5 @@ pub fn intersections(list: &[Intersection]) -> Vec<Intersection> {
 }
 
 pub fn equals(a: &Intersection, b: &Intersection) -> bool {
-    return (a.t == b.t && shape::equals(&a.s, &b.s));
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    return a.t == b.t && shape::equals(&a.s, &b.s);
 }
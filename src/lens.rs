@@ -0,0 +1,104 @@
+// Aperture shapes for the thin lens camera. Out of focus highlights (bokeh)
+// take the shape of the aperture. Points are on the unit lens: the disc of
+// radius 1, or the regular polygon inscribed in it.
+
+use crate::float::consts::PI;
+use crate::float::Float;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aperture {
+    Disc,
+    // One corner points straight up.
+    Polygon { sides: usize },
+}
+
+pub fn disc() -> Aperture {
+    return Aperture::Disc;
+}
+
+pub fn polygon(sides: usize) -> Aperture {
+    return Aperture::Polygon {
+        sides: sides.max(3),
+    };
+}
+
+// Maps (u, v) in the unit square onto the aperture, evenly by area and
+// keeping nearby samples nearby so stratification carries over.
+pub fn point(aperture: &Aperture, u: Float, v: Float) -> (Float, Float) {
+    match aperture {
+        Aperture::Disc => {
+            // Shirley and Chiu's concentric mapping.
+            let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+            if a == 0.0 && b == 0.0 {
+                return (0.0, 0.0);
+            }
+            let (r, theta) = if a.abs() > b.abs() {
+                (a, PI / 4.0 * (b / a))
+            } else {
+                (b, PI / 2.0 - PI / 4.0 * (a / b))
+            };
+            return (r * theta.cos(), r * theta.sin());
+        }
+        Aperture::Polygon { sides } => {
+            // u picks one of the triangles fanning out from the center and
+            // is reused, with v, to place the point inside it.
+            let scaled = u * *sides as Float;
+            let i = (scaled.floor() as usize).min(sides - 1);
+            let r = (scaled - i as Float).sqrt();
+            let corner = |k: usize| {
+                let angle = PI / 2.0 + 2.0 * PI * k as Float / *sides as Float;
+                return (angle.cos(), angle.sin());
+            };
+            let (a, b) = (corner(i), corner(i + 1));
+            return (
+                r * ((1.0 - v) * a.0 + v * b.0),
+                r * ((1.0 - v) * a.1 + v * b.1),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    #[test]
+    fn should_map_square_onto_disc() {
+        let (x, y) = point(&disc(), 0.5, 0.5);
+        assert!(float::equals(x, 0.0) && float::equals(y, 0.0));
+        let (x, y) = point(&disc(), 1.0, 0.5);
+        assert!(float::equals(x, 1.0) && float::equals(y, 0.0));
+        let (x, y) = point(&disc(), 0.5, 0.0);
+        assert!(float::equals(x, 0.0) && float::equals(y, -1.0));
+        for i in 0..20 {
+            for j in 0..20 {
+                let (x, y) = point(&disc(), i as Float / 19.0, j as Float / 19.0);
+                assert!(x * x + y * y <= 1.0 + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn should_stay_inside_polygon() {
+        let hexagon = polygon(6);
+        let (x, y) = point(&hexagon, 0.0, 0.0);
+        assert!(float::equals(x, 0.0) && float::equals(y, 0.0));
+        // The far edge of the first triangle starts at the top corner.
+        let (x, y) = point(&hexagon, 1.0 / 6.0 - 1e-6, 0.0);
+        assert!(float::equals(x, 0.0) && float::equals(y, 1.0));
+        // Every point lies within the hexagon's apothem of its edges.
+        let apothem = (PI / 6.0).cos();
+        for i in 0..60 {
+            for j in 0..10 {
+                let (x, y) = point(&hexagon, i as Float / 60.0, j as Float / 9.0);
+                for k in 0..6 {
+                    let angle = PI / 2.0 + PI / 6.0 + 2.0 * PI * k as Float / 6.0;
+                    let along = x * angle.cos() + y * angle.sin();
+                    assert!(along <= apothem + 1e-9);
+                }
+            }
+        }
+        assert!(polygon(2) == polygon(3));
+    }
+}
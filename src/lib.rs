@@ -0,0 +1,38 @@
+#![allow(clippy::needless_return, clippy::needless_range_loop)]
+
+pub mod array2d;
+pub mod background;
+pub mod bsdf;
+pub mod bump;
+pub mod camera;
+pub mod canvas;
+pub mod cli;
+pub mod color;
+pub mod filter;
+pub mod float;
+pub mod hdr;
+pub mod intersection;
+pub mod lens;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod matrix4;
+pub mod normal3;
+pub mod obj;
+pub mod path;
+pub mod point3;
+pub mod progress;
+pub mod quaternion;
+pub mod random;
+pub mod ray;
+pub mod sampler;
+pub mod scene;
+pub mod shape;
+pub mod simd;
+pub mod stats;
+pub mod texture;
+pub mod transform;
+pub mod tuple;
+pub mod uv;
+pub mod vector3;
+pub mod yaml;
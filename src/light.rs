@@ -0,0 +1,396 @@
+use crate::float::consts::PI;
+use crate::float::Float;
+
+use crate::color;
+use crate::point3;
+use crate::random;
+use crate::sampler;
+use crate::vector3;
+
+// Intensity is divided by constant + linear * d + quadratic * d^2.
+#[derive(Clone, Copy, Debug)]
+pub struct Attenuation {
+    pub constant: Float,
+    pub linear: Float,
+    pub quadratic: Float,
+}
+
+pub const NO_ATTENUATION: Attenuation = Attenuation {
+    constant: 1.0,
+    linear: 0.0,
+    quadratic: 0.0,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    Point {
+        position: point3::Point3,
+        intensity: color::Color,
+        attenuation: Attenuation,
+    },
+    // Parallel light travelling along direction, like the sun.
+    Directional {
+        direction: vector3::Vector3,
+        intensity: color::Color,
+    },
+    // Full intensity inside the inner cone, fading smoothly to nothing at the
+    // outer cone. Angles are measured from direction, in radians.
+    Spot {
+        position: point3::Point3,
+        direction: vector3::Vector3,
+        inner_angle: Float,
+        outer_angle: Float,
+        intensity: color::Color,
+        attenuation: Attenuation,
+    },
+    // Parallelogram spanned by uvec and vvec from corner.
+    Rectangle {
+        corner: point3::Point3,
+        uvec: vector3::Vector3,
+        vvec: vector3::Vector3,
+        intensity: color::Color,
+        samples: usize,
+        attenuation: Attenuation,
+    },
+    Sphere {
+        center: point3::Point3,
+        radius: Float,
+        intensity: color::Color,
+        samples: usize,
+        attenuation: Attenuation,
+    },
+}
+
+pub fn attenuation(constant: Float, linear: Float, quadratic: Float) -> Attenuation {
+    return Attenuation {
+        constant,
+        linear,
+        quadratic,
+    };
+}
+
+pub fn point_light(position: &point3::Point3, intensity: &color::Color) -> Light {
+    return Light::Point {
+        position: *position,
+        intensity: *intensity,
+        attenuation: NO_ATTENUATION,
+    };
+}
+
+pub fn directional_light(direction: &vector3::Vector3, intensity: &color::Color) -> Light {
+    return Light::Directional {
+        direction: vector3::normalize(direction),
+        intensity: *intensity,
+    };
+}
+
+pub fn spot_light(
+    position: &point3::Point3,
+    direction: &vector3::Vector3,
+    inner_angle: Float,
+    outer_angle: Float,
+    intensity: &color::Color,
+) -> Light {
+    return Light::Spot {
+        position: *position,
+        direction: vector3::normalize(direction),
+        inner_angle,
+        outer_angle,
+        intensity: *intensity,
+        attenuation: NO_ATTENUATION,
+    };
+}
+
+pub fn rectangle_light(
+    corner: &point3::Point3,
+    uvec: &vector3::Vector3,
+    vvec: &vector3::Vector3,
+    intensity: &color::Color,
+    samples: usize,
+) -> Light {
+    return Light::Rectangle {
+        corner: *corner,
+        uvec: *uvec,
+        vvec: *vvec,
+        intensity: *intensity,
+        samples,
+        attenuation: NO_ATTENUATION,
+    };
+}
+
+pub fn sphere_light(
+    center: &point3::Point3,
+    radius: Float,
+    intensity: &color::Color,
+    samples: usize,
+) -> Light {
+    return Light::Sphere {
+        center: *center,
+        radius,
+        intensity: *intensity,
+        samples,
+        attenuation: NO_ATTENUATION,
+    };
+}
+
+// Directional lights are infinitely far away and ignore attenuation.
+pub fn set_attenuation(light: &mut Light, a: &Attenuation) {
+    match light {
+        Light::Point { attenuation, .. }
+        | Light::Spot { attenuation, .. }
+        | Light::Rectangle { attenuation, .. }
+        | Light::Sphere { attenuation, .. } => *attenuation = *a,
+        Light::Directional { .. } => {}
+    }
+}
+
+pub fn intensity(light: &Light) -> color::Color {
+    match light {
+        Light::Point { intensity, .. }
+        | Light::Directional { intensity, .. }
+        | Light::Spot { intensity, .. }
+        | Light::Rectangle { intensity, .. }
+        | Light::Sphere { intensity, .. } => return *intensity,
+    }
+}
+
+// Intensity arriving at point from from, one of the light's sample points,
+// after the spot cone and distance falloff. Directional lights have no
+// samples and ignore from.
+pub fn intensity_at(light: &Light, from: &point3::Point3, point: &point3::Point3) -> color::Color {
+    match light {
+        Light::Directional { intensity, .. } => return *intensity,
+        Light::Spot {
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+            intensity,
+            attenuation,
+        } => {
+            let to_point = *point - *position;
+            let d = point3::distance(point, from);
+            let cos_angle = vector3::dot(&vector3::normalize(&to_point), direction);
+            let cone = smoothstep(outer_angle.cos(), inner_angle.cos(), cos_angle);
+            return color::multiply(intensity, cone * falloff(attenuation, d));
+        }
+        Light::Point {
+            intensity,
+            attenuation,
+            ..
+        }
+        | Light::Rectangle {
+            intensity,
+            attenuation,
+            ..
+        }
+        | Light::Sphere {
+            intensity,
+            attenuation,
+            ..
+        } => {
+            let d = point3::distance(from, point);
+            return color::multiply(intensity, falloff(attenuation, d));
+        }
+    }
+}
+
+fn falloff(a: &Attenuation, d: Float) -> Float {
+    return 1.0 / (a.constant + a.linear * d + a.quadratic * d * d);
+}
+
+fn smoothstep(edge0: Float, edge1: Float, x: Float) -> Float {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+// Jittered positions on the light's surface. Point and spot lights are their
+// own single sample; a rectangle is stratified so the samples cover it evenly.
+// Directional lights have no position and return no samples.
+pub fn sample_points(light: &Light, rng: &mut random::Random) -> Vec<point3::Point3> {
+    match light {
+        Light::Point { position, .. } | Light::Spot { position, .. } => return vec![*position],
+        Light::Directional { .. } => return Vec::new(),
+        Light::Rectangle {
+            corner,
+            uvec,
+            vvec,
+            samples,
+            ..
+        } => {
+            let mut v = Vec::new();
+            for (u, w) in sampler::samples(&sampler::Sampler::Jittered, *samples, rng) {
+                v.push(*corner + *uvec * u + *vvec * w);
+            }
+            return v;
+        }
+        Light::Sphere {
+            center,
+            radius,
+            samples,
+            ..
+        } => {
+            let mut v = Vec::with_capacity(*samples);
+            for _ in 0..(*samples).max(1) {
+                let z = 1.0 - 2.0 * random::next_float(rng);
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * random::next_float(rng);
+                let direction = vector3::vector3(r * phi.cos(), r * phi.sin(), z);
+                v.push(*center + direction * *radius);
+            }
+            return v;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    // Page 84
+    #[test]
+    fn should_create_point_light() {
+        let intensity = color::color(1.0, 1.0, 1.0);
+        let position = point3::point3(0.0, 0.0, 0.0);
+        let light = point_light(&position, &intensity);
+        match light {
+            Light::Point {
+                position: p,
+                intensity: i,
+                ..
+            } => {
+                assert!(point3::equals(&p, &position));
+                assert!(color::equals(&i, &intensity));
+            }
+            _ => panic!("expected a point light"),
+        }
+    }
+
+    #[test]
+    fn should_sample_point_light_once() {
+        let mut rng = random::random(0);
+        let position = point3::point3(1.0, 2.0, 3.0);
+        let light = point_light(&position, &color::color(1.0, 1.0, 1.0));
+        let points = sample_points(&light, &mut rng);
+        assert!(points.len() == 1);
+        assert!(point3::equals(&points[0], &position));
+    }
+
+    #[test]
+    fn should_spread_samples_over_rectangle() {
+        let mut rng = random::random(1);
+        let light = rectangle_light(
+            &point3::point3(0.0, 0.0, 0.0),
+            &vector3::vector3(2.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+            &color::color(1.0, 1.0, 1.0),
+            4,
+        );
+        let points = sample_points(&light, &mut rng);
+        assert!(points.len() == 4);
+        // One sample per quadrant.
+        for (n, p) in points.iter().enumerate() {
+            let i = (n % 2) as Float;
+            let j = (n / 2) as Float;
+            assert!(p.x >= i && p.x < i + 1.0);
+            assert!(p.z >= j * 0.5 && p.z < j * 0.5 + 0.5);
+            assert!(float::equals(p.y, 0.0));
+        }
+    }
+
+    #[test]
+    fn should_sample_sphere_surface() {
+        let mut rng = random::random(2);
+        let center = point3::point3(0.0, 5.0, 0.0);
+        let light = sphere_light(&center, 0.5, &color::color(1.0, 1.0, 1.0), 32);
+        let points = sample_points(&light, &mut rng);
+        assert!(points.len() == 32);
+        for p in points.iter() {
+            let d = point3::distance(p, &center);
+            assert!(float::equals(d, 0.5));
+        }
+    }
+
+    #[test]
+    fn should_not_attenuate_by_default() {
+        let white = color::color(1.0, 1.0, 1.0);
+        let light = point_light(&point3::point3(0.0, 0.0, 0.0), &white);
+        let origin = point3::point3(0.0, 0.0, 0.0);
+        let far = intensity_at(&light, &origin, &point3::point3(0.0, 100.0, 0.0));
+        assert!(color::equals(&far, &white));
+    }
+
+    #[test]
+    fn should_attenuate_with_distance() {
+        let mut light = point_light(&point3::point3(0.0, 0.0, 0.0), &color::color(1.0, 1.0, 1.0));
+        set_attenuation(&mut light, &attenuation(1.0, 0.5, 0.25));
+        let origin = point3::point3(0.0, 0.0, 0.0);
+        let c = intensity_at(&light, &origin, &point3::point3(0.0, 2.0, 0.0));
+        assert!(float::equals(c.red, 1.0 / (1.0 + 1.0 + 1.0)));
+    }
+
+    #[test]
+    fn should_attenuate_area_light_by_sample_distance() {
+        // The near end of a long light is much closer than its center.
+        let mut light = rectangle_light(
+            &point3::point3(-10.0, 1.0, 0.0),
+            &vector3::vector3(20.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+            &color::color(1.0, 1.0, 1.0),
+            1,
+        );
+        set_attenuation(&mut light, &attenuation(0.0, 0.0, 1.0));
+        let point = point3::point3(-10.0, 0.0, 0.0);
+        let near = intensity_at(&light, &point3::point3(-10.0, 1.0, 0.0), &point);
+        assert!(float::equals(near.red, 1.0));
+        let far = intensity_at(&light, &point3::point3(10.0, 1.0, 0.0), &point);
+        assert!(float::equals(far.red, 1.0 / 401.0));
+    }
+
+    #[test]
+    fn should_light_everything_with_directional_light() {
+        let white = color::color(1.0, 1.0, 1.0);
+        let mut light = directional_light(&vector3::vector3(0.0, -2.0, 0.0), &white);
+        set_attenuation(&mut light, &attenuation(0.0, 0.0, 1.0));
+        let anywhere = point3::point3(0.0, 0.0, 0.0);
+        let c = intensity_at(&light, &anywhere, &point3::point3(1e6, -1e6, 3.0));
+        assert!(color::equals(&c, &white));
+        match light {
+            Light::Directional { direction, .. } => {
+                assert!(vector3::equals(
+                    &direction,
+                    &vector3::vector3(0.0, -1.0, 0.0)
+                ))
+            }
+            _ => panic!("expected a directional light"),
+        }
+        let mut rng = random::random(0);
+        assert!(sample_points(&light, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn should_fade_spot_light_between_cones() {
+        let light = spot_light(
+            &point3::point3(0.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+            PI / 8.0,
+            PI / 4.0,
+            &color::color(1.0, 1.0, 1.0),
+        );
+        let at = |angle: Float| {
+            let p = point3::point3(angle.sin(), 0.0, angle.cos());
+            return intensity_at(&light, &point3::point3(0.0, 0.0, 0.0), &p).red;
+        };
+        assert!(float::equals(at(0.0), 1.0));
+        assert!(float::equals(at(PI / 10.0), 1.0));
+        let middle = at(3.0 * PI / 16.0);
+        assert!(middle > 0.0 && middle < 1.0);
+        assert!(at(PI / 5.0) < middle);
+        assert!(float::equals(at(PI / 3.0), 0.0));
+        assert!(float::equals(at(PI), 0.0));
+    }
+}
@@ -1,79 +1,187 @@
-mod array2d;
-mod canvas;
-mod color;
-mod float;
-mod intersection;
-mod matrix2;
-mod matrix3;
-mod matrix4;
-mod ray;
-mod shape;
-mod transform;
-mod tuple;
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use raytracer::background;
+use raytracer::camera;
+use raytracer::canvas;
+use raytracer::cli;
+use raytracer::color;
+use raytracer::filter;
+use raytracer::float::Float;
+use raytracer::light;
+use raytracer::material;
+use raytracer::path;
+use raytracer::point3;
+use raytracer::sampler;
+use raytracer::scene;
+use raytracer::shape;
+use raytracer::stats;
+use raytracer::transform;
+use raytracer::tuple;
 
 fn main() {
-    /*let mut art = canvas::canvas(256, 256);
-    for x in 0..art.width() {
-        for y in 0..art.height() {
-            let red = x as f64 / 255.0;
-            let green = y as f64 / 255.0;
-            let blue = (255 - x) as f64 / 255.0;
-            let c = color::color(red, green, blue);
-            canvas::write_pixel(&mut art, x, y, &c);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("raytracer: {}\n\n{}", e, cli::USAGE);
+            process::exit(cli::EXIT_USAGE);
         }
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return;
     }
-    let lines = canvas::to_ppm(&art);
-    for line in lines.iter() {
-        println!("{}", line);
-    }*/
 
-    /*
-    let mut clock = canvas::canvas(255, 255);
-    let point = tuple::point(100.0, 0.0, 0.0);
-    let mut point2 = &point;
-    let c = color::color(255.0, 255.0, 255.0);
-    for i in 0..12 {
-        let j = i as f64 * ((2.0 * std::f64::consts::PI) / 12.0);
-        point2 = &point;
-        let point2 = &matrix4::multiply_tuple(&transform::translation(127.0, 127.0, 0.0), &matrix4::multiply_tuple(&transform::rotation_z(j), &point2));
-        let x = point2.x.round();
-        let y = point2.y.round();
-        canvas::write_pixel(&mut clock, x as usize, y as usize, &c);
+    let mut scene = match &options.scene {
+        None => demo(),
+        Some(path) => {
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("raytracer: cannot read {}: {}", path, e);
+                    process::exit(cli::EXIT_NOINPUT);
+                }
+            };
+            let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+            match scene::parse_in(&text, dir) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("raytracer: {}: {}", path, e);
+                    process::exit(cli::EXIT_DATAERR);
+                }
+            }
+        }
+    };
+
+    if let Some((width, height)) = options.resolution {
+        camera::set_size(&mut scene.camera, width, height);
     }
-    let lines = canvas::to_ppm(&clock);
-    for line in lines.iter() {
-        println!("{}", line);
+    if let Some(samples) = options.samples {
+        scene.camera.samples = samples;
     }
-    */
+    scene.camera.threads = match options.threads {
+        Some(n) => n,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    scene.camera.seed = options.seed;
+    if let Some(e) = options.ray_epsilon {
+        scene.camera.ray_epsilon = e;
+    }
+    scene.camera.progress = !options.quiet;
 
-    let mut canvas = canvas::canvas(256, 256);
-    let color = color::color(0.0, 0.9, 1.0);
-    let sphere = shape::sphere();
-    let point = tuple::point(14.0, 19.0, -75.0);
-    let scale = transform::scaling(0.04, 0.04, 0.04);
+    let start = Instant::now();
+    let before = stats::total();
+    let (image, counts) = match options.integrator {
+        cli::Integrator::Whitted => camera::render_with_sample_counts(&scene.camera, |r, rng| {
+            scene::color_at(&scene, r, options.max_depth, rng)
+        }),
+        cli::Integrator::Path => camera::render_with_sample_counts(&scene.camera, |r, rng| {
+            path::trace(&scene, r, options.max_depth, rng)
+        }),
+    };
+    if !options.quiet {
+        let used = stats::difference(&stats::total(), &before);
+        for line in stats::summary(&used, start.elapsed()) {
+            eprintln!("{}", line);
+        }
+    }
+    writeout(&encode(&image, options.format), options.output.as_deref());
+    // The sample count image goes out in the same format as the render.
+    if let Some(path) = &options.sample_counts {
+        writeout(&encode(&counts, options.format), Some(path));
+    }
+}
 
-    for x in 0..256 {
-        for y in 0..256 {
-            let direction = tuple::vector(x as f64 - 128.0, y as f64 - 128.0, 135.0);
-            let ray = ray::ray(&point, &direction);
-            let ray2 = transform::transform(&ray, &scale);
-            let intlist = ray::intersect(&sphere, &ray2);
-            let hit = ray::hit(&intlist);
-            match hit {
-                None => {}
-                Some(_i) => {
-                    canvas::write_pixel(&mut canvas, x, y, &color);
-                }
-            }
+fn encode(image: &canvas::Canvas, format: cli::Format) -> Vec<u8> {
+    match format {
+        cli::Format::Ppm => {
+            let mut text = canvas::to_ppm(image).join("\n");
+            text.push('\n');
+            return text.into_bytes();
         }
+        cli::Format::Png => return canvas::to_png(image),
     }
+}
+
+fn demo() -> scene::Scene {
+    let mut sphere = shape::sphere();
+    shape::set_transform(&mut sphere, &transform::scaling(25.0, 25.0, 25.0));
+    let mut m = material::material();
+    m.color = color::color(0.0, 0.9, 1.0);
+    shape::set_material(&mut sphere, &m);
+
+    let mut camera = camera::camera(256, 256, 2.0 * Float::atan(128.0 / 135.0));
+    camera.transform = transform::view_transform(
+        &tuple::point(14.0, 19.0, -75.0),
+        &tuple::point(14.0, 19.0, 0.0),
+        &tuple::vector(0.0, -1.0, 0.0),
+    );
+    camera.samples = 4;
+    camera.max_samples = 64;
+    camera.sampler = sampler::Sampler::Jittered;
+    camera.filter = filter::mitchell();
+
+    // Up is -y for this camera, so the light is above and to the left.
+    let lamp = light::point_light(
+        &point3::point3(-40.0, -40.0, -100.0),
+        &color::color(1.0, 1.0, 1.0),
+    );
+
+    return scene::Scene {
+        camera,
+        lights: vec![lamp],
+        shapes: vec![sphere],
+        background: background::constant(&color::color(0.0, 0.0, 0.0)),
+        textures: Vec::new(),
+    };
+}
 
-    writeout(canvas)
+pub fn writeout(bytes: &[u8], path: Option<&str>) {
+    let result = match path {
+        None => io::stdout().lock().write_all(bytes),
+        Some(path) => match fs::File::create(path) {
+            Ok(mut file) => file.write_all(bytes),
+            Err(e) => {
+                eprintln!("raytracer: cannot create {}: {}", path, e);
+                process::exit(cli::EXIT_CANTCREAT);
+            }
+        },
+    };
+    if let Err(e) = result {
+        eprintln!("raytracer: cannot write image: {}", e);
+        process::exit(cli::EXIT_IOERR);
+    }
 }
 
-pub fn writeout(canvas: canvas::Canvas) {
-    let lines = canvas::to_ppm(&canvas);
-    for line in lines.iter() {
-        println!("{}", line);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytracer::random;
+
+    #[test]
+    fn should_light_the_demo_scene() {
+        let mut scene = demo();
+        camera::set_size(&mut scene.camera, 16, 16);
+        scene.camera.max_samples = 0;
+        let image = camera::render(&scene.camera, |r, rng: &mut random::Random| {
+            scene::color_at(&scene, r, 5, rng)
+        });
+        let mut lit = false;
+        for y in 0..16 {
+            for x in 0..16 {
+                let c = canvas::pixel_at(&image, x, y);
+                lit = lit || c.red > 0.0 || c.green > 0.0 || c.blue > 0.0;
+            }
+        }
+        assert!(lit);
     }
 }
@@ -0,0 +1,224 @@
+use crate::bsdf;
+use crate::bump;
+use crate::color;
+use crate::float::Float;
+use crate::light;
+use crate::normal3;
+use crate::point3;
+use crate::random;
+use crate::vector3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+    pub color: color::Color,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    pub reflective: Float,
+    pub transparency: Float,
+    pub refractive_index: Float,
+    // How the path tracer scatters light. The Whitted integrator ignores it
+    // and uses the Phong terms above.
+    pub bsdf: bsdf::Bsdf,
+    // Radiance given off by the surface is emission * emission_strength.
+    pub emission: color::Color,
+    pub emission_strength: Float,
+    // Replaces color when set: an index into the scene's textures.
+    pub texture: Option<usize>,
+    // Shading normal detail, also by scene texture index for normal maps.
+    // Applied normal map first, then bump.
+    pub normal_map: Option<usize>,
+    pub bump: Option<bump::Bump>,
+}
+
+pub fn material() -> Material {
+    return Material {
+        color: color::color(1.0, 1.0, 1.0),
+        ambient: 0.1,
+        diffuse: 0.9,
+        specular: 0.9,
+        shininess: 200.0,
+        reflective: 0.0,
+        transparency: 0.0,
+        refractive_index: 1.0,
+        bsdf: bsdf::lambertian(),
+        emission: color::color(0.0, 0.0, 0.0),
+        emission_strength: 1.0,
+        texture: None,
+        normal_map: None,
+        bump: None,
+    };
+}
+
+pub fn emitted(m: &Material) -> color::Color {
+    return color::multiply(&m.emission, m.emission_strength);
+}
+
+pub fn is_emissive(m: &Material) -> bool {
+    let e = emitted(m);
+    return e.red > 0.0 || e.green > 0.0 || e.blue > 0.0;
+}
+
+// Phong shading of point by one light: ambient, plus diffuse and specular
+// averaged over the light's sample points. visibility is the fraction of the
+// light point can see (see scene::visibility); shadow only dims the diffuse
+// and specular terms, so shadows are never pitch black.
+pub fn lighting(
+    m: &Material,
+    l: &light::Light,
+    point: &point3::Point3,
+    eyev: &vector3::Vector3,
+    normalv: &normal3::Normal3,
+    visibility: Float,
+    rng: &mut random::Random,
+) -> color::Color {
+    // Towards each sample, and what arrives from there.
+    let samples: Vec<(vector3::Vector3, color::Color)> = match l {
+        light::Light::Directional {
+            direction,
+            intensity,
+        } => vec![(-*direction, *intensity)],
+        _ => light::sample_points(l, rng)
+            .iter()
+            .map(|p| {
+                let lightv = vector3::normalize(&(*p - *point));
+                (lightv, light::intensity_at(l, p, point))
+            })
+            .collect(),
+    };
+    let black = color::color(0.0, 0.0, 0.0);
+    let (mut ambient, mut diffuse, mut specular) = (black, black, black);
+    for (lightv, intensity) in samples.iter() {
+        let effective = m.color * *intensity;
+        ambient += effective * m.ambient;
+        let light_dot_normal = normal3::dot(normalv, lightv);
+        if light_dot_normal < 0.0 {
+            continue;
+        }
+        diffuse += effective * (m.diffuse * light_dot_normal);
+        let reflectv = normal3::reflect(&-*lightv, normalv);
+        let reflect_dot_eye = vector3::dot(&reflectv, eyev);
+        if reflect_dot_eye > 0.0 {
+            specular += *intensity * (m.specular * reflect_dot_eye.powf(m.shininess));
+        }
+    }
+    let scale = 1.0 / samples.len().max(1) as Float;
+    return (ambient + (diffuse + specular) * visibility) * scale;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    // Page 85
+    #[test]
+    fn should_create_default_material() {
+        let m = material();
+        assert!(color::equals(&m.color, &color::color(1.0, 1.0, 1.0)));
+        assert!(float::equals(m.ambient, 0.1));
+        assert!(float::equals(m.diffuse, 0.9));
+        assert!(float::equals(m.specular, 0.9));
+        assert!(float::equals(m.shininess, 200.0));
+    }
+
+    #[test]
+    fn should_scale_emission_by_strength() {
+        let mut m = material();
+        assert!(!is_emissive(&m));
+        m.emission = color::color(1.0, 0.5, 0.0);
+        m.emission_strength = 4.0;
+        assert!(is_emissive(&m));
+        assert!(color::equals(&emitted(&m), &color::color(4.0, 2.0, 0.0)));
+    }
+
+    fn light_at(x: Float, y: Float, z: Float) -> light::Light {
+        return light::point_light(&point3::point3(x, y, z), &color::color(1.0, 1.0, 1.0));
+    }
+
+    fn lit(l: &light::Light, eyev: &vector3::Vector3, visibility: Float) -> color::Color {
+        let mut rng = random::random(0);
+        let point = point3::point3(0.0, 0.0, 0.0);
+        let normalv = normal3::normal3(0.0, 0.0, -1.0);
+        return lighting(&material(), l, &point, eyev, &normalv, visibility, &mut rng);
+    }
+
+    // Page 86
+    #[test]
+    fn should_light_with_eye_between_light_and_surface() {
+        let c = lit(
+            &light_at(0.0, 0.0, -10.0),
+            &vector3::vector3(0.0, 0.0, -1.0),
+            1.0,
+        );
+        assert!(color::equals(&c, &color::color(1.9, 1.9, 1.9)));
+    }
+
+    // Page 86
+    #[test]
+    fn should_light_with_eye_offset_45_degrees() {
+        let half = Float::sqrt(2.0) / 2.0;
+        let c = lit(
+            &light_at(0.0, 0.0, -10.0),
+            &vector3::vector3(0.0, half, -half),
+            1.0,
+        );
+        assert!(color::equals(&c, &color::color(1.0, 1.0, 1.0)));
+    }
+
+    // Page 87
+    #[test]
+    fn should_light_with_light_offset_45_degrees() {
+        let c = lit(
+            &light_at(0.0, 10.0, -10.0),
+            &vector3::vector3(0.0, 0.0, -1.0),
+            1.0,
+        );
+        assert!(color::equals(&c, &color::color(0.7364, 0.7364, 0.7364)));
+    }
+
+    // Page 87
+    #[test]
+    fn should_light_with_eye_in_reflection_path() {
+        let half = Float::sqrt(2.0) / 2.0;
+        let c = lit(
+            &light_at(0.0, 10.0, -10.0),
+            &vector3::vector3(0.0, -half, -half),
+            1.0,
+        );
+        assert!(color::equals(&c, &color::color(1.6364, 1.6364, 1.6364)));
+    }
+
+    // Page 88
+    #[test]
+    fn should_light_with_light_behind_surface() {
+        let c = lit(
+            &light_at(0.0, 0.0, 10.0),
+            &vector3::vector3(0.0, 0.0, -1.0),
+            1.0,
+        );
+        assert!(color::equals(&c, &color::color(0.1, 0.1, 0.1)));
+    }
+
+    // Page 110
+    #[test]
+    fn should_light_surface_in_shadow() {
+        let c = lit(
+            &light_at(0.0, 0.0, -10.0),
+            &vector3::vector3(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(color::equals(&c, &color::color(0.1, 0.1, 0.1)));
+    }
+
+    #[test]
+    fn should_scale_diffuse_and_specular_by_visibility() {
+        let c = lit(
+            &light_at(0.0, 0.0, -10.0),
+            &vector3::vector3(0.0, 0.0, -1.0),
+            0.5,
+        );
+        assert!(color::equals(&c, &color::color(1.0, 1.0, 1.0)));
+    }
+}
@@ -0,0 +1,357 @@
+// Square matrices of any size, indexed m[row][column]. Transforms only
+// ever use 4x4, which has its own module (matrix4) for the parts that
+// involve tuples or that a fixed size makes faster.
+
+use std::ops::{Index, IndexMut, Mul};
+
+use crate::float;
+use crate::float::Float;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Matrix<const N: usize>(pub [[Float; N]; N]);
+
+// Rounding leaves what should be a zero pivot or determinant at a few
+// Float::EPSILON times the size of what went into it, so anything within
+// this factor counts as zero.
+pub const SINGULAR: Float = 16.0 * Float::EPSILON;
+
+// LU decomposition with partial pivoting: row pivots[i] of the original
+// matrix is row i of L * U. Both triangles share lu, with L's diagonal of
+// ones left out.
+pub struct Lu<const N: usize> {
+    pub lu: Matrix<N>,
+    pub pivots: [usize; N],
+    // 1 or -1, by whether the rows were swapped an even number of times.
+    pub sign: Float,
+}
+
+pub fn matrix<const N: usize>() -> Matrix<N> {
+    return Matrix([[0.0; N]; N]);
+}
+
+pub fn identity<const N: usize>() -> Matrix<N> {
+    let mut m = matrix();
+    for i in 0..N {
+        m[i][i] = 1.0;
+    }
+    return m;
+}
+
+pub fn equals<const N: usize>(a: &Matrix<N>, b: &Matrix<N>) -> bool {
+    for i in 0..N {
+        for j in 0..N {
+            if !float::equals(a[i][j], b[i][j]) {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+pub fn multiply<const N: usize>(a: &Matrix<N>, b: &Matrix<N>) -> Matrix<N> {
+    let mut m = matrix();
+    for i in 0..N {
+        for j in 0..N {
+            for k in 0..N {
+                m[i][j] += a[i][k] * b[k][j];
+            }
+        }
+    }
+    return m;
+}
+
+pub fn transpose<const N: usize>(m: &Matrix<N>) -> Matrix<N> {
+    let mut t = matrix();
+    for i in 0..N {
+        for j in 0..N {
+            t[j][i] = m[i][j];
+        }
+    }
+    return t;
+}
+
+// m without row x and column y. M has to be N - 1, which stable Rust
+// cannot spell out in the signature, so it is checked at compile time.
+pub fn submatrix<const N: usize, const M: usize>(m: &Matrix<N>, x: usize, y: usize) -> Matrix<M> {
+    const { assert!(M + 1 == N) };
+    let mut sub = matrix();
+    let mut w = 0;
+    for i in 0..N {
+        if i != x {
+            let mut v = 0;
+            for j in 0..N {
+                if j != y {
+                    sub[w][v] = m[i][j];
+                    v += 1;
+                }
+            }
+            w += 1
+        }
+    }
+    return sub;
+}
+
+pub fn minor<const N: usize>(m: &Matrix<N>, x: usize, y: usize) -> Float {
+    if (x + y).is_multiple_of(2) {
+        return cofactor(m, x, y);
+    } else {
+        return -cofactor(m, x, y);
+    }
+}
+
+// Expanding along row x shows this is the determinant of m with row x and
+// column y cleared and a 1 where they cross, which keeps it at size N.
+pub fn cofactor<const N: usize>(m: &Matrix<N>, x: usize, y: usize) -> Float {
+    let mut a = *m;
+    for i in 0..N {
+        a[x][i] = 0.0;
+        a[i][y] = 0.0;
+    }
+    a[x][y] = 1.0;
+    return determinant(&a);
+}
+
+// The product of U's diagonal, which is exact for 2x2 and far cheaper than
+// cofactor expansion beyond that.
+pub fn determinant<const N: usize>(m: &Matrix<N>) -> Float {
+    match lu(m) {
+        Some(d) => {
+            let mut det = d.sign;
+            for i in 0..N {
+                det *= d.lu[i][i];
+            }
+            return det;
+        }
+        None => return 0.0,
+    }
+}
+
+// Asks for the inverse rather than comparing the determinant to a fixed
+// tolerance, which would depend on the size of the entries.
+pub fn is_invertible<const N: usize>(m: &Matrix<N>) -> bool {
+    return inverse(m).is_some();
+}
+
+// None when a column has nothing left to pivot on, which makes m singular.
+// Taking the largest pivot each time keeps rounding errors from growing.
+// A pivot lost in the rounding of m's largest entry is as good as none.
+pub fn lu<const N: usize>(m: &Matrix<N>) -> Option<Lu<N>> {
+    let tiny = SINGULAR * largest(m);
+    let mut a = *m;
+    let mut pivots = [0; N];
+    for (i, p) in pivots.iter_mut().enumerate() {
+        *p = i;
+    }
+    let mut sign = 1.0;
+    for k in 0..N {
+        let mut p = k;
+        for i in k + 1..N {
+            if a[i][k].abs() > a[p][k].abs() {
+                p = i;
+            }
+        }
+        if a[p][k].abs() <= tiny {
+            return None;
+        }
+        if p != k {
+            a.0.swap(p, k);
+            pivots.swap(p, k);
+            sign = -sign;
+        }
+        for i in k + 1..N {
+            let factor = a[i][k] / a[k][k];
+            a[i][k] = factor;
+            for j in k + 1..N {
+                a[i][j] -= factor * a[k][j];
+            }
+        }
+    }
+    return Some(Lu {
+        lu: a,
+        pivots,
+        sign,
+    });
+}
+
+// The largest entry by magnitude, which sets the scale of m's rounding
+// errors.
+pub fn largest<const N: usize>(m: &Matrix<N>) -> Float {
+    let mut l: Float = 0.0;
+    for row in m.0.iter() {
+        for v in row.iter() {
+            l = l.max(v.abs());
+        }
+    }
+    return l;
+}
+
+// Solves m x = b given m's decomposition.
+pub fn solve<const N: usize>(d: &Lu<N>, b: &[Float; N]) -> [Float; N] {
+    let a = &d.lu;
+    let mut x = [0.0; N];
+    for i in 0..N {
+        x[i] = b[d.pivots[i]];
+        for j in 0..i {
+            x[i] -= a[i][j] * x[j];
+        }
+    }
+    for i in (0..N).rev() {
+        for j in i + 1..N {
+            x[i] -= a[i][j] * x[j];
+        }
+        x[i] /= a[i][i];
+    }
+    return x;
+}
+
+// None for a singular matrix, or one so nearly singular that the inverse
+// overflows.
+pub fn inverse<const N: usize>(m: &Matrix<N>) -> Option<Matrix<N>> {
+    let d = lu(m)?;
+    let mut inv = matrix();
+    for j in 0..N {
+        let mut column = [0.0; N];
+        column[j] = 1.0;
+        let x = solve(&d, &column);
+        for i in 0..N {
+            if !x[i].is_finite() {
+                return None;
+            }
+            inv[i][j] = x[i];
+        }
+    }
+    return Some(inv);
+}
+
+impl<const N: usize> Index<usize> for Matrix<N> {
+    type Output = [Float; N];
+    fn index(&self, row: usize) -> &[Float; N] {
+        return &self.0[row];
+    }
+}
+
+impl<const N: usize> IndexMut<usize> for Matrix<N> {
+    fn index_mut(&mut self, row: usize) -> &mut [Float; N] {
+        return &mut self.0[row];
+    }
+}
+
+impl<const N: usize> PartialEq for Matrix<N> {
+    fn eq(&self, other: &Matrix<N>) -> bool {
+        return equals(self, other);
+    }
+}
+
+impl<const N: usize> Mul for Matrix<N> {
+    type Output = Matrix<N>;
+    fn mul(self, other: Matrix<N>) -> Matrix<N> {
+        return multiply(&self, &other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_create_2x2() {
+        let m = Matrix([[-3.0, 5.0], [1.0, -2.0]]);
+        assert!(float::equals(m[0][0], -3.0));
+        assert!(float::equals(m[0][1], 5.0));
+        assert!(float::equals(m[1][0], 1.0));
+        assert!(float::equals(m[1][1], -2.0));
+    }
+
+    #[test]
+    fn should_create_3x3() {
+        let m = Matrix([[-3.0, 5.0, 0.0], [1.0, -2.0, -7.0], [0.0, 1.0, 1.0]]);
+        assert!(float::equals(m[0][0], -3.0));
+        assert!(float::equals(m[1][1], -2.0));
+        assert!(float::equals(m[2][2], 1.0));
+    }
+
+    #[test]
+    fn should_calculate_2x2_determinant() {
+        let a = Matrix([[1.0, 5.0], [-3.0, 2.0]]);
+        assert!(float::equals(determinant(&a), 17.0));
+    }
+
+    #[test]
+    fn should_calculate_3x3_submatrix() {
+        let a = Matrix([[1.0, 5.0, 0.0], [-3.0, 2.0, 7.0], [0.0, 6.0, -3.0]]);
+        let expected = Matrix([[-3.0, 2.0], [0.0, 6.0]]);
+        let result = submatrix(&a, 0, 2);
+        assert!(equals(&result, &expected));
+    }
+
+    #[test]
+    fn should_calculate_minor() {
+        let a = Matrix([[3.0, 5.0, 0.0], [2.0, -1.0, -7.0], [6.0, -1.0, 5.0]]);
+        let b: Matrix<2> = submatrix(&a, 1, 0);
+        assert!(float::equals(determinant(&b), 25.0));
+        assert!(float::equals(minor(&a, 1, 0), 25.0));
+    }
+
+    #[test]
+    fn should_calculate_cofactor() {
+        let a = Matrix([[3.0, 5.0, 0.0], [2.0, -1.0, -7.0], [6.0, -1.0, 5.0]]);
+        assert!(float::equals(minor(&a, 0, 0), -12.0));
+        assert!(float::equals(cofactor(&a, 0, 0), -12.0));
+        assert!(float::equals(minor(&a, 1, 0), 25.0));
+        assert!(float::equals(cofactor(&a, 1, 0), -25.0));
+    }
+
+    #[test]
+    fn should_calculate_3x3_determinant() {
+        let a = Matrix([[1.0, 2.0, 6.0], [-5.0, 8.0, -4.0], [2.0, 6.0, 4.0]]);
+        assert!(float::equals(cofactor(&a, 0, 0), 56.0));
+        assert!(float::equals(cofactor(&a, 0, 1), 12.0));
+        assert!(float::equals(cofactor(&a, 0, 2), -46.0));
+        assert!(float::equals(determinant(&a), -196.0));
+    }
+
+    #[test]
+    fn should_decompose_with_pivoting() {
+        // The first pivot is zero, so rows have to be swapped.
+        let a = Matrix([[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [3.0, 0.0, 4.0]]);
+        let d = lu(&a).unwrap();
+        let mut l = identity::<3>();
+        let mut u = matrix::<3>();
+        for i in 0..3 {
+            for j in 0..3 {
+                if j < i {
+                    l[i][j] = d.lu[i][j];
+                } else {
+                    u[i][j] = d.lu[i][j];
+                }
+            }
+        }
+        let product = multiply(&l, &u);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!(float::equals(product[i][j], a[d.pivots[i]][j]));
+            }
+        }
+        assert!(float::equals(determinant(&a), -11.0));
+        let swap = Matrix([[0.0, 1.0], [1.0, 0.0]]);
+        assert!(float::equals(determinant(&swap), -1.0));
+        let singular = Matrix([[1.0, 2.0], [2.0, 4.0]]);
+        assert!(lu(&singular).is_none());
+        assert!(inverse(&singular).is_none());
+    }
+
+    #[test]
+    fn should_invert_any_size() {
+        let a = Matrix([[4.0, -2.0, 1.0], [3.0, 6.0, -4.0], [2.0, 1.0, 8.0]]);
+        assert!(equals(&multiply(&a, &inverse(&a).unwrap()), &identity()));
+        let mut big = identity::<6>();
+        for i in 0..6 {
+            for j in 0..6 {
+                big[i][j] += ((i * 7 + j * 3) % 5) as Float - 2.0;
+            }
+        }
+        let inv = inverse(&big).unwrap();
+        assert!(equals(&(big * inv), &identity()));
+        assert!(equals(&transpose(&transpose(&big)), &big));
+    }
+}
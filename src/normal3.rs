@@ -0,0 +1,100 @@
+// Surface normals. They look like vectors but transform differently: by
+// the inverse transpose of a shape's transform (matrix4::multiply_normal),
+// so keeping them a separate type stops a normal being moved like a vector.
+
+use std::ops::Neg;
+
+use crate::float;
+use crate::float::Float;
+use crate::tuple;
+use crate::vector3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Normal3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+pub fn normal3(x: Float, y: Float, z: Float) -> Normal3 {
+    return Normal3 { x, y, z };
+}
+
+pub fn from_vector(v: &vector3::Vector3) -> Normal3 {
+    return normal3(v.x, v.y, v.z);
+}
+
+pub fn to_vector(n: &Normal3) -> vector3::Vector3 {
+    return vector3::vector3(n.x, n.y, n.z);
+}
+
+pub fn negate(n: &Normal3) -> Normal3 {
+    return normal3(-n.x, -n.y, -n.z);
+}
+
+pub fn normalize(n: &Normal3) -> Normal3 {
+    return from_vector(&vector3::normalize(&to_vector(n)));
+}
+
+pub fn dot(n: &Normal3, v: &vector3::Vector3) -> Float {
+    return n.x * v.x + n.y * v.y + n.z * v.z;
+}
+
+// n flipped if needed to lie on the same side as v.
+pub fn face_forward(n: &Normal3, v: &vector3::Vector3) -> Normal3 {
+    if dot(n, v) < 0.0 {
+        return negate(n);
+    }
+    return *n;
+}
+
+// v bounced off a surface with unit normal n.
+pub fn reflect(v: &vector3::Vector3, n: &Normal3) -> vector3::Vector3 {
+    return *v - to_vector(n) * (2.0 * dot(n, v));
+}
+
+pub fn equals(a: &Normal3, b: &Normal3) -> bool {
+    return float::equals(a.x, b.x) && float::equals(a.y, b.y) && float::equals(a.z, b.z);
+}
+
+// Compatibility with code still using tuple::Tuple, where normals are
+// vectors.
+pub fn from_tuple(t: &tuple::Tuple) -> Normal3 {
+    return from_vector(&vector3::from_tuple(t));
+}
+
+pub fn to_tuple(n: &Normal3) -> tuple::Tuple {
+    return vector3::to_tuple(&to_vector(n));
+}
+
+impl Neg for Normal3 {
+    type Output = Normal3;
+    fn neg(self) -> Normal3 {
+        return negate(&self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_reflect_off_slanted_surface() {
+        let half = Float::sqrt(2.0) / 2.0;
+        let v = vector3::vector3(0.0, -1.0, 0.0);
+        let r = reflect(&v, &normal3(half, half, 0.0));
+        assert!(vector3::equals(&r, &vector3::vector3(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn should_face_forward() {
+        let n = normal3(0.0, 0.0, 2.0);
+        let ahead = vector3::vector3(1.0, 0.0, 1.0);
+        let behind = vector3::vector3(1.0, 0.0, -1.0);
+        assert!(equals(&face_forward(&n, &ahead), &n));
+        assert!(equals(&face_forward(&n, &behind), &normal3(0.0, 0.0, -2.0)));
+        assert!(equals(&normalize(&n), &normal3(0.0, 0.0, 1.0)));
+        assert!(float::equals(dot(&n, &ahead), 2.0));
+        assert!(tuple::equals(&to_tuple(&n), &tuple::vector(0.0, 0.0, 2.0)));
+    }
+}
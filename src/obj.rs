@@ -0,0 +1,228 @@
+// Wavefront OBJ meshes. Vertices (v), texture coordinates (vt), vertex
+// normals (vn) and faces (f) are read; a face with more than three corners
+// is split into a fan of triangles around its first. Everything else, such
+// as groups, materials and smoothing, is skipped.
+
+use crate::float::Float;
+use crate::normal3;
+use crate::point3;
+use crate::shape;
+use crate::vector3;
+
+// One corner of a face: its position, and its texture coordinates and normal
+// if the file gives them.
+type Corner = (
+    point3::Point3,
+    Option<(Float, Float)>,
+    Option<normal3::Normal3>,
+);
+
+pub fn parse(text: &str) -> Result<Vec<shape::Shape>, String> {
+    let mut vertices = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut triangles = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let at = |message: &str| format!("line {}: {}", i + 1, message);
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(w) => w,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        match keyword {
+            "v" => {
+                let (x, y, z) = numbers(&args).map_err(|e| at(&e))?;
+                vertices.push(point3::point3(x, y, z));
+            }
+            "vt" => uvs.push(texture_coordinates(&args).map_err(|e| at(&e))?),
+            "vn" => {
+                let (x, y, z) = numbers(&args).map_err(|e| at(&e))?;
+                normals.push(normal3::normal3(x, y, z));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(at("a face needs at least 3 corners"));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for a in args.iter() {
+                    corners.push(corner(a, &vertices, &uvs, &normals).map_err(|e| at(&e))?);
+                }
+                for k in 1..corners.len() - 1 {
+                    if let Some(t) = triangle(&corners[0], &corners[k], &corners[k + 1]) {
+                        triangles.push(t);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    return Ok(triangles);
+}
+
+// x, y and z, ignoring the optional w of a vertex.
+fn numbers(args: &[&str]) -> Result<(Float, Float, Float), String> {
+    if args.len() < 3 || args.len() > 4 {
+        return Err(format!("expected 3 numbers, found {}", args.len()));
+    }
+    let mut v = [0.0; 3];
+    for i in 0..3 {
+        v[i] = match args[i].parse::<Float>() {
+            Ok(n) if n.is_finite() => n,
+            _ => return Err(format!("invalid number '{}'", args[i])),
+        };
+    }
+    return Ok((v[0], v[1], v[2]));
+}
+
+// u and v; v defaults to 0 and the optional depth w is ignored.
+fn texture_coordinates(args: &[&str]) -> Result<(Float, Float), String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(format!("expected 1 to 3 numbers, found {}", args.len()));
+    }
+    let mut t = [0.0; 2];
+    for i in 0..args.len().min(2) {
+        t[i] = match args[i].parse::<Float>() {
+            Ok(n) if n.is_finite() => n,
+            _ => return Err(format!("invalid number '{}'", args[i])),
+        };
+    }
+    return Ok((t[0], t[1]));
+}
+
+// A corner is v, v/vt, v//vn or v/vt/vn. Indices count from 1, or back from
+// the last one read if negative.
+fn corner(
+    text: &str,
+    vertices: &[point3::Point3],
+    uvs: &[(Float, Float)],
+    normals: &[normal3::Normal3],
+) -> Result<Corner, String> {
+    let mut parts = text.split('/');
+    let vertex = lookup(parts.next().unwrap_or(""), vertices, "vertex")?;
+    let uv = match parts.next() {
+        Some(t) if !t.is_empty() => Some(lookup(t, uvs, "texture coordinate")?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(n) if !n.is_empty() => Some(lookup(n, normals, "normal")?),
+        _ => None,
+    };
+    return Ok((vertex, uv, normal));
+}
+
+fn lookup<T: Copy>(index: &str, list: &[T], what: &str) -> Result<T, String> {
+    let i = match index.parse::<i64>() {
+        Ok(i) if i > 0 => i - 1,
+        Ok(i) if i < 0 => list.len() as i64 + i,
+        _ => return Err(format!("invalid {} index '{}'", what, index)),
+    };
+    if i < 0 || i >= list.len() as i64 {
+        return Err(format!("{} {} does not exist", what, index));
+    }
+    return Ok(list[i as usize]);
+}
+
+// Corners on one line make no surface, so they give no triangle. Normals and
+// texture coordinates are only blended when every corner has them.
+fn triangle(a: &Corner, b: &Corner, c: &Corner) -> Option<shape::Shape> {
+    let area = vector3::cross(&(b.0 - a.0), &(c.0 - a.0));
+    if vector3::magnitude(&area) == 0.0 {
+        return None;
+    }
+    let mut t = match (a.2, b.2, c.2) {
+        (Some(n1), Some(n2), Some(n3)) => shape::smooth_triangle(&a.0, &b.0, &c.0, &n1, &n2, &n3),
+        _ => shape::triangle(&a.0, &b.0, &c.0),
+    };
+    if let (Some(t1), Some(t2), Some(t3)) = (a.1, b.1, c.1) {
+        shape::set_uvs(&mut t, &[t1, t2, t3]);
+    }
+    return Some(t);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    fn corners(s: &shape::Shape) -> (point3::Point3, point3::Point3, point3::Point3) {
+        match s {
+            shape::Shape::Triangle { p1, p2, p3, .. } => return (*p1, *p2, *p3),
+            _ => panic!("expected a triangle"),
+        }
+    }
+
+    #[test]
+    fn should_ignore_unrecognized_lines() {
+        let text = "There was a young lady named Bright\nwho traveled much faster than light.\n";
+        assert!(parse(text).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_parse_triangle_faces() {
+        let text = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 3\nf 1 3 4\n";
+        let t = parse(text).unwrap();
+        assert!(t.len() == 2);
+        let (p1, p2, p3) = corners(&t[1]);
+        assert!(point3::equals(&p1, &point3::point3(-1.0, 1.0, 0.0)));
+        assert!(point3::equals(&p2, &point3::point3(1.0, 0.0, 0.0)));
+        assert!(point3::equals(&p3, &point3::point3(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn should_triangulate_polygons() {
+        let text = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nv 0 2 0\n\nf 1 2 3 4 5\n";
+        let t = parse(text).unwrap();
+        assert!(t.len() == 3);
+        let (p1, _, p3) = corners(&t[2]);
+        assert!(point3::equals(&p1, &point3::point3(-1.0, 1.0, 0.0)));
+        assert!(point3::equals(&p3, &point3::point3(0.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn should_blend_vertex_normals() {
+        let text = "v 0 1 0\nv -1 0 0\nv 1 0 0\nvn -1 0 0\nvn 1 0 0\nvn 0 1 0\nf 1//3 2//1 3//2\nf 1//3 2//1 -1//-2\n";
+        let t = parse(text).unwrap();
+        assert!(t.len() == 2);
+        for s in t.iter() {
+            let n = shape::local_normal_at(s, &point3::point3(0.0, 1.0, 0.0));
+            assert!(normal3::equals(&n, &normal3::normal3(0.0, 1.0, 0.0)));
+            let n = shape::local_normal_at(s, &point3::point3(-1.0, 0.0, 0.0));
+            assert!(normal3::equals(&n, &normal3::normal3(-1.0, 0.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn should_blend_texture_coordinates() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0.5 1 0\nvn 0 0 1\nf 1/1 2/2 3/3\nf 1/1/1 2/2/1 3/-1/1\nf 1 2/2 3/3\n";
+        let t = parse(text).unwrap();
+        assert!(t.len() == 3);
+        for s in t[0..2].iter() {
+            let (u, v) = shape::uv_at(s, &point3::point3(0.0, 1.0, 0.0)).unwrap();
+            assert!(float::equals(u, 0.5) && float::equals(v, 1.0));
+            let (u, v) = shape::uv_at(s, &point3::point3(0.5, 0.0, 0.0)).unwrap();
+            assert!(float::equals(u, 0.5) && float::equals(v, 0.0));
+        }
+        assert!(shape::uv_at(&t[2], &point3::point3(0.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn should_skip_degenerate_faces() {
+        let text = "v 0 0 0\nv 1 0 0\nv 2 0 0\nf 1 2 3\n";
+        assert!(parse(text).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_report_errors_with_line_numbers() {
+        let e = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").err().unwrap();
+        assert!(e == "line 3: vertex 3 does not exist");
+        let e = parse("v 0 zero 0\n").err().unwrap();
+        assert!(e == "line 1: invalid number 'zero'");
+        let e = parse("v 0 0 0\n\nf 1 1\n").err().unwrap();
+        assert!(e == "line 3: a face needs at least 3 corners");
+        let e = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n")
+            .err()
+            .unwrap();
+        assert!(e == "line 4: texture coordinate 1 does not exist");
+    }
+}
@@ -0,0 +1,569 @@
+// Path tracing integrator. Each camera ray follows one random path through
+// the scene: at every bounce the lights are sampled directly (next event
+// estimation) and the path continues in a direction importance sampled from
+// the material's BSDF. After a few bounces Russian roulette ends dim paths,
+// boosting the survivors so the estimate stays unbiased, and no path goes
+// on past max_depth bounces. Many samples per
+// pixel are needed to bring the noise down; the camera averages them.
+//
+// Emissive shapes are lights too. They are reached both by sampling a point
+// on one of them and by a bounce happening to hit one; multiple importance
+// sampling (the power heuristic) weighs the two so neither is counted twice.
+// Triangles, such as those of a mesh loaded from OBJ, emit from both faces.
+
+use crate::float::consts::PI;
+use crate::float::Float;
+
+use crate::background;
+use crate::bsdf;
+use crate::color;
+use crate::light;
+use crate::material;
+use crate::normal3;
+use crate::point3;
+use crate::random;
+use crate::ray;
+use crate::scene;
+use crate::shape;
+use crate::stats;
+use crate::vector3;
+
+// Bounces before Russian roulette may end a path.
+pub const MIN_BOUNCES: usize = 3;
+
+// Where light is gathered: the shading point and normal, the direction back
+// along the incoming ray, the time of the path, and how far new rays start
+// off the surface.
+struct Surface {
+    point: point3::Point3,
+    normal: normal3::Normal3,
+    wo: vector3::Vector3,
+    time: Float,
+    offset: Float,
+}
+
+pub fn trace(
+    scene: &scene::Scene,
+    r: &ray::Ray,
+    max_depth: usize,
+    rng: &mut random::Random,
+) -> color::Color {
+    let mut radiance = color::color(0.0, 0.0, 0.0);
+    let mut throughput = color::color(1.0, 1.0, 1.0);
+    let mut r = ray::ray_at_time(&r.origin, &r.direction, r.time);
+    let mut bounce = 0;
+    let mut bsdf_pdf = 0.0;
+    // A path happens at one instant, so moving emitters stand still for it.
+    let emitters: Vec<shape::Shape> = scene::emitters(scene)
+        .iter()
+        .map(|e| shape::at_time(e, r.time))
+        .filter(shape::is_invertible)
+        .collect();
+    loop {
+        let hit = match scene::intersect(scene, &r) {
+            Some(h) => h,
+            None => {
+                let direction = r.direction;
+                let weight = if bounce == 0 {
+                    1.0
+                } else {
+                    let env_pdf = background::pdf(&scene.background, &direction);
+                    power_heuristic(bsdf_pdf, env_pdf)
+                };
+                let le = background::radiance(&scene.background, &direction);
+                return radiance + throughput * le * weight;
+            }
+        };
+        let hit_point = ray::position(&r, hit.t);
+        let shading = scene::shading_normal(scene, &hit.s, &hit_point);
+        let mut m = shape::material(&hit.s);
+        m.color = scene::surface_color(scene, &hit.s, &hit_point);
+        let normal = shape::normal_at(&hit.s, &hit_point);
+        let wo = -r.direction;
+
+        let facing = normal3::dot(&normal, &wo) > 0.0 || shape::is_two_sided(&hit.s);
+        if material::is_emissive(&m) && facing {
+            let weight = if bounce == 0 {
+                1.0
+            } else {
+                let light_pdf = emitter_pdf(emitters.len(), &hit.s, &r.origin, &hit_point, &normal);
+                power_heuristic(bsdf_pdf, light_pdf)
+            };
+            radiance += throughput * material::emitted(&m) * weight;
+        }
+
+        // Shading uses the normal and bump mapped normal; the new ray still
+        // leaves from the true surface.
+        let at = Surface {
+            point: hit_point,
+            normal: shading,
+            wo,
+            time: r.time,
+            offset: ray::offset_distance(&hit_point, &r.origin, scene.camera.ray_epsilon),
+        };
+        let mut direct = emissive_light(scene, &emitters, &m, &at, rng);
+        direct += environment_light(scene, &m, &at, rng);
+        for l in scene.lights.iter() {
+            direct += direct_light(scene, l, &m, &at, rng);
+        }
+        radiance += throughput * direct;
+        if bounce >= max_depth {
+            return radiance;
+        }
+
+        let s = match bsdf::sample(&m, &at.normal, &wo, rng) {
+            Some(s) => s,
+            None => return radiance,
+        };
+        throughput *= s.weight;
+        bsdf_pdf = s.pdf;
+        bounce += 1;
+        if bounce >= MIN_BOUNCES {
+            let survive = throughput
+                .red
+                .max(throughput.green)
+                .max(throughput.blue)
+                .min(0.95);
+            if random::next_float(rng) >= survive {
+                return radiance;
+            }
+            throughput /= survive;
+        }
+        let start = offset(&hit_point, &normal, &s.direction, at.offset);
+        stats::scatter_ray();
+        r = ray::ray_at_time(&start, &s.direction, r.time);
+    }
+}
+
+// Light reflected towards wo from l, averaged over the light's samples that
+// are not in shadow. Light intensities use the book's units, where a white
+// diffuse surface facing a unit light reflects its full color; that is pi
+// times the physically based value.
+fn direct_light(
+    scene: &scene::Scene,
+    l: &light::Light,
+    m: &material::Material,
+    at: &Surface,
+    rng: &mut random::Random,
+) -> color::Color {
+    let (point, normal, wo) = (&at.point, &at.normal, &at.wo);
+    let black = color::color(0.0, 0.0, 0.0);
+    if let light::Light::Directional {
+        direction,
+        intensity,
+    } = l
+    {
+        let wi = -*direction;
+        let f = bsdf::evaluate(m, normal, wo, &wi);
+        let start = offset(point, normal, &wi, at.offset);
+        if color::equals(&f, &black) || scene::visibility(scene, l, &start, at.time, rng) == 0.0 {
+            return black;
+        }
+        let cos = normal3::dot(normal, &wi).abs();
+        return *intensity * f * (PI * cos);
+    }
+    let samples = light::sample_points(l, rng);
+    let mut sum = black;
+    for p in samples.iter() {
+        let wi = vector3::normalize(&(*p - *point));
+        let f = bsdf::evaluate(m, normal, wo, &wi);
+        if color::equals(&f, &black) {
+            continue;
+        }
+        if !scene::is_shadowed(scene, &offset(point, normal, &wi, at.offset), p, at.time) {
+            let cos = normal3::dot(normal, &wi).abs();
+            sum += light::intensity_at(l, p, point) * f * cos;
+        }
+    }
+    let scale = PI / samples.len() as Float;
+    return sum * scale;
+}
+
+// Samples a point on one emissive shape, chosen uniformly, and weighs it
+// against the chance of the BSDF having picked the same direction.
+fn emissive_light(
+    scene: &scene::Scene,
+    emitters: &[shape::Shape],
+    m: &material::Material,
+    at: &Surface,
+    rng: &mut random::Random,
+) -> color::Color {
+    let (point, normal, wo) = (&at.point, &at.normal, &at.wo);
+    let black = color::color(0.0, 0.0, 0.0);
+    if emitters.is_empty() {
+        return black;
+    }
+    let pick =
+        ((random::next_float(rng) * emitters.len() as Float) as usize).min(emitters.len() - 1);
+    let e = &emitters[pick];
+    let (lp, mut ln) = shape::sample_surface(e, rng);
+    let wi = vector3::normalize(&(lp - *point));
+    if normal3::dot(&ln, &wi) >= 0.0 {
+        if !shape::is_two_sided(e) {
+            return black;
+        }
+        ln = -ln;
+    }
+    let f = bsdf::evaluate(m, normal, wo, &wi);
+    if color::equals(&f, &black) {
+        return black;
+    }
+    // Stop just short of the emitter so it does not shadow itself.
+    let distance = ray::offset_distance(&lp, point, scene.camera.ray_epsilon);
+    let target = lp + normal3::to_vector(&ln) * distance;
+    if scene::is_shadowed(
+        scene,
+        &offset(point, normal, &wi, at.offset),
+        &target,
+        at.time,
+    ) {
+        return black;
+    }
+    let light_pdf = emitter_pdf(emitters.len(), e, point, &lp, &ln);
+    let weight = power_heuristic(light_pdf, bsdf::pdf(m, normal, wo, &wi));
+    let cos = normal3::dot(normal, &wi).abs();
+    let le = material::emitted(&shape::material(e));
+    return le * f * (cos * weight / light_pdf);
+}
+
+// Samples a bright direction of the environment map, if there is one.
+fn environment_light(
+    scene: &scene::Scene,
+    m: &material::Material,
+    at: &Surface,
+    rng: &mut random::Random,
+) -> color::Color {
+    let (point, normal, wo) = (&at.point, &at.normal, &at.wo);
+    let black = color::color(0.0, 0.0, 0.0);
+    let (wi, env_pdf) = match background::sample(&scene.background, rng) {
+        Some(s) => s,
+        None => return black,
+    };
+    let f = bsdf::evaluate(m, normal, wo, &wi);
+    if color::equals(&f, &black) {
+        return black;
+    }
+    if scene::is_blocked(
+        scene,
+        &offset(point, normal, &wi, at.offset),
+        &wi,
+        Float::INFINITY,
+        at.time,
+    ) {
+        return black;
+    }
+    let weight = power_heuristic(env_pdf, bsdf::pdf(m, normal, wo, &wi));
+    let cos = normal3::dot(normal, &wi).abs();
+    let le = background::radiance(&scene.background, &wi);
+    return le * f * (cos * weight / env_pdf);
+}
+
+// Solid angle density, seen from from, of emissive_light choosing point on
+// shape.
+fn emitter_pdf(
+    count: usize,
+    shape: &shape::Shape,
+    from: &point3::Point3,
+    point: &point3::Point3,
+    normal: &normal3::Normal3,
+) -> Float {
+    let v = *point - *from;
+    let distance2 = vector3::dot(&v, &v);
+    let cos = normal3::dot(normal, &vector3::normalize(&v)).abs();
+    if cos == 0.0 {
+        return 0.0;
+    }
+    return shape::surface_pdf(shape, point) * distance2 / cos / count as Float;
+}
+
+fn power_heuristic(a: Float, b: Float) -> Float {
+    if a == 0.0 {
+        return 0.0;
+    }
+    return a * a / (a * a + b * b);
+}
+
+// Nudges point off the surface on the side direction leaves from.
+fn offset(
+    point: &point3::Point3,
+    normal: &normal3::Normal3,
+    direction: &vector3::Vector3,
+    distance: Float,
+) -> point3::Point3 {
+    let side = if normal3::dot(normal, direction) < 0.0 {
+        -distance
+    } else {
+        distance
+    };
+    return *point + normal3::to_vector(normal) * side;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera;
+    use crate::canvas;
+    use crate::float;
+    use crate::matrix;
+    use crate::transform;
+
+    fn scene(lights: Vec<light::Light>, shapes: Vec<shape::Shape>) -> scene::Scene {
+        return scene::Scene {
+            camera: camera::camera(1, 1, PI / 2.0),
+            lights,
+            shapes,
+            background: background::constant(&color::color(0.0, 0.0, 0.0)),
+            textures: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn should_return_black_on_miss() {
+        let s = scene(Vec::new(), vec![shape::sphere()]);
+        let r = ray::ray(
+            &point3::point3(0.0, 5.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        assert!(color::equals(
+            &trace(&s, &r, 5, &mut rng),
+            &color::color(0.0, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_light_directly() {
+        // A lone convex sphere cannot light itself, so only the direct term
+        // contributes: color * diffuse * intensity * cos.
+        let l = light::point_light(
+            &point3::point3(0.0, 0.0, -10.0),
+            &color::color(1.0, 1.0, 1.0),
+        );
+        let s = scene(vec![l], vec![shape::sphere()]);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        for _ in 0..10 {
+            let c = trace(&s, &r, 5, &mut rng);
+            assert!(float::equals(c.red, 0.9));
+        }
+    }
+
+    #[test]
+    fn should_gather_indirect_light() {
+        // Inside a closed sphere, the floor under a small occluder gets no
+        // direct light but is lit by the walls.
+        let mut room = shape::sphere();
+        shape::set_transform(&mut room, &transform::scaling(5.0, 5.0, 5.0));
+        let mut occluder = shape::sphere();
+        shape::set_transform(&mut occluder, &transform::translation(0.0, -2.0, 0.0));
+        let l = light::point_light(&point3::point3(0.0, 3.0, 0.0), &color::color(1.0, 1.0, 1.0));
+        let s = scene(vec![l], vec![room, occluder]);
+        let r = ray::ray(
+            &point3::point3(2.0, -4.0, 0.0),
+            &vector3::normalize(&vector3::vector3(-2.0, -1.0, 0.0)),
+        );
+        let mut rng = random::random(3);
+        let mut sum = 0.0;
+        for _ in 0..200 {
+            sum += trace(&s, &r, 5, &mut rng).red;
+        }
+        assert!(sum / 200.0 > 0.05);
+    }
+
+    fn emitter(strength: Float) -> shape::Shape {
+        let mut e = shape::sphere();
+        let mut m = material::material();
+        m.color = color::color(0.0, 0.0, 0.0);
+        m.emission = color::color(1.0, 1.0, 1.0);
+        m.emission_strength = strength;
+        shape::set_material(&mut e, &m);
+        return e;
+    }
+
+    #[test]
+    fn should_see_emission_directly() {
+        let s = scene(Vec::new(), vec![emitter(3.0)]);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        let c = trace(&s, &r, 5, &mut rng);
+        assert!(color::equals(&c, &color::color(3.0, 3.0, 3.0)));
+    }
+
+    #[test]
+    fn should_light_from_emissive_sphere() {
+        // A unit sphere of radiance L at distance 4 subtends sin^2 = 1/16,
+        // so a facing diffuse surface of albedo 0.9 reflects 0.9 * L / 16.
+        let mut e = emitter(16.0);
+        shape::set_transform(&mut e, &transform::translation(0.0, 4.0, 0.0));
+        let mut ground = shape::sphere();
+        let m = matrix::multiply(
+            &transform::translation(0.0, -1000.0, 0.0),
+            &transform::scaling(1000.0, 1000.0, 1000.0),
+        );
+        shape::set_transform(&mut ground, &m);
+        let s = scene(Vec::new(), vec![e, ground]);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.5, -2.0),
+            &vector3::normalize(&vector3::vector3(0.0, -0.5, 2.0)),
+        );
+        let mut rng = random::random(21);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            sum += trace(&s, &r, 5, &mut rng).red;
+        }
+        assert!((sum / 10000.0 - 0.9).abs() < 0.05);
+    }
+
+    #[test]
+    fn should_light_from_emissive_mesh() {
+        // A 2x2 quad of radiance L, 1 above a facing diffuse surface of
+        // albedo 0.9, has a form factor of about 0.554, so the surface
+        // reflects about 0.9 * 0.554 * L. The quad's corners wind away from
+        // the surface, which must not matter.
+        let mut m = material::material();
+        m.color = color::color(0.0, 0.0, 0.0);
+        m.emission = color::color(1.0, 1.0, 1.0);
+        let mut shapes = Vec::new();
+        for (p2, p3) in [((1.0, -1.0), (1.0, 1.0)), ((1.0, 1.0), (-1.0, 1.0))] {
+            let mut t = shape::triangle(
+                &point3::point3(-1.0, 1.0, -1.0),
+                &point3::point3(p2.0, 1.0, p2.1),
+                &point3::point3(p3.0, 1.0, p3.1),
+            );
+            shape::set_material(&mut t, &m);
+            assert!(shape::normal_at(&t, &point3::point3(0.0, 1.0, 0.0)).y > 0.0);
+            shapes.push(t);
+        }
+        shapes.push(shape::triangle(
+            &point3::point3(-100.0, 0.0, -100.0),
+            &point3::point3(300.0, 0.0, -100.0),
+            &point3::point3(-100.0, 0.0, 300.0),
+        ));
+        let s = scene(Vec::new(), shapes);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.5, 0.0),
+            &vector3::vector3(0.0, -1.0, 0.0),
+        );
+        let mut rng = random::random(5);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            sum += trace(&s, &r, 1, &mut rng).red;
+        }
+        assert!((sum / 10000.0 - 0.9 * 0.5541).abs() < 0.03);
+    }
+
+    #[test]
+    fn should_stop_at_max_depth() {
+        // With no bounces allowed, the walls of a closed room add nothing
+        // to what the light gives directly.
+        let mut room = shape::sphere();
+        shape::set_transform(&mut room, &transform::scaling(5.0, 5.0, 5.0));
+        let l = light::point_light(&point3::point3(0.0, 3.0, 0.0), &color::color(1.0, 1.0, 1.0));
+        let s = scene(vec![l], vec![room]);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, 0.0),
+            &vector3::vector3(0.0, -1.0, 0.0),
+        );
+        let mut rng = random::random(3);
+        let direct = trace(&s, &r, 0, &mut rng);
+        for _ in 0..20 {
+            assert!(color::equals(&trace(&s, &r, 0, &mut rng), &direct));
+        }
+        let mut sum = 0.0;
+        for _ in 0..200 {
+            sum += trace(&s, &r, 5, &mut rng).red;
+        }
+        assert!(sum / 200.0 > direct.red + 0.05);
+    }
+
+    #[test]
+    fn should_see_background_on_miss() {
+        let mut s = scene(Vec::new(), Vec::new());
+        s.background = background::constant(&color::color(0.2, 0.4, 0.6));
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        let c = trace(&s, &r, 5, &mut rng);
+        assert!(color::equals(&c, &color::color(0.2, 0.4, 0.6)));
+    }
+
+    #[test]
+    fn should_reflect_uniform_environment() {
+        // Every bounce off a lone convex sphere escapes, so under a uniform
+        // sky of radiance 1 a diffuse surface returns its albedo, whether the
+        // sky is found by bounces or by sampling the environment map.
+        let mut s = scene(Vec::new(), vec![shape::sphere()]);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        s.background = background::constant(&color::color(1.0, 1.0, 1.0));
+        assert!(float::equals(trace(&s, &r, 5, &mut rng).red, 0.9));
+
+        let mut image = canvas::canvas(8, 4);
+        for x in 0..8 {
+            for y in 0..4 {
+                canvas::write_pixel(&mut image, x, y, &color::color(1.0, 1.0, 1.0));
+            }
+        }
+        s.background = background::environment(image, 1.0);
+        let mut sum = 0.0;
+        for _ in 0..2000 {
+            sum += trace(&s, &r, 5, &mut rng).red;
+        }
+        assert!((sum / 2000.0 - 0.9).abs() < 0.03);
+    }
+
+    #[test]
+    fn should_leave_surfaces_cleanly_at_any_scale() {
+        let mut rng = random::random(3);
+        for scale in [1e-3, 1.0, 1e3, 1e6].iter() {
+            let scale = *scale;
+            let mut s = shape::sphere();
+            let m = transform::identity().scale(scale, scale, scale).translate(
+                2.0 * scale,
+                -scale,
+                0.5 * scale,
+            );
+            shape::set_transform(&mut s, &m);
+            for _ in 0..100 {
+                // Hit a random point from five radii away, as a camera would.
+                let (target, outward) = shape::sample_surface(&s, &mut rng);
+                let eye = target + normal3::to_vector(&outward) * (5.0 * scale);
+                let r = ray::ray(&eye, &vector3::normalize(&(target - eye)));
+                let hit = ray::hit(&ray::intersect(&s, &r)).unwrap();
+                let p = ray::position(&r, hit.t);
+                let distance = ray::offset_distance(&p, &r.origin, float::RAY_EPSILON);
+                let n = shape::normal_at(&s, &p);
+                let leave = |direction: vector3::Vector3| {
+                    return ray::ray(&offset(&p, &n, &direction, distance), &direction);
+                };
+                // Small next to the shape, so contact shadows stay attached.
+                assert!(distance < 1e-3 * scale);
+                // A ray skimming away from the surface must not hit it.
+                let helper = if n.x.abs() < 0.9 {
+                    vector3::vector3(1.0, 0.0, 0.0)
+                } else {
+                    vector3::vector3(0.0, 1.0, 0.0)
+                };
+                let up = normal3::to_vector(&n);
+                let along = vector3::normalize(&vector3::cross(&up, &helper));
+                let grazing = vector3::normalize(&(along + up * 0.001));
+                assert!(ray::hit(&ray::intersect(&s, &leave(grazing))).is_none());
+                // One heading inwards must reach the far side, not this one.
+                let through = leave(-up);
+                let far = ray::hit(&ray::intersect(&s, &through)).unwrap();
+                assert!(float::relative_equals(far.t, 2.0 * scale, 1e-3));
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Positions in space. Only the operations that make sense for positions
+// exist: two points give the vector between them, and a point moved by a
+// vector is another point. Adding two points does not compile.
+
+use std::ops::{Add, Sub};
+
+use crate::float;
+use crate::float::Float;
+use crate::tuple;
+use crate::vector3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Point3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+pub const ORIGIN: Point3 = Point3 {
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+};
+
+pub fn point3(x: Float, y: Float, z: Float) -> Point3 {
+    return Point3 { x, y, z };
+}
+
+// p moved by v.
+pub fn add(p: &Point3, v: &vector3::Vector3) -> Point3 {
+    return point3(p.x + v.x, p.y + v.y, p.z + v.z);
+}
+
+// The vector from b to a.
+pub fn subtract(a: &Point3, b: &Point3) -> vector3::Vector3 {
+    return vector3::vector3(a.x - b.x, a.y - b.y, a.z - b.z);
+}
+
+// p moved back by v.
+pub fn subtract_vector(p: &Point3, v: &vector3::Vector3) -> Point3 {
+    return point3(p.x - v.x, p.y - v.y, p.z - v.z);
+}
+
+pub fn distance(a: &Point3, b: &Point3) -> Float {
+    return vector3::magnitude(&subtract(a, b));
+}
+
+pub fn equals(a: &Point3, b: &Point3) -> bool {
+    return float::equals(a.x, b.x) && float::equals(a.y, b.y) && float::equals(a.z, b.z);
+}
+
+// Compatibility with code still using tuple::Tuple. t must be a point.
+pub fn from_tuple(t: &tuple::Tuple) -> Point3 {
+    debug_assert!(tuple::is_point(t), "{:?} is not a point", t);
+    return point3(t.x, t.y, t.z);
+}
+
+pub fn to_tuple(p: &Point3) -> tuple::Tuple {
+    return tuple::point(p.x, p.y, p.z);
+}
+
+impl Add<vector3::Vector3> for Point3 {
+    type Output = Point3;
+    fn add(self, v: vector3::Vector3) -> Point3 {
+        return add(&self, &v);
+    }
+}
+
+impl Sub for Point3 {
+    type Output = vector3::Vector3;
+    fn sub(self, other: Point3) -> vector3::Vector3 {
+        return subtract(&self, &other);
+    }
+}
+
+impl Sub<vector3::Vector3> for Point3 {
+    type Output = Point3;
+    fn sub(self, v: vector3::Vector3) -> Point3 {
+        return subtract_vector(&self, &v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Page 6
+    #[test]
+    fn should_add_vector_to_point() {
+        let p = point3(3.0, -2.0, 5.0);
+        let v = vector3::vector3(-2.0, 3.0, 1.0);
+        assert!(equals(&add(&p, &v), &point3(1.0, 1.0, 6.0)));
+    }
+
+    // Page 6
+    #[test]
+    fn should_subtract_point_from_point() {
+        let p1 = point3(3.0, 2.0, 1.0);
+        let p2 = point3(5.0, 6.0, 7.0);
+        let v = subtract(&p1, &p2);
+        assert!(vector3::equals(&v, &vector3::vector3(-2.0, -4.0, -6.0)));
+        assert!(float::equals(distance(&p1, &p2), Float::sqrt(56.0)));
+    }
+
+    // Page 6
+    #[test]
+    fn should_subtract_vector_from_point() {
+        let p = point3(3.0, 2.0, 1.0);
+        let v = vector3::vector3(5.0, 6.0, 7.0);
+        assert!(equals(&subtract_vector(&p, &v), &point3(-2.0, -4.0, -6.0)));
+    }
+
+    #[test]
+    fn should_convert_tuples() {
+        let p = from_tuple(&tuple::point(1.0, 2.0, 3.0));
+        assert!(equals(&p, &point3(1.0, 2.0, 3.0)));
+        assert!(tuple::equals(&to_tuple(&p), &tuple::point(1.0, 2.0, 3.0)));
+        assert!(tuple::equals(
+            &to_tuple(&ORIGIN),
+            &tuple::point(0.0, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_vectors_as_points() {
+        from_tuple(&tuple::vector(1.0, 2.0, 3.0));
+    }
+}
@@ -0,0 +1,96 @@
+// Prints a one-line progress report to stderr, at most a few times a second.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::stats;
+
+const INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    start: Instant,
+    rays_at_start: u64,
+    last: Mutex<Instant>,
+}
+
+pub fn progress(total: usize) -> Progress {
+    let now = Instant::now();
+    return Progress {
+        total,
+        done: AtomicUsize::new(0),
+        start: now,
+        rays_at_start: stats::rays(&stats::total()),
+        last: Mutex::new(now),
+    };
+}
+
+// Call once per finished unit of work, after stats::flush().
+pub fn step(p: &Progress) {
+    let done = p.done.fetch_add(1, Ordering::Relaxed) + 1;
+    let now = Instant::now();
+    let mut last = p.last.lock().unwrap();
+    if done < p.total && now.duration_since(*last) < INTERVAL {
+        return;
+    }
+    *last = now;
+    let rays = stats::rays(&stats::total()) - p.rays_at_start;
+    eprint!(
+        "\r{}",
+        report(done, p.total, now.duration_since(p.start), rays)
+    );
+    if done == p.total {
+        eprintln!();
+    }
+}
+
+pub fn report(done: usize, total: usize, elapsed: Duration, rays: u64) -> String {
+    let fraction = done as f64 / total.max(1) as f64;
+    let seconds = elapsed.as_secs_f64();
+    let eta = if done == 0 {
+        String::from("--:--")
+    } else {
+        clock(seconds / fraction - seconds)
+    };
+    let rate = if seconds > 0.0 {
+        rays as f64 / seconds
+    } else {
+        0.0
+    };
+    return format!(
+        "{:3.0}% | elapsed {} | ETA {} | {:.2} Mrays/s",
+        fraction * 100.0,
+        clock(seconds),
+        eta,
+        rate / 1e6
+    );
+}
+
+fn clock(seconds: f64) -> String {
+    let s = seconds.max(0.0).round() as u64;
+    return format!("{}:{:02}", s / 60, s % 60);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_report_percentage_eta_and_rate() {
+        let line = report(25, 100, Duration::from_secs(10), 5_000_000);
+        assert!(line == " 25% | elapsed 0:10 | ETA 0:30 | 0.50 Mrays/s");
+    }
+
+    #[test]
+    fn should_report_unknown_eta_before_any_work() {
+        let line = report(0, 100, Duration::from_secs(0), 0);
+        assert!(line == "  0% | elapsed 0:00 | ETA --:-- | 0.00 Mrays/s");
+    }
+
+    #[test]
+    fn should_format_minutes() {
+        assert!(clock(125.4) == "2:05");
+    }
+}
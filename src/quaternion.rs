@@ -0,0 +1,246 @@
+// Unit quaternions for rotations, w + xi + yj + zk. They interpolate
+// smoothly where rotation matrices do not, which is what motion blur needs.
+
+use std::ops::Mul;
+
+use crate::float;
+use crate::float::Float;
+use crate::matrix;
+use crate::matrix4;
+use crate::tuple;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+pub const IDENTITY: Quaternion = Quaternion {
+    w: 1.0,
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+};
+
+pub fn quaternion(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+    return Quaternion { w, x, y, z };
+}
+
+pub fn equals(a: &Quaternion, b: &Quaternion) -> bool {
+    return float::equals(a.w, b.w)
+        && float::equals(a.x, b.x)
+        && float::equals(a.y, b.y)
+        && float::equals(a.z, b.z);
+}
+
+pub fn dot(a: &Quaternion, b: &Quaternion) -> Float {
+    return a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z;
+}
+
+pub fn normalize(q: &Quaternion) -> Quaternion {
+    let length = dot(q, q).sqrt();
+    return quaternion(q.w / length, q.x / length, q.y / length, q.z / length);
+}
+
+// Turning by rad about axis, counterclockwise looking back down the axis
+// as the book's rotation_x and friends do. The axis need not be normalized.
+pub fn from_axis_angle(axis: &tuple::Tuple, rad: Float) -> Quaternion {
+    let a = tuple::normalize(&tuple::vector(axis.x, axis.y, axis.z));
+    let (sin, cos) = (rad / 2.0).sin_cos();
+    return quaternion(cos, a.x * sin, a.y * sin, a.z * sin);
+}
+
+// The Hamilton product: the rotation b followed by a, in the same order as
+// multiplying their matrices.
+pub fn multiply(a: &Quaternion, b: &Quaternion) -> Quaternion {
+    return quaternion(
+        a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+    );
+}
+
+// The opposite rotation, for unit quaternions.
+pub fn conjugate(q: &Quaternion) -> Quaternion {
+    return quaternion(q.w, -q.x, -q.y, -q.z);
+}
+
+// Rotates the x, y and z of t by unit q, leaving w alone so points and
+// vectors both work.
+pub fn rotate(q: &Quaternion, t: &tuple::Tuple) -> tuple::Tuple {
+    let v = quaternion(0.0, t.x, t.y, t.z);
+    let r = multiply(&multiply(q, &v), &conjugate(q));
+    return tuple::tuple(r.x, r.y, r.z, t.w);
+}
+
+// The rotation in the upper 3x3 of m, which must be orthonormal with a
+// determinant of 1 (Shepperd's method, picking the best conditioned
+// component to divide by).
+pub fn from_matrix(m: &matrix4::Matrix4) -> Quaternion {
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let q = if trace > 0.0 {
+        let s = 2.0 * (trace + 1.0).sqrt();
+        quaternion(
+            0.25 * s,
+            (m[2][1] - m[1][2]) / s,
+            (m[0][2] - m[2][0]) / s,
+            (m[1][0] - m[0][1]) / s,
+        )
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+        quaternion(
+            (m[2][1] - m[1][2]) / s,
+            0.25 * s,
+            (m[0][1] + m[1][0]) / s,
+            (m[0][2] + m[2][0]) / s,
+        )
+    } else if m[1][1] > m[2][2] {
+        let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+        quaternion(
+            (m[0][2] - m[2][0]) / s,
+            (m[0][1] + m[1][0]) / s,
+            0.25 * s,
+            (m[1][2] + m[2][1]) / s,
+        )
+    } else {
+        let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+        quaternion(
+            (m[1][0] - m[0][1]) / s,
+            (m[0][2] + m[2][0]) / s,
+            (m[1][2] + m[2][1]) / s,
+            0.25 * s,
+        )
+    };
+    return normalize(&q);
+}
+
+pub fn to_matrix(q: &Quaternion) -> matrix4::Matrix4 {
+    let q = normalize(q);
+    let (w, x, y, z) = (q.w, q.x, q.y, q.z);
+    return matrix::Matrix([
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+            0.0,
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+            0.0,
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+        ],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+}
+
+// Spherical linear interpolation, turning at a constant rate the short way
+// round from a (t = 0) to b (t = 1).
+pub fn slerp(a: &Quaternion, b: &Quaternion, t: Float) -> Quaternion {
+    let mut cos = dot(a, b);
+    let mut b = *b;
+    if cos < 0.0 {
+        cos = -cos;
+        b = quaternion(-b.w, -b.x, -b.y, -b.z);
+    }
+    // Nearly parallel: the angle is too small to divide by.
+    let (wa, wb) = if cos > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let theta = cos.acos();
+        let sin = theta.sin();
+        (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+    };
+    return normalize(&quaternion(
+        wa * a.w + wb * b.w,
+        wa * a.x + wb * b.x,
+        wa * a.y + wb * b.y,
+        wa * a.z + wb * b.z,
+    ));
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, other: Quaternion) -> Quaternion {
+        return multiply(&self, &other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::consts::PI;
+    use crate::transform;
+
+    #[test]
+    fn should_convert_rotations_both_ways() {
+        let rotations = [
+            matrix4::IDENTITY,
+            transform::rotation_x(PI / 3.0),
+            transform::rotation_y(PI),
+            transform::rotation_z(-PI / 2.0),
+            matrix::multiply(&transform::rotation_x(2.5), &transform::rotation_y(-2.0)),
+        ];
+        for m in rotations.iter() {
+            let q = from_matrix(m);
+            assert!(float::equals(dot(&q, &q), 1.0));
+            assert!(matrix::equals(&to_matrix(&q), m));
+        }
+        let half = (PI / 4.0).sin();
+        let q = from_matrix(&transform::rotation_z(PI / 2.0));
+        assert!(equals(&q, &quaternion(half, 0.0, 0.0, half)));
+    }
+
+    #[test]
+    fn should_slerp_at_constant_rate() {
+        let a = from_matrix(&transform::rotation_y(0.2));
+        let b = from_matrix(&transform::rotation_y(1.4));
+        for i in 0..=4 {
+            let t = i as Float / 4.0;
+            let expected = transform::rotation_y(0.2 + 1.2 * t);
+            assert!(matrix::equals(&to_matrix(&slerp(&a, &b, t)), &expected));
+        }
+        // q and -q are the same rotation; the short way round is taken.
+        let c = from_matrix(&transform::rotation_x(0.1));
+        let flipped = quaternion(-c.w, -c.x, -c.y, -c.z);
+        let mid = slerp(&IDENTITY, &flipped, 0.5);
+        assert!(matrix::equals(
+            &to_matrix(&mid),
+            &transform::rotation_x(0.05)
+        ));
+    }
+
+    #[test]
+    fn should_rotate_about_any_axis() {
+        let q = from_axis_angle(&tuple::vector(0.0, 0.0, 2.0), PI / 2.0);
+        let p = rotate(&q, &tuple::point(1.0, 0.0, 0.0));
+        assert!(tuple::equals(&p, &tuple::point(0.0, 1.0, 0.0)));
+        // A third of a turn about the diagonal cycles the axes.
+        let diagonal = from_axis_angle(&tuple::vector(1.0, 1.0, 1.0), 2.0 * PI / 3.0);
+        let v = rotate(&diagonal, &tuple::vector(1.0, 0.0, 0.0));
+        assert!(tuple::equals(&v, &tuple::vector(0.0, 1.0, 0.0)));
+        assert!(matrix::equals(
+            &to_matrix(&from_axis_angle(&tuple::vector(1.0, 0.0, 0.0), 0.7)),
+            &transform::rotation_x(0.7)
+        ));
+    }
+
+    #[test]
+    fn should_compose_like_matrices() {
+        let a = from_axis_angle(&tuple::vector(0.0, 1.0, 0.0), 0.9);
+        let b = from_axis_angle(&tuple::vector(1.0, -2.0, 0.5), 1.3);
+        let expected = matrix::multiply(&to_matrix(&a), &to_matrix(&b));
+        assert!(matrix::equals(&to_matrix(&multiply(&a, &b)), &expected));
+        assert!(equals(&(a * b), &multiply(&a, &b)));
+        assert!(equals(&(a * conjugate(&a)), &IDENTITY));
+    }
+}
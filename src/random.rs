@@ -0,0 +1,65 @@
+// SplitMix64: small, fast and good enough for sample placement. Every
+// pixel gets its own generator so renders are reproducible for a given seed.
+
+use crate::float::Float;
+
+pub struct Random {
+    state: u64,
+}
+
+pub fn random(seed: u64) -> Random {
+    return Random { state: seed };
+}
+
+pub fn for_pixel(seed: u64, x: usize, y: usize) -> Random {
+    let mut mixer = random(seed ^ ((x as u64) << 32 | y as u64));
+    return random(next_u64(&mut mixer));
+}
+
+pub fn next_u64(rng: &mut Random) -> u64 {
+    rng.state = rng.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = rng.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return z ^ (z >> 31);
+}
+
+// Uniformly distributed in [0, 1). Only as many bits as Float can hold
+// exactly are used, so rounding never reaches 1.
+pub fn next_float(rng: &mut Random) -> Float {
+    let bits = Float::MANTISSA_DIGITS;
+    return (next_u64(rng) >> (64 - bits)) as Float / (1u64 << bits) as Float;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_repeat_for_same_seed() {
+        let mut a = random(42);
+        let mut b = random(42);
+        for _ in 0..100 {
+            assert!(next_u64(&mut a) == next_u64(&mut b));
+        }
+    }
+
+    #[test]
+    fn should_stay_in_unit_interval() {
+        let mut rng = random(7);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            let value = next_float(&mut rng);
+            assert!((0.0..1.0).contains(&value));
+            sum += value;
+        }
+        assert!((sum / 10000.0 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn should_differ_between_pixels() {
+        let mut a = for_pixel(1, 3, 4);
+        let mut b = for_pixel(1, 4, 3);
+        assert!(next_u64(&mut a) != next_u64(&mut b));
+    }
+}
@@ -1,40 +1,59 @@
-use std::vec;
-
-use crate::float;
+use crate::float::Float;
 use crate::intersection;
-use crate::matrix4;
+use crate::point3;
 use crate::shape;
 use crate::shape::Shape;
+use crate::stats;
 use crate::transform;
-use crate::tuple;
+use crate::vector3;
 
 pub struct Ray {
-    pub origin: tuple::Tuple,
-    pub direction: tuple::Tuple,
+    pub origin: point3::Point3,
+    pub direction: vector3::Vector3,
+    // When the ray is cast, for shapes in motion: they move from their
+    // transform at time 0 to their end transform at time 1.
+    pub time: Float,
+}
+
+pub fn ray(origin: &point3::Point3, direction: &vector3::Vector3) -> Ray {
+    return ray_at_time(origin, direction, 0.0);
 }
 
-pub fn ray(origin: &tuple::Tuple, direction: &tuple::Tuple) -> Ray {
+pub fn ray_at_time(origin: &point3::Point3, direction: &vector3::Vector3, time: Float) -> Ray {
     return Ray {
         origin: *origin,
         direction: *direction,
+        time,
     };
 }
 
-pub fn position(ray: &Ray, magnitude: f64) -> tuple::Tuple {
-    return tuple::add(&ray.origin, &tuple::multiply(&ray.direction, magnitude));
+pub fn position(ray: &Ray, magnitude: Float) -> point3::Point3 {
+    return ray.origin + ray.direction * magnitude;
 }
 
-pub fn spheretoray(ray: &Ray) -> tuple::Tuple {
-    return tuple::subtract(&ray.origin, &tuple::point(0.0, 0.0, 0.0));
+pub fn spheretoray(ray: &Ray) -> vector3::Vector3 {
+    return ray.origin - point3::ORIGIN;
 }
 
+// Moving shapes are intersected where they are at the ray's time, and the
+// intersections hold the shape frozen there, so normals and textures work
+// out without knowing about time.
 pub fn intersect(shape: &Shape, ray: &Ray) -> Vec<intersection::Intersection> {
+    stats::intersection_test(shape);
+    let moved = shape::at_time(shape, ray.time);
+    let shape = &moved;
+    // A shape flattened by a scale of 0 has no inside or outside to hit.
+    let inv = match shape::inverse(shape) {
+        Some(inv) => inv,
+        None => return Vec::new(),
+    };
+    let ray = transform::transform(ray, &inv);
     match shape {
-        Shape::Sphere { id } => {
+        Shape::Sphere { .. } => {
             let d = spheretoray(&ray);
-            let a = tuple::dot(&ray.direction, &ray.direction);
-            let b = 2.0 * tuple::dot(&ray.direction, &d);
-            let c = tuple::dot(&d, &d) - 1.0;
+            let a = vector3::dot(&ray.direction, &ray.direction);
+            let b = 2.0 * vector3::dot(&ray.direction, &d);
+            let c = vector3::dot(&d, &d) - 1.0;
 
             let discriminant = b.powi(2) - 4.0 * a * c;
 
@@ -45,14 +64,49 @@ pub fn intersect(shape: &Shape, ray: &Ray) -> Vec<intersection::Intersection> {
             let t1 = (-b - (discriminant.sqrt())) / (2.0 * a);
             let t2 = (-b + (discriminant.sqrt())) / (2.0 * a);
 
-            let mut vec = Vec::new();
-            vec.push(intersection::intersection(t1, shape));
-            vec.push(intersection::intersection(t2, shape));
-            return vec;
+            return vec![
+                intersection::intersection(t1, shape),
+                intersection::intersection(t2, shape),
+            ];
+        }
+        Shape::Triangle { p1, p2, p3, .. } => {
+            // Moller-Trumbore: solve for the distance and how far along each
+            // edge the hit lies at once. Only a ray parallel to the plane has
+            // no answer; anything else that misses falls outside the edges.
+            let (e1, e2) = (*p2 - *p1, *p3 - *p1);
+            let dir_cross_e2 = vector3::cross(&ray.direction, &e2);
+            let det = vector3::dot(&e1, &dir_cross_e2);
+            if det == 0.0 {
+                return Vec::new();
+            }
+            let f = 1.0 / det;
+            let p1_to_origin = ray.origin - *p1;
+            let u = f * vector3::dot(&p1_to_origin, &dir_cross_e2);
+            if !(0.0..=1.0).contains(&u) {
+                return Vec::new();
+            }
+            let origin_cross_e1 = vector3::cross(&p1_to_origin, &e1);
+            let v = f * vector3::dot(&ray.direction, &origin_cross_e1);
+            if v < 0.0 || u + v > 1.0 {
+                return Vec::new();
+            }
+            let t = f * vector3::dot(&e2, &origin_cross_e1);
+            return vec![intersection::intersection(t, shape)];
         }
     }
 }
 
+// How far off the surface a ray leaving point, having arrived from origin,
+// has to start to not hit it again. A hit is only as accurate as the
+// coordinates that went into finding it, so epsilon is relative to the
+// largest of them and the distance grows with the scene.
+pub fn offset_distance(point: &point3::Point3, origin: &point3::Point3, epsilon: Float) -> Float {
+    let scale = [point.x, point.y, point.z, origin.x, origin.y, origin.z]
+        .iter()
+        .fold(Float::MIN_POSITIVE, |m, v| m.max(v.abs()));
+    return epsilon * scale;
+}
+
 pub fn hit(intvec: &Vec<intersection::Intersection>) -> Option<intersection::Intersection> {
     let mut closest: Option<intersection::Intersection> = None;
     for i in intvec {
@@ -75,38 +129,42 @@ pub fn hit(intvec: &Vec<intersection::Intersection>) -> Option<intersection::Int
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::float;
 
     // Page 58
     #[test]
     fn should_create_a_ray() {
-        let origin = tuple::point(1.0, 2.0, 3.0);
-        let direction = tuple::vector(4.0, 5.0, 6.0);
+        let origin = point3::point3(1.0, 2.0, 3.0);
+        let direction = vector3::vector3(4.0, 5.0, 6.0);
         let r = ray(&origin, &direction);
-        assert!(tuple::equals(&r.origin, &origin));
-        assert!(tuple::equals(&r.direction, &direction));
+        assert!(point3::equals(&r.origin, &origin));
+        assert!(vector3::equals(&r.direction, &direction));
     }
 
     #[test]
     fn should_calculate_position() {
-        let origin = tuple::point(2.0, 3.0, 4.0);
-        let direction = tuple::vector(1.0, 0.0, 0.0);
+        let origin = point3::point3(2.0, 3.0, 4.0);
+        let direction = vector3::vector3(1.0, 0.0, 0.0);
         let r = ray(&origin, &direction);
         let r1 = position(&r, 0.0);
-        assert!(tuple::equals(&r1, &origin));
+        assert!(point3::equals(&r1, &origin));
         let r2 = position(&r, 1.0);
-        let e2 = tuple::point(3.0, 3.0, 4.0);
-        assert!(tuple::equals(&r2, &e2));
+        let e2 = point3::point3(3.0, 3.0, 4.0);
+        assert!(point3::equals(&r2, &e2));
         let r3 = position(&r, -1.0);
-        let e3 = tuple::point(1.0, 3.0, 4.0);
-        assert!(tuple::equals(&r3, &e3));
+        let e3 = point3::point3(1.0, 3.0, 4.0);
+        assert!(point3::equals(&r3, &e3));
         let r4 = position(&r, 2.5);
-        let e4 = tuple::point(4.5, 3.0, 4.0);
-        assert!(tuple::equals(&r4, &e4));
+        let e4 = point3::point3(4.5, 3.0, 4.0);
+        assert!(point3::equals(&r4, &e4));
     }
 
     #[test]
     fn should_intersect_a_sphere_at_two_points() {
-        let r = ray(&tuple::point(0.0, 0.0, -5.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
         assert!(xs.len() == 2);
@@ -116,7 +174,10 @@ mod tests {
 
     #[test]
     fn should_intersect_a_sphere_at_tangent_twice() {
-        let r = ray(&tuple::point(0.0, 1.0, -5.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 1.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
         assert!(xs.len() == 2);
@@ -126,15 +187,21 @@ mod tests {
 
     #[test]
     fn should_miss_a_sphere() {
-        let r = ray(&tuple::point(0.0, 2.0, -5.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 2.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
-        assert!(xs.len() == 0);
+        assert!(xs.is_empty());
     }
 
     #[test]
     fn should_intersect_inside_a_sphere() {
-        let r = ray(&tuple::point(0.0, 0.0, 0.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
         assert!(xs.len() == 2);
@@ -144,7 +211,10 @@ mod tests {
 
     #[test]
     fn should_intersect_behind_a_sphere() {
-        let r = ray(&tuple::point(0.0, 0.0, 5.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 0.0, 5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
         assert!(xs.len() == 2);
@@ -173,7 +243,10 @@ mod tests {
 
     #[test]
     fn should_set_object_on_intersection() {
-        let r = ray(&tuple::point(0.0, 0.0, -5.0), &tuple::vector(0.0, 0.0, 1.0));
+        let r = ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
         let s = shape::sphere();
         let xs = intersect(&s, &r);
         assert!(xs.len() == 2);
@@ -186,12 +259,10 @@ mod tests {
         let s = shape::sphere();
         let i1 = intersection::intersection(1.0, &s);
         let i2 = intersection::intersection(2.0, &s);
-        let mut xs = Vec::new();
-        xs.push(i1);
-        xs.push(i2);
+        let xs = vec![i1, i2];
         let option = hit(&xs);
         match option {
-            None => assert!(false),
+            None => panic!("expected a hit"),
             Some(i) => {
                 assert!(intersection::equals(&i, &i1));
             }
@@ -206,7 +277,7 @@ mod tests {
         let xs = intersection::intersections(&[i2, i1]);
         let option = hit(&xs);
         match option {
-            None => assert!(false),
+            None => panic!("expected a hit"),
             Some(i) => {
                 assert!(intersection::equals(&i, &i2));
             }
@@ -220,12 +291,7 @@ mod tests {
         let i2 = intersection::intersection(-1.0, &s);
         let xs = intersection::intersections(&[i2, i1]);
         let i = hit(&xs);
-        match i {
-            None => assert!(true),
-            Some(_i) => {
-                assert!(false);
-            }
-        }
+        assert!(i.is_none());
     }
 
     #[test]
@@ -238,7 +304,7 @@ mod tests {
         let xs = intersection::intersections(&[i1, i2, i3, i4]);
         let option = hit(&xs);
         match option {
-            None => assert!(false),
+            None => panic!("expected a hit"),
             Some(i) => {
                 assert!(intersection::equals(&i, &i4));
             }
@@ -248,10 +314,112 @@ mod tests {
     // Page 69
     #[test]
     fn translating_a_ray() {
-        let r = ray(&tuple::point(1.0, 2.0, 3.0), &tuple::vector(0.0, 1.0, 0.0));
+        let r = ray(
+            &point3::point3(1.0, 2.0, 3.0),
+            &vector3::vector3(0.0, 1.0, 0.0),
+        );
         let m = transform::translation(3.0, 4.0, 5.0);
         let r2 = transform::transform(&r, &m);
-        assert!(tuple::equals(&r2.origin, &tuple::point(4.0, 6.0, 8.0)));
-        assert!(tuple::equals(&r2.direction, &tuple::vector(0.0, 1.0, 0.0)));
+        assert!(point3::equals(&r2.origin, &point3::point3(4.0, 6.0, 8.0)));
+        assert!(vector3::equals(
+            &r2.direction,
+            &vector3::vector3(0.0, 1.0, 0.0)
+        ));
+    }
+
+    // Page 69
+    #[test]
+    fn scaling_a_ray() {
+        let r = ray(
+            &point3::point3(1.0, 2.0, 3.0),
+            &vector3::vector3(0.0, 1.0, 0.0),
+        );
+        let m = transform::scaling(2.0, 3.0, 4.0);
+        let r2 = transform::transform(&r, &m);
+        assert!(point3::equals(&r2.origin, &point3::point3(2.0, 6.0, 12.0)));
+        assert!(vector3::equals(
+            &r2.direction,
+            &vector3::vector3(0.0, 3.0, 0.0)
+        ));
+    }
+
+    // Page 69
+    #[test]
+    fn should_intersect_scaled_sphere() {
+        let r = ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut s = shape::sphere();
+        shape::set_transform(&mut s, &transform::scaling(2.0, 2.0, 2.0));
+        let xs = intersect(&s, &r);
+        assert!(xs.len() == 2);
+        assert!(float::equals(xs[0].t, 3.0));
+        assert!(float::equals(xs[1].t, 7.0));
+    }
+
+    // Page 70
+    #[test]
+    fn should_intersect_translated_sphere() {
+        let r = ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut s = shape::sphere();
+        shape::set_transform(&mut s, &transform::translation(5.0, 0.0, 0.0));
+        let xs = intersect(&s, &r);
+        assert!(xs.is_empty());
+    }
+
+    #[test]
+    fn should_miss_flattened_sphere() {
+        let r = ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let mut s = shape::sphere();
+        shape::set_transform(&mut s, &transform::scaling(1.0, 0.0, 1.0));
+        assert!(!shape::is_invertible(&s));
+        assert!(intersect(&s, &r).is_empty());
+    }
+
+    fn book_triangle() -> shape::Shape {
+        return shape::triangle(
+            &point3::point3(0.0, 1.0, 0.0),
+            &point3::point3(-1.0, 0.0, 0.0),
+            &point3::point3(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn should_miss_triangle_parallel_to_ray() {
+        let r = ray(
+            &point3::point3(0.0, -1.0, -2.0),
+            &vector3::vector3(0.0, 1.0, 0.0),
+        );
+        assert!(intersect(&book_triangle(), &r).is_empty());
+    }
+
+    #[test]
+    fn should_miss_triangle_past_each_edge() {
+        let d = vector3::vector3(0.0, 0.0, 1.0);
+        for origin in [
+            point3::point3(1.0, 1.0, -2.0),
+            point3::point3(-1.0, 1.0, -2.0),
+            point3::point3(0.0, -1.0, -2.0),
+        ] {
+            assert!(intersect(&book_triangle(), &ray(&origin, &d)).is_empty());
+        }
+    }
+
+    #[test]
+    fn should_intersect_triangle() {
+        let r = ray(
+            &point3::point3(0.0, 0.5, -2.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let xs = intersect(&book_triangle(), &r);
+        assert!(xs.len() == 1);
+        assert!(float::equals(xs[0].t, 2.0));
     }
 }
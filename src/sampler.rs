@@ -0,0 +1,119 @@
+use crate::float::Float;
+use crate::random;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampler {
+    Grid,
+    Jittered,
+    Halton,
+}
+
+// Grid and Jittered round the count up to the next square number.
+pub fn sample_count(sampler: &Sampler, count: usize) -> usize {
+    match sampler {
+        Sampler::Grid | Sampler::Jittered => {
+            let side = side_for(count);
+            return side * side;
+        }
+        Sampler::Halton => return count.max(1),
+    }
+}
+
+// Offsets inside the unit square, to be mapped onto the pixel footprint.
+pub fn samples(sampler: &Sampler, count: usize, rng: &mut random::Random) -> Vec<(Float, Float)> {
+    let mut v = Vec::with_capacity(sample_count(sampler, count));
+    match sampler {
+        Sampler::Grid | Sampler::Jittered => {
+            let side = side_for(count);
+            for j in 0..side {
+                for i in 0..side {
+                    let (du, dv) = if *sampler == Sampler::Jittered {
+                        (random::next_float(rng), random::next_float(rng))
+                    } else {
+                        (0.5, 0.5)
+                    };
+                    v.push((
+                        (i as Float + du) / side as Float,
+                        (j as Float + dv) / side as Float,
+                    ));
+                }
+            }
+        }
+        Sampler::Halton => {
+            // Cranley-Patterson rotation decorrelates neighbouring pixels.
+            let ou = random::next_float(rng);
+            let ov = random::next_float(rng);
+            for i in 0..count.max(1) {
+                v.push((
+                    (radical_inverse(i + 1, 2) + ou).fract(),
+                    (radical_inverse(i + 1, 3) + ov).fract(),
+                ));
+            }
+        }
+    }
+    return v;
+}
+
+pub fn radical_inverse(mut index: usize, base: usize) -> Float {
+    let inv_base = 1.0 / base as Float;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as Float * factor;
+        index /= base;
+        factor *= inv_base;
+    }
+    return result;
+}
+
+fn side_for(count: usize) -> usize {
+    let mut side = 1;
+    while side * side < count {
+        side += 1;
+    }
+    return side;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    #[test]
+    fn should_center_single_grid_sample() {
+        let mut rng = random::random(0);
+        let s = samples(&Sampler::Grid, 1, &mut rng);
+        assert!(s.len() == 1);
+        assert!(float::equals(s[0].0, 0.5));
+        assert!(float::equals(s[0].1, 0.5));
+    }
+
+    #[test]
+    fn should_round_grid_up_to_square() {
+        let mut rng = random::random(0);
+        assert!(sample_count(&Sampler::Grid, 5) == 9);
+        assert!(samples(&Sampler::Jittered, 5, &mut rng).len() == 9);
+        assert!(samples(&Sampler::Halton, 5, &mut rng).len() == 5);
+    }
+
+    #[test]
+    fn should_keep_jittered_samples_in_their_stratum() {
+        let mut rng = random::random(3);
+        let s = samples(&Sampler::Jittered, 16, &mut rng);
+        for (n, (u, v)) in s.iter().enumerate() {
+            let i = n % 4;
+            let j = n / 4;
+            assert!(*u >= i as Float / 4.0 && *u < (i + 1) as Float / 4.0);
+            assert!(*v >= j as Float / 4.0 && *v < (j + 1) as Float / 4.0);
+        }
+    }
+
+    #[test]
+    fn should_calculate_radical_inverse() {
+        assert!(float::equals(radical_inverse(1, 2), 0.5));
+        assert!(float::equals(radical_inverse(2, 2), 0.25));
+        assert!(float::equals(radical_inverse(3, 2), 0.75));
+        assert!(float::equals(radical_inverse(1, 3), 1.0 / 3.0));
+        assert!(float::equals(radical_inverse(4, 3), 1.0 / 3.0 + 1.0 / 9.0));
+    }
+}
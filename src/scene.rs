@@ -0,0 +1,1742 @@
+// Builds a scene from the YAML-like scene format:
+//
+//   - add: camera
+//     width: 320
+//     height: 240
+//     field-of-view: 1.0472
+//     from: [ 0, 1.5, -5 ]
+//     to: [ 0, 1, 0 ]
+//     up: [ 0, 1, 0 ]
+//     aperture: 0.1           # optional depth of field: lens width, the
+//     focal-distance: 5       # distance in focus, and disc or a number of
+//     aperture-shape: 6       # polygon sides for the bokeh
+//     shutter-open: 0         # optional motion blur: the span of time the
+//     shutter-close: 1        # camera's rays are spread over
+//     ray-epsilon: 0.00001    # optional: how far bounced and shadow rays
+//                             # start off a surface, relative to the size
+//                             # of its coordinates
+//
+//   - add: light
+//     type: spot              # point, directional, spot, rectangle or sphere
+//     at: [ 0, 10, 0 ]
+//     direction: [ 0, -1, 0 ]
+//     inner-angle: 0.3        # radians
+//     outer-angle: 0.5
+//     intensity: [ 1, 1, 1 ]
+//     attenuation: [ 1, 0, 0.01 ]   # constant, linear, quadratic
+//
+//   - add: background
+//     type: gradient          # constant (color), gradient (bottom, top) or
+//     bottom: [ 1, 1, 1 ]     # environment (file: an equirectangular .hdr,
+//     top: [ 0.5, 0.7, 1 ]    # relative to the scene, and strength)
+//
+//   - define: red
+//     value:
+//       color: [ 1, 0, 0 ]
+//
+//   - define: shiny-red
+//     extend: red
+//     value:
+//       specular: 1.0
+//
+//   - define: gold            # bsdf: lambertian, microfacet, conductor or
+//     value:                  # dielectric; only the path tracer uses it
+//       bsdf: conductor
+//       roughness: 0.3
+//       eta: [ 0.143, 0.374, 1.442 ]
+//       k: [ 3.983, 2.385, 1.603 ]
+//
+//   - define: earth           # texture replaces color; mapping is spherical,
+//     value:                  # planar, cylindrical or cube, filter nearest
+//       texture:              # or bilinear, wrap repeat or clamp
+//         file: earth.ppm     # a PPM or .hdr image, relative to the scene
+//         mapping: spherical
+//
+//   - define: cobbles         # normal-map takes the same keys as texture;
+//     value:                  # bump is noise (scale) or waves (frequency)
+//       normal-map:
+//         file: cobbles.ppm
+//         mapping: planar
+//       bump:
+//         type: noise
+//         scale: 8
+//         amplitude: 0.05
+//
+//   - define: neon            # any shape can glow; the path tracer uses it
+//     value:                  # as a light
+//       emission: [ 1, 0.2, 0.6 ]
+//       emission-strength: 8
+//
+//   - add: sphere
+//     material: shiny-red
+//     transform:
+//       - [ scale, 0.5, 0.5, 0.5 ]
+//       - [ translate, 0, 1, 0 ]
+//     motion:                 # optional: where the shape ends up at time 1,
+//       - [ scale, 0.5, 0.5, 0.5 ]  # moving there from transform at time 0
+//       - [ translate, 1, 1, 0 ]
+//
+//   - add: obj                # a Wavefront OBJ mesh, relative to the scene;
+//     file: teapot.obj        # material, transform and motion apply to
+//     material: shiny-red     # every triangle in it
+//     transform:
+//       - [ rotate-x, -1.5708 ]
+//
+// Transforms are applied in the order they are listed. Besides translate,
+// scale, shear and rotate-x/y/z there are:
+//
+//   - [ rotate, 1, 1, 0, 0.5 ]          # axis x, y, z, then radians
+//   - [ quaternion, 0.92, 0, 0.38, 0 ]  # w, x, y, z; normalized for you
+//   - [ rotate-zyx, 0.1, 0.2, 0.3 ]     # Euler angles about x, y and z,
+//                                       # applied in the named order
+//   - [ look-along, 1, 0, 1, 0, 1, 0 ]  # turns +z to a direction, then up
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::background;
+use crate::bsdf;
+use crate::bump;
+use crate::camera;
+use crate::color;
+use crate::filter;
+use crate::float::Float;
+use crate::hdr;
+use crate::intersection;
+use crate::lens;
+use crate::light;
+use crate::material;
+use crate::matrix4;
+use crate::normal3;
+use crate::obj;
+use crate::point3;
+use crate::quaternion;
+use crate::random;
+use crate::ray;
+use crate::sampler;
+use crate::shape;
+use crate::stats;
+use crate::texture;
+use crate::transform;
+use crate::tuple;
+use crate::vector3;
+use crate::yaml;
+use crate::yaml::{Node, Value};
+
+pub struct Scene {
+    pub camera: camera::Camera,
+    pub lights: Vec<light::Light>,
+    pub shapes: Vec<shape::Shape>,
+    pub background: background::Background,
+    pub textures: Vec<texture::Texture>,
+}
+
+struct Builder {
+    defines: HashMap<String, Node>,
+    camera: Option<camera::Camera>,
+    lights: Vec<light::Light>,
+    shapes: Vec<shape::Shape>,
+    background: background::Background,
+    textures: Vec<texture::Texture>,
+    dir: PathBuf,
+}
+
+pub fn parse(text: &str) -> Result<Scene, yaml::Error> {
+    return parse_in(text, Path::new(""));
+}
+
+// Like parse, with files the scene refers to looked up relative to dir.
+pub fn parse_in(text: &str, dir: &Path) -> Result<Scene, yaml::Error> {
+    let doc = yaml::parse(text)?;
+    let items = match &doc.value {
+        Value::List(items) => items,
+        _ => {
+            return Err(yaml::error(
+                doc.line,
+                "expected a list of 'add' and 'define' entries",
+            ))
+        }
+    };
+    let mut b = Builder {
+        defines: HashMap::new(),
+        camera: None,
+        lights: Vec::new(),
+        shapes: Vec::new(),
+        background: background::constant(&color::color(0.0, 0.0, 0.0)),
+        textures: Vec::new(),
+        dir: dir.to_path_buf(),
+    };
+    for item in items {
+        if let Some(what) = yaml::get(item, "add") {
+            add(&mut b, item, what)?;
+        } else if let Some(name) = yaml::get(item, "define") {
+            define(&mut b, item, name)?;
+        } else {
+            return Err(yaml::error(item.line, "expected 'add' or 'define'"));
+        }
+    }
+    match b.camera {
+        Some(camera) => {
+            // With the shutter open for an instant every ray sees the same
+            // moment, so shapes in motion can be put in place once.
+            let shapes = if camera.shutter_open == camera.shutter_close {
+                b.shapes
+                    .iter()
+                    .map(|s| shape::at_time(s, camera.shutter_open))
+                    .collect()
+            } else {
+                b.shapes
+            };
+            return Ok(Scene {
+                camera,
+                lights: b.lights,
+                shapes,
+                background: b.background,
+                textures: b.textures,
+            });
+        }
+        None => return Err(yaml::error(doc.line, "scene has no camera")),
+    }
+}
+
+// The nearest hit in front of the ray's origin, if any.
+pub fn intersect(scene: &Scene, r: &ray::Ray) -> Option<intersection::Intersection> {
+    let mut xs = Vec::new();
+    for s in scene.shapes.iter() {
+        xs.extend(ray::intersect(s, r));
+    }
+    return ray::hit(&xs);
+}
+
+// Shapes with emissive materials, which the path tracer samples as lights.
+pub fn emitters(scene: &Scene) -> Vec<shape::Shape> {
+    let mut v = Vec::new();
+    for s in scene.shapes.iter() {
+        if material::is_emissive(&shape::material(s)) {
+            v.push(*s);
+        }
+    }
+    return v;
+}
+
+// The material's color at world_point, looked up in its texture if it has
+// one: by the shape's own texture coordinates where it has them, otherwise
+// through the texture's mapping.
+pub fn surface_color(
+    scene: &Scene,
+    s: &shape::Shape,
+    world_point: &point3::Point3,
+) -> color::Color {
+    let m = shape::material(s);
+    match m.texture {
+        Some(i) => {
+            let inv = shape::inverse_transform(s);
+            let object_point = matrix4::multiply_point(&inv, world_point);
+            let t = &scene.textures[i];
+            match shape::uv_at(s, &object_point) {
+                Some((u, v)) => return texture::color_at_uv(t, u, v),
+                None => return texture::color_at(t, &point3::to_tuple(&object_point)),
+            }
+        }
+        None => return m.color,
+    }
+}
+
+// The normal used for shading at world_point: the shape's own, tilted by
+// the material's normal map and bump if it has them. Like surface_color, the
+// normal map goes by the shape's own texture coordinates where it has them.
+pub fn shading_normal(
+    scene: &Scene,
+    s: &shape::Shape,
+    world_point: &point3::Point3,
+) -> normal3::Normal3 {
+    let m = shape::material(s);
+    if m.normal_map.is_none() && m.bump.is_none() {
+        return shape::normal_at(s, world_point);
+    }
+    let inv = shape::inverse_transform(s);
+    let object_point = matrix4::multiply_point(&inv, world_point);
+    let uv = shape::uv_at(s, &object_point);
+    // Textures and bumps are looked up in object space tuples.
+    let p = point3::to_tuple(&object_point);
+    let mut n = normal3::to_tuple(&shape::local_normal_at(s, &object_point));
+    if let Some(i) = m.normal_map {
+        let map = &scene.textures[i];
+        n = match (uv, shape::uv_tangents(s)) {
+            (Some(uv), Some((dpdu, dpdv))) => {
+                let rates = (vector3::to_tuple(&dpdu), vector3::to_tuple(&dpdv));
+                bump::normal_map_uv(map, uv, rates, &n)
+            }
+            _ => bump::normal_map(map, &p, &n),
+        };
+    }
+    if let Some(b) = m.bump {
+        n = bump::perturb(&b, &p, &n);
+    }
+    return shape::normal_to_world(s, &normal3::from_tuple(&n));
+}
+
+// Whitted style ray tracing: what the ray hits glows with its own emission
+// and is Phong shaded by every light, each dimmed by how much of it the point
+// can see. Reflective and transparent materials send out further rays until
+// remaining runs out. A miss takes the background.
+pub fn color_at(
+    scene: &Scene,
+    r: &ray::Ray,
+    remaining: usize,
+    rng: &mut random::Random,
+) -> color::Color {
+    match intersect(scene, r) {
+        Some(h) => return shade_hit(scene, r, &h, remaining, rng),
+        None => return background::radiance(&scene.background, &r.direction),
+    }
+}
+
+fn shade_hit(
+    scene: &Scene,
+    r: &ray::Ray,
+    hit: &intersection::Intersection,
+    remaining: usize,
+    rng: &mut random::Random,
+) -> color::Color {
+    let point = ray::position(r, hit.t);
+    let eyev = vector3::normalize(&-r.direction);
+    let mut normal = shape::normal_at(&hit.s, &point);
+    let mut shading = shading_normal(scene, &hit.s, &point);
+    // Seen from inside a shape, or from behind a triangle.
+    let inside = normal3::dot(&normal, &eyev) < 0.0;
+    if inside {
+        normal = -normal;
+        shading = -shading;
+    }
+    let mut m = shape::material(&hit.s);
+    m.color = surface_color(scene, &hit.s, &point);
+    // Shadow and reflected rays leave from just above the true surface, and
+    // refracted ones from just below it.
+    let distance = ray::offset_distance(&point, &r.origin, scene.camera.ray_epsilon);
+    let offset = normal3::to_vector(&normal) * distance;
+    let (over_point, under_point) = (point + offset, point - offset);
+    let mut surface = material::emitted(&m);
+    for l in scene.lights.iter() {
+        let v = visibility(scene, l, &over_point, r.time, rng);
+        surface += material::lighting(&m, l, &over_point, &eyev, &shading, v, rng);
+    }
+    let black = color::color(0.0, 0.0, 0.0);
+    if remaining == 0 || (m.reflective <= 0.0 && m.transparency <= 0.0) {
+        return surface;
+    }
+
+    let mut reflected = black;
+    if m.reflective > 0.0 {
+        let direction = normal3::reflect(&-eyev, &shading);
+        stats::reflection_ray();
+        let bounce = ray::ray_at_time(&over_point, &direction, r.time);
+        reflected = color_at(scene, &bounce, remaining - 1, rng) * m.reflective;
+    }
+
+    // Snell's law. Shapes are taken to sit in air, so nesting one
+    // transparent shape in another is not accounted for.
+    let (n1, n2) = if inside {
+        (m.refractive_index, 1.0)
+    } else {
+        (1.0, m.refractive_index)
+    };
+    let cos_i = normal3::dot(&shading, &eyev);
+    let mut refracted = black;
+    if m.transparency > 0.0 {
+        let ratio = n1 / n2;
+        let sin2_t = ratio * ratio * (1.0 - cos_i * cos_i);
+        // Beyond the critical angle all the light is reflected.
+        if sin2_t <= 1.0 {
+            let cos_t = (1.0 - sin2_t).sqrt();
+            let direction = normal3::to_vector(&shading) * (ratio * cos_i - cos_t) - eyev * ratio;
+            stats::refraction_ray();
+            let through = ray::ray_at_time(&under_point, &direction, r.time);
+            refracted = color_at(scene, &through, remaining - 1, rng) * m.transparency;
+        }
+    }
+
+    // Glass reflects more at grazing angles and lets less through.
+    if m.reflective > 0.0 && m.transparency > 0.0 {
+        let reflectance = bsdf::fresnel_dielectric(cos_i, n1, n2);
+        return surface + reflected * reflectance + refracted * (1.0 - reflectance);
+    }
+    return surface + reflected + refracted;
+}
+
+// True when something lies between point and light_point. Callers should
+// pass a point nudged off the surface to avoid shadowing itself.
+// Shapes in motion are checked where they are at time.
+pub fn is_shadowed(
+    scene: &Scene,
+    point: &point3::Point3,
+    light_point: &point3::Point3,
+    time: Float,
+) -> bool {
+    let v = *light_point - *point;
+    return is_blocked(
+        scene,
+        point,
+        &vector3::normalize(&v),
+        vector3::magnitude(&v),
+        time,
+    );
+}
+
+// True when something lies within distance of point along direction.
+pub fn is_blocked(
+    scene: &Scene,
+    point: &point3::Point3,
+    direction: &vector3::Vector3,
+    distance: Float,
+    time: Float,
+) -> bool {
+    stats::shadow_ray();
+    let r = ray::ray_at_time(point, direction, time);
+    match intersect(scene, &r) {
+        Some(h) => return h.t < distance,
+        None => return false,
+    }
+}
+
+// Fraction of the light's sample points that point can see, from 0.0 (fully
+// in shadow) to 1.0 (fully lit). This is what softens area light shadows.
+// Directional lights have no position, so their shadow rays run parallel and
+// never end.
+pub fn visibility(
+    scene: &Scene,
+    light: &light::Light,
+    point: &point3::Point3,
+    time: Float,
+    rng: &mut random::Random,
+) -> Float {
+    if let light::Light::Directional { direction, .. } = light {
+        let towards = -*direction;
+        if is_blocked(scene, point, &towards, Float::INFINITY, time) {
+            return 0.0;
+        }
+        return 1.0;
+    }
+    let samples = light::sample_points(light, rng);
+    let mut visible = 0;
+    for p in samples.iter() {
+        if !is_shadowed(scene, point, p, time) {
+            visible += 1;
+        }
+    }
+    return visible as Float / samples.len() as Float;
+}
+
+fn add(b: &mut Builder, item: &Node, what: &Node) -> Result<(), yaml::Error> {
+    match text(what)? {
+        "camera" => {
+            check_keys(
+                item,
+                &[
+                    "add",
+                    "width",
+                    "height",
+                    "field-of-view",
+                    "from",
+                    "to",
+                    "up",
+                    "samples",
+                    "max-samples",
+                    "sampler",
+                    "filter",
+                    "aperture",
+                    "focal-distance",
+                    "aperture-shape",
+                    "shutter-open",
+                    "shutter-close",
+                    "ray-epsilon",
+                ],
+            )?;
+            let mut c = camera::camera(
+                count(required(item, "width")?)?,
+                count(required(item, "height")?)?,
+                number(required(item, "field-of-view")?)?,
+            );
+            c.transform = transform::view_transform(
+                &point3::to_tuple(&point(required(item, "from")?)?),
+                &point3::to_tuple(&point(required(item, "to")?)?),
+                &vector3::to_tuple(&vector(required(item, "up")?)?),
+            );
+            if matrix4::inverse(&c.transform).is_none() {
+                return Err(yaml::error(item.line, "camera has no direction to look in"));
+            }
+            if let Some(n) = yaml::get(item, "samples") {
+                c.samples = count(n)?;
+            }
+            if let Some(n) = yaml::get(item, "max-samples") {
+                c.max_samples = count(n)?;
+            }
+            if let Some(n) = yaml::get(item, "sampler") {
+                c.sampler = match text(n)? {
+                    "grid" => sampler::Sampler::Grid,
+                    "jittered" => sampler::Sampler::Jittered,
+                    "halton" => sampler::Sampler::Halton,
+                    other => {
+                        return Err(yaml::error(n.line, &format!("unknown sampler '{}'", other)))
+                    }
+                };
+            }
+            if let Some(n) = yaml::get(item, "filter") {
+                c.filter = match text(n)? {
+                    "box" => filter::box_filter(),
+                    "tent" => filter::tent(),
+                    "gaussian" => filter::gaussian(),
+                    "mitchell" => filter::mitchell(),
+                    other => {
+                        return Err(yaml::error(n.line, &format!("unknown filter '{}'", other)))
+                    }
+                };
+            }
+            if let Some(n) = yaml::get(item, "aperture") {
+                c.aperture = number(n)?;
+            }
+            if let Some(n) = yaml::get(item, "focal-distance") {
+                c.focal_distance = number(n)?;
+            }
+            // disc, or the number of sides of a polygon
+            if let Some(n) = yaml::get(item, "aperture-shape") {
+                c.aperture_shape = match &n.value {
+                    Value::Text(s) if s == "disc" => lens::disc(),
+                    Value::Number(_) if count(n)? >= 3 => lens::polygon(count(n)?),
+                    _ => {
+                        return Err(yaml::error(
+                            n.line,
+                            "expected 'disc' or a number of sides from 3 up",
+                        ))
+                    }
+                };
+            }
+            if let Some(n) = yaml::get(item, "shutter-open") {
+                c.shutter_open = number(n)?;
+            }
+            if let Some(n) = yaml::get(item, "shutter-close") {
+                c.shutter_close = number(n)?;
+            }
+            if let Some(n) = yaml::get(item, "ray-epsilon") {
+                c.ray_epsilon = number(n)?;
+                if c.ray_epsilon <= 0.0 {
+                    return Err(yaml::error(n.line, "ray-epsilon must be positive"));
+                }
+            }
+            b.camera = Some(c);
+        }
+        "light" => b.lights.push(build_light(item)?),
+        "sphere" => {
+            check_keys(item, &["add", "material", "transform", "motion"])?;
+            let mut s = shape::sphere();
+            if let Some(m) = yaml::get(item, "material") {
+                shape::set_material(&mut s, &build_material(b, m)?);
+            }
+            if let Some(t) = yaml::get(item, "transform") {
+                shape::set_transform(&mut s, &build_transform(b, t)?);
+            }
+            if let Some(t) = yaml::get(item, "motion") {
+                shape::set_motion(&mut s, &build_transform(b, t)?);
+            }
+            b.shapes.push(s);
+        }
+        "background" => b.background = build_background(b, item)?,
+        "obj" => {
+            check_keys(item, &["add", "file", "material", "transform", "motion"])?;
+            let file = required(item, "file")?;
+            let path = b.dir.join(text(file)?);
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    let message = format!("cannot read {}: {}", path.display(), e);
+                    return Err(yaml::error(file.line, &message));
+                }
+            };
+            let triangles = match obj::parse(&source) {
+                Ok(triangles) => triangles,
+                Err(e) => {
+                    let message = format!("{}: {}", path.display(), e);
+                    return Err(yaml::error(file.line, &message));
+                }
+            };
+            let m = match yaml::get(item, "material") {
+                Some(m) => Some(build_material(b, m)?),
+                None => None,
+            };
+            let t = match yaml::get(item, "transform") {
+                Some(t) => Some(build_transform(b, t)?),
+                None => None,
+            };
+            let motion = match yaml::get(item, "motion") {
+                Some(t) => Some(build_transform(b, t)?),
+                None => None,
+            };
+            for mut s in triangles {
+                if let Some(m) = &m {
+                    shape::set_material(&mut s, m);
+                }
+                if let Some(t) = &t {
+                    shape::set_transform(&mut s, t);
+                }
+                if let Some(t) = &motion {
+                    shape::set_motion(&mut s, t);
+                }
+                b.shapes.push(s);
+            }
+        }
+        other => {
+            return Err(yaml::error(
+                what.line,
+                &format!("unknown object '{}'", other),
+            ))
+        }
+    }
+    return Ok(());
+}
+
+fn build_light(item: &Node) -> Result<light::Light, yaml::Error> {
+    let kind = match yaml::get(item, "type") {
+        Some(n) => text(n)?,
+        None => "point",
+    };
+    let intensity = rgb(required(item, "intensity")?)?;
+    let mut l = match kind {
+        "point" => {
+            check_keys(item, &["add", "type", "at", "intensity", "attenuation"])?;
+            light::point_light(&point(required(item, "at")?)?, &intensity)
+        }
+        "directional" => {
+            check_keys(item, &["add", "type", "direction", "intensity"])?;
+            light::directional_light(&direction(required(item, "direction")?)?, &intensity)
+        }
+        "spot" => {
+            check_keys(
+                item,
+                &[
+                    "add",
+                    "type",
+                    "at",
+                    "direction",
+                    "inner-angle",
+                    "outer-angle",
+                    "intensity",
+                    "attenuation",
+                ],
+            )?;
+            light::spot_light(
+                &point(required(item, "at")?)?,
+                &direction(required(item, "direction")?)?,
+                number(required(item, "inner-angle")?)?,
+                number(required(item, "outer-angle")?)?,
+                &intensity,
+            )
+        }
+        "rectangle" => {
+            check_keys(
+                item,
+                &[
+                    "add",
+                    "type",
+                    "corner",
+                    "uvec",
+                    "vvec",
+                    "intensity",
+                    "samples",
+                    "attenuation",
+                ],
+            )?;
+            light::rectangle_light(
+                &point(required(item, "corner")?)?,
+                &vector(required(item, "uvec")?)?,
+                &vector(required(item, "vvec")?)?,
+                &intensity,
+                count(required(item, "samples")?)?,
+            )
+        }
+        "sphere" => {
+            check_keys(
+                item,
+                &[
+                    "add",
+                    "type",
+                    "at",
+                    "radius",
+                    "intensity",
+                    "samples",
+                    "attenuation",
+                ],
+            )?;
+            light::sphere_light(
+                &point(required(item, "at")?)?,
+                number(required(item, "radius")?)?,
+                &intensity,
+                count(required(item, "samples")?)?,
+            )
+        }
+        other => {
+            let line = yaml::get(item, "type").map_or(item.line, |n| n.line);
+            return Err(yaml::error(
+                line,
+                &format!("unknown light type '{}'", other),
+            ));
+        }
+    };
+    // [ constant, linear, quadratic ]. Negative terms or none at all would
+    // let the denominator reach zero or below at some distance.
+    if let Some(n) = yaml::get(item, "attenuation") {
+        let (c, l1, q) = triple(n)?;
+        if c < 0.0 || l1 < 0.0 || q < 0.0 || c + l1 + q == 0.0 {
+            return Err(yaml::error(
+                n.line,
+                "attenuation must be non-negative and not all zero",
+            ));
+        }
+        light::set_attenuation(&mut l, &light::attenuation(c, l1, q));
+    }
+    return Ok(l);
+}
+
+fn build_background(b: &Builder, item: &Node) -> Result<background::Background, yaml::Error> {
+    let kind = match yaml::get(item, "type") {
+        Some(n) => text(n)?,
+        None => "constant",
+    };
+    match kind {
+        "constant" => {
+            check_keys(item, &["add", "type", "color"])?;
+            return Ok(background::constant(&rgb(required(item, "color")?)?));
+        }
+        "gradient" => {
+            check_keys(item, &["add", "type", "bottom", "top"])?;
+            return Ok(background::gradient(
+                &rgb(required(item, "bottom")?)?,
+                &rgb(required(item, "top")?)?,
+            ));
+        }
+        "environment" => {
+            check_keys(item, &["add", "type", "file", "strength"])?;
+            let file = required(item, "file")?;
+            let path = b.dir.join(text(file)?);
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    let message = format!("cannot read {}: {}", path.display(), e);
+                    return Err(yaml::error(file.line, &message));
+                }
+            };
+            let image = match hdr::parse(&bytes) {
+                Ok(image) => image,
+                Err(e) => {
+                    let message = format!("{}: {}", path.display(), e);
+                    return Err(yaml::error(file.line, &message));
+                }
+            };
+            let strength = match yaml::get(item, "strength") {
+                Some(n) => number(n)?,
+                None => 1.0,
+            };
+            return Ok(background::environment(image, strength));
+        }
+        other => {
+            let line = yaml::get(item, "type").map_or(item.line, |n| n.line);
+            return Err(yaml::error(
+                line,
+                &format!("unknown background type '{}'", other),
+            ));
+        }
+    }
+}
+
+fn define(b: &mut Builder, item: &Node, name: &Node) -> Result<(), yaml::Error> {
+    check_keys(item, &["define", "extend", "value"])?;
+    let name = text(name)?;
+    let value = required(item, "value")?;
+    let resolved = match yaml::get(item, "extend") {
+        None => value.clone(),
+        Some(base) => {
+            let base = lookup(b, base)?;
+            match (&base.value, &value.value) {
+                (Value::Map(old), Value::Map(new)) => {
+                    let mut entries: Vec<(String, Node)> = old
+                        .iter()
+                        .filter(|(k, _)| !new.iter().any(|(n, _)| n == k))
+                        .cloned()
+                        .collect();
+                    entries.extend(new.iter().cloned());
+                    Node {
+                        line: value.line,
+                        value: Value::Map(entries),
+                    }
+                }
+                (Value::List(old), Value::List(new)) => {
+                    let mut items = old.clone();
+                    items.extend(new.iter().cloned());
+                    Node {
+                        line: value.line,
+                        value: Value::List(items),
+                    }
+                }
+                _ => {
+                    return Err(yaml::error(
+                        value.line,
+                        "cannot extend a different kind of value",
+                    ))
+                }
+            }
+        }
+    };
+    b.defines.insert(String::from(name), resolved);
+    return Ok(());
+}
+
+fn lookup<'a>(b: &'a Builder, name: &Node) -> Result<&'a Node, yaml::Error> {
+    let key = text(name)?;
+    match b.defines.get(key) {
+        Some(node) => return Ok(node),
+        None => return Err(yaml::error(name.line, &format!("'{}' is not defined", key))),
+    }
+}
+
+fn build_material(b: &mut Builder, node: &Node) -> Result<material::Material, yaml::Error> {
+    let node = match node.value {
+        Value::Text(_) => lookup(b, node)?.clone(),
+        _ => node.clone(),
+    };
+    let entries = match &node.value {
+        Value::Map(entries) => entries,
+        _ => return Err(yaml::error(node.line, "expected a material")),
+    };
+    let mut m = material::material();
+    let mut kind: Option<&Node> = None;
+    let mut roughness = 0.0;
+    let mut eta = color::color(1.0, 1.0, 1.0);
+    let mut k = color::color(0.0, 0.0, 0.0);
+    for (key, value) in entries {
+        match key.as_str() {
+            "bsdf" => kind = Some(value),
+            "roughness" => roughness = number(value)?,
+            "eta" => eta = rgb(value)?,
+            "k" => k = rgb(value)?,
+            "color" => m.color = rgb(value)?,
+            "ambient" => m.ambient = number(value)?,
+            "diffuse" => m.diffuse = number(value)?,
+            "specular" => m.specular = number(value)?,
+            "shininess" => m.shininess = number(value)?,
+            "reflective" => m.reflective = number(value)?,
+            "transparency" => m.transparency = number(value)?,
+            "refractive-index" => m.refractive_index = number(value)?,
+            "emission" => m.emission = rgb(value)?,
+            "emission-strength" => m.emission_strength = number(value)?,
+            "texture" => {
+                let t = build_texture(b, value)?;
+                b.textures.push(t);
+                m.texture = Some(b.textures.len() - 1);
+            }
+            "normal-map" => {
+                let t = build_texture(b, value)?;
+                b.textures.push(t);
+                m.normal_map = Some(b.textures.len() - 1);
+            }
+            "bump" => m.bump = Some(build_bump(value)?),
+            _ => {
+                return Err(yaml::error(
+                    value.line,
+                    &format!("unknown material property '{}'", key),
+                ))
+            }
+        }
+    }
+    if let Some(n) = kind {
+        m.bsdf = match text(n)? {
+            "lambertian" => bsdf::lambertian(),
+            "microfacet" => bsdf::microfacet(roughness),
+            "conductor" => bsdf::conductor(roughness, &eta, &k),
+            "dielectric" => bsdf::dielectric(roughness),
+            other => return Err(yaml::error(n.line, &format!("unknown bsdf '{}'", other))),
+        };
+    }
+    return Ok(m);
+}
+
+fn build_texture(b: &Builder, node: &Node) -> Result<texture::Texture, yaml::Error> {
+    check_keys(node, &["file", "mapping", "filter", "wrap"])?;
+    let file = required(node, "file")?;
+    let path = b.dir.join(text(file)?);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let message = format!("cannot read {}: {}", path.display(), e);
+            return Err(yaml::error(file.line, &message));
+        }
+    };
+    let image = match texture::load(&bytes) {
+        Ok(image) => image,
+        Err(e) => {
+            let message = format!("{}: {}", path.display(), e);
+            return Err(yaml::error(file.line, &message));
+        }
+    };
+    let mapping = match yaml::get(node, "mapping") {
+        None => texture::Mapping::Spherical,
+        Some(n) => match text(n)? {
+            "spherical" => texture::Mapping::Spherical,
+            "planar" => texture::Mapping::Planar,
+            "cylindrical" => texture::Mapping::Cylindrical,
+            "cube" => texture::Mapping::Cube,
+            other => return Err(yaml::error(n.line, &format!("unknown mapping '{}'", other))),
+        },
+    };
+    let mut t = texture::texture(image, mapping);
+    if let Some(n) = yaml::get(node, "filter") {
+        t.filter = match text(n)? {
+            "nearest" => texture::Filter::Nearest,
+            "bilinear" => texture::Filter::Bilinear,
+            other => return Err(yaml::error(n.line, &format!("unknown filter '{}'", other))),
+        };
+    }
+    if let Some(n) = yaml::get(node, "wrap") {
+        t.wrap = match text(n)? {
+            "repeat" => texture::Wrap::Repeat,
+            "clamp" => texture::Wrap::Clamp,
+            other => return Err(yaml::error(n.line, &format!("unknown wrap '{}'", other))),
+        };
+    }
+    return Ok(t);
+}
+
+fn build_bump(node: &Node) -> Result<bump::Bump, yaml::Error> {
+    let kind = required(node, "type")?;
+    let amplitude = number(required(node, "amplitude")?)?;
+    match text(kind)? {
+        "noise" => {
+            check_keys(node, &["type", "scale", "amplitude"])?;
+            return Ok(bump::noise(number(required(node, "scale")?)?, amplitude));
+        }
+        "waves" => {
+            check_keys(node, &["type", "frequency", "amplitude"])?;
+            return Ok(bump::waves(
+                number(required(node, "frequency")?)?,
+                amplitude,
+            ));
+        }
+        other => {
+            return Err(yaml::error(
+                kind.line,
+                &format!("unknown bump type '{}'", other),
+            ))
+        }
+    }
+}
+
+fn build_transform(b: &Builder, node: &Node) -> Result<matrix4::Matrix4, yaml::Error> {
+    let steps = match &node.value {
+        Value::Text(_) => list(lookup(b, node)?)?,
+        _ => list(node)?,
+    };
+    let mut m = matrix4::IDENTITY;
+    for step in steps {
+        m = match &step.value {
+            Value::Text(_) => m.then(&build_transform(b, step)?),
+            _ => build_step(m, step)?,
+        };
+    }
+    return Ok(m);
+}
+
+// m followed by step.
+fn build_step(m: matrix4::Matrix4, step: &Node) -> Result<matrix4::Matrix4, yaml::Error> {
+    let items = list(step)?;
+    if items.is_empty() {
+        return Err(yaml::error(step.line, "empty transform"));
+    }
+    let op = text(&items[0])?;
+    let mut args = Vec::with_capacity(items.len() - 1);
+    for item in &items[1..] {
+        args.push(number(item)?);
+    }
+    let expected = match op {
+        "translate" | "scale" => 3,
+        "rotate-x" | "rotate-y" | "rotate-z" => 1,
+        "rotate" | "quaternion" => 4,
+        "rotate-xyz" | "rotate-xzy" | "rotate-yxz" | "rotate-yzx" | "rotate-zxy" | "rotate-zyx" => {
+            3
+        }
+        "shear" | "look-along" => 6,
+        _ => {
+            return Err(yaml::error(
+                step.line,
+                &format!("unknown transform '{}'", op),
+            ))
+        }
+    };
+    if args.len() != expected {
+        return Err(yaml::error(
+            step.line,
+            &format!("'{}' takes {} numbers, found {}", op, expected, args.len()),
+        ));
+    }
+    match op {
+        "translate" => return Ok(m.translate(args[0], args[1], args[2])),
+        "scale" => return Ok(m.scale(args[0], args[1], args[2])),
+        "rotate-x" => return Ok(m.rotate_x(args[0])),
+        "rotate-y" => return Ok(m.rotate_y(args[0])),
+        "rotate-z" => return Ok(m.rotate_z(args[0])),
+        "rotate" => {
+            let axis = tuple::vector(args[0], args[1], args[2]);
+            if tuple::magnitude(&axis) == 0.0 {
+                return Err(yaml::error(step.line, "rotation axis is zero"));
+            }
+            return Ok(m.rotate(&axis, args[3]));
+        }
+        "quaternion" => {
+            let q = quaternion::quaternion(args[0], args[1], args[2], args[3]);
+            if quaternion::dot(&q, &q) == 0.0 {
+                return Err(yaml::error(step.line, "quaternion is zero"));
+            }
+            return Ok(m.rotate_quaternion(&q));
+        }
+        "look-along" => {
+            let direction = tuple::vector(args[0], args[1], args[2]);
+            if tuple::magnitude(&direction) == 0.0 {
+                return Err(yaml::error(step.line, "look-along direction is zero"));
+            }
+            let up = tuple::vector(args[3], args[4], args[5]);
+            return Ok(m.look_along(&direction, &up));
+        }
+        "shear" => return Ok(m.shear(args[0], args[1], args[2], args[3], args[4], args[5])),
+        _ => {
+            let order = match op {
+                "rotate-xyz" => transform::EulerOrder::Xyz,
+                "rotate-xzy" => transform::EulerOrder::Xzy,
+                "rotate-yxz" => transform::EulerOrder::Yxz,
+                "rotate-yzx" => transform::EulerOrder::Yzx,
+                "rotate-zxy" => transform::EulerOrder::Zxy,
+                _ => transform::EulerOrder::Zyx,
+            };
+            return Ok(m.rotate_euler(args[0], args[1], args[2], order));
+        }
+    }
+}
+
+fn check_keys(item: &Node, allowed: &[&str]) -> Result<(), yaml::Error> {
+    if let Value::Map(entries) = &item.value {
+        for (key, value) in entries {
+            if !allowed.contains(&key.as_str()) {
+                return Err(yaml::error(value.line, &format!("unknown key '{}'", key)));
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn required<'a>(item: &'a Node, key: &str) -> Result<&'a Node, yaml::Error> {
+    match yaml::get(item, key) {
+        Some(node) => return Ok(node),
+        None => return Err(yaml::error(item.line, &format!("missing '{}'", key))),
+    }
+}
+
+fn text(node: &Node) -> Result<&str, yaml::Error> {
+    match &node.value {
+        Value::Text(s) => return Ok(s),
+        _ => return Err(yaml::error(node.line, "expected a name")),
+    }
+}
+
+fn number(node: &Node) -> Result<Float, yaml::Error> {
+    match node.value {
+        Value::Number(n) => return Ok(n),
+        _ => return Err(yaml::error(node.line, "expected a number")),
+    }
+}
+
+fn count(node: &Node) -> Result<usize, yaml::Error> {
+    let n = number(node)?;
+    if n < 1.0 || n.fract() != 0.0 {
+        return Err(yaml::error(node.line, "expected a positive whole number"));
+    }
+    return Ok(n as usize);
+}
+
+fn list(node: &Node) -> Result<&Vec<Node>, yaml::Error> {
+    match &node.value {
+        Value::List(items) => return Ok(items),
+        _ => return Err(yaml::error(node.line, "expected a list")),
+    }
+}
+
+fn triple(node: &Node) -> Result<(Float, Float, Float), yaml::Error> {
+    let items = list(node)?;
+    if items.len() != 3 {
+        return Err(yaml::error(node.line, "expected three numbers"));
+    }
+    return Ok((number(&items[0])?, number(&items[1])?, number(&items[2])?));
+}
+
+fn point(node: &Node) -> Result<point3::Point3, yaml::Error> {
+    let (x, y, z) = triple(node)?;
+    return Ok(point3::point3(x, y, z));
+}
+
+fn vector(node: &Node) -> Result<vector3::Vector3, yaml::Error> {
+    let (x, y, z) = triple(node)?;
+    return Ok(vector3::vector3(x, y, z));
+}
+
+// A vector that points somewhere, for lights to shine along.
+fn direction(node: &Node) -> Result<vector3::Vector3, yaml::Error> {
+    let v = vector(node)?;
+    if vector3::magnitude(&v) == 0.0 {
+        return Err(yaml::error(node.line, "direction must not be zero"));
+    }
+    return Ok(v);
+}
+
+fn rgb(node: &Node) -> Result<color::Color, yaml::Error> {
+    let (r, g, b) = triple(node)?;
+    return Ok(color::color(r, g, b));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+    use crate::matrix;
+
+    const CAMERA: &str = "- add: camera\n  width: 100\n  height: 50\n  field-of-view: 0.785\n  from: [ 0, 0, -5 ]\n  to: [ 0, 0, 0 ]\n  up: [ 0, 1, 0 ]\n";
+
+    #[test]
+    fn should_load_camera_and_light() {
+        let text = format!(
+            "{}  samples: 4\n  sampler: halton\n  ray-epsilon: 0.001\n- add: light\n  at: [ -10, 10, -10 ]\n  intensity: [ 1, 1, 1 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        assert!(s.camera.hsize == 100);
+        assert!(s.camera.vsize == 50);
+        assert!(s.camera.samples == 4);
+        assert!(s.camera.sampler == sampler::Sampler::Halton);
+        assert!(s.camera.ray_epsilon == 0.001);
+        assert!(matrix::equals(
+            &s.camera.transform,
+            &matrix::multiply(
+                &transform::scaling(-1.0, 1.0, -1.0),
+                &transform::translation(0.0, 0.0, 5.0)
+            )
+        ));
+        assert!(s.lights.len() == 1);
+    }
+
+    #[test]
+    fn should_load_thin_lens() {
+        let text = format!(
+            "{}  aperture: 0.2\n  focal-distance: 5\n  aperture-shape: 6\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        assert!(float::equals(s.camera.aperture, 0.2));
+        assert!(float::equals(s.camera.focal_distance, 5.0));
+        assert!(s.camera.aperture_shape == lens::polygon(6));
+
+        let text = format!("{}  aperture: 0.2\n  aperture-shape: 2\n", CAMERA);
+        let e = parse(&text).err().unwrap();
+        assert!(e.line == 9);
+    }
+
+    #[test]
+    fn should_load_motion() {
+        let text = format!(
+            "{}  shutter-open: 0\n  shutter-close: 1\n- add: sphere\n  motion:\n    - [ translate, 4, 0, 0 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        assert!(float::equals(s.camera.shutter_close, 1.0));
+        let at = |time: Float| {
+            let r = ray::ray_at_time(
+                &point3::point3(3.0, 0.0, -5.0),
+                &vector3::vector3(0.0, 0.0, 1.0),
+                time,
+            );
+            return intersect(&s, &r).is_some();
+        };
+        assert!(!at(0.0));
+        assert!(at(0.75));
+        assert!(at(1.0));
+
+        // An instant's exposure freezes shapes where they are then.
+        let text = format!(
+            "{}  shutter-open: 0.5\n  shutter-close: 0.5\n- add: sphere\n  motion:\n    - [ translate, 4, 0, 0 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let halfway = transform::translation(2.0, 0.0, 0.0);
+        assert!(matrix::equals(
+            &shape::transform_at(&s.shapes[0], 1.0),
+            &halfway
+        ));
+    }
+
+    #[test]
+    fn should_apply_transforms_in_listed_order() {
+        let text = format!(
+            "{}- add: sphere\n  transform:\n    - [ rotate-x, 1.5707963267948966 ]\n    - [ scale, 5, 5, 5 ]\n    - [ translate, 10, 5, 7 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let p = matrix4::multiply_tuple(
+            &shape::transform(&s.shapes[0]),
+            &tuple::point(1.0, 0.0, 1.0),
+        );
+        assert!(tuple::equals(&p, &tuple::point(15.0, 0.0, 7.0)));
+    }
+
+    #[test]
+    fn should_define_and_extend() {
+        let text = format!(
+            "{}\
+             - define: red\n  value:\n    color: [ 1, 0, 0 ]\n    ambient: 0.5\n\
+             - define: shiny-red\n  extend: red\n  value:\n    ambient: 0.2\n\
+             - define: up\n  value:\n    - [ translate, 0, 1, 0 ]\n\
+             - define: up-twice\n  extend: up\n  value:\n    - up\n\
+             - add: sphere\n  material: shiny-red\n  transform: up-twice\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let m = shape::material(&s.shapes[0]);
+        assert!(color::equals(&m.color, &color::color(1.0, 0.0, 0.0)));
+        assert!(float::equals(m.ambient, 0.2));
+        assert!(matrix::equals(
+            &shape::transform(&s.shapes[0]),
+            &transform::translation(0.0, 2.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_load_bsdf() {
+        let text = format!(
+            "{}- add: sphere\n  material:\n    bsdf: conductor\n    roughness: 0.3\n    eta: [ 0.2, 0.4, 1.4 ]\n    k: [ 4, 2.4, 1.6 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        match shape::material(&s.shapes[0]).bsdf {
+            bsdf::Bsdf::Conductor { roughness, k, .. } => {
+                assert!(float::equals(roughness, 0.3));
+                assert!(float::equals(k.green, 2.4));
+            }
+            _ => panic!("expected a conductor"),
+        }
+
+        let text = format!("{}- add: sphere\n  material:\n    bsdf: velvet\n", CAMERA);
+        let e = parse(&text).err().unwrap();
+        assert!(e.to_string() == "line 10: unknown bsdf 'velvet'");
+    }
+
+    #[test]
+    fn should_load_emission() {
+        let text = format!(
+            "{}- add: sphere\n  material:\n    emission: [ 1, 0.5, 0 ]\n    emission-strength: 2\n- add: sphere\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let e = emitters(&s);
+        assert!(e.len() == 1);
+        assert!(shape::equals(&e[0], &s.shapes[0]));
+        let m = shape::material(&s.shapes[0]);
+        assert!(color::equals(
+            &material::emitted(&m),
+            &color::color(2.0, 1.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_load_background() {
+        let text = format!(
+            "{}- add: background\n  type: gradient\n  bottom: [ 0, 0, 0 ]\n  top: [ 0, 0, 1 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let up = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 1.0, 0.0),
+        );
+        assert!(color::equals(
+            &color_at(&s, &up, 5, &mut random::random(0)),
+            &color::color(0.0, 0.0, 1.0)
+        ));
+
+        let text = format!(
+            "{}- add: background\n  type: environment\n  file: missing.hdr\n",
+            CAMERA
+        );
+        let e = parse_in(&text, Path::new("no-such-dir")).err().unwrap();
+        assert!(e.line == 10);
+        assert!(e.message.contains("no-such-dir"));
+    }
+
+    #[test]
+    fn should_load_texture() {
+        let dir = std::env::temp_dir().join(format!("scene-texture-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("half.ppm"), "P3\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let text = format!(
+            "{}- add: sphere\n  material:\n    texture:\n      file: half.ppm\n      mapping: spherical\n      filter: nearest\n",
+            CAMERA
+        );
+        let s = parse_in(&text, &dir).unwrap();
+        assert!(s.textures.len() == 1);
+        // Spherical u runs from -z through +x, so +x is the left half.
+        let left = surface_color(&s, &s.shapes[0], &point3::point3(1.0, 0.0, 0.0));
+        assert!(color::equals(&left, &color::color(1.0, 0.0, 0.0)));
+        let right = surface_color(&s, &s.shapes[0], &point3::point3(-1.0, 0.0, 0.0));
+        assert!(color::equals(&right, &color::color(0.0, 0.0, 1.0)));
+
+        let text = format!(
+            "{}- add: sphere\n  material:\n    texture:\n      file: half.ppm\n      mapping: conical\n",
+            CAMERA
+        );
+        let e = parse_in(&text, &dir).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(e.to_string() == "line 12: unknown mapping 'conical'");
+    }
+
+    #[test]
+    fn should_load_obj() {
+        let dir = std::env::temp_dir().join(format!("scene-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("quad.obj"),
+            "# a unit square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.obj"), "v 0 0 0\nf 1 2 3\n").unwrap();
+        let text = format!(
+            "{}- add: obj\n  file: quad.obj\n  material:\n    color: [ 0, 1, 0 ]\n  transform:\n    - [ translate, -0.5, -0.5, 0 ]\n",
+            CAMERA
+        );
+        let s = parse_in(&text, &dir).unwrap();
+        assert!(s.shapes.len() == 2);
+        let r = ray::ray(
+            &point3::point3(0.25, 0.25, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let hit = intersect(&s, &r).unwrap();
+        assert!(float::equals(hit.t, 5.0));
+        assert!(color::equals(
+            &shape::material(&hit.s).color,
+            &color::color(0.0, 1.0, 0.0)
+        ));
+
+        let text = format!("{}- add: obj\n  file: broken.obj\n", CAMERA);
+        let e = parse_in(&text, &dir).err().unwrap();
+        assert!(e.line == 9);
+        assert!(e
+            .message
+            .ends_with("broken.obj: line 2: vertex 2 does not exist"));
+        let text = format!("{}- add: obj\n  file: missing.obj\n", CAMERA);
+        let e = parse_in(&text, &dir).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(e.line == 9);
+        assert!(e.message.contains("cannot read"));
+    }
+
+    #[test]
+    fn should_texture_obj_by_its_coordinates() {
+        let dir = std::env::temp_dir().join(format!("scene-obj-uv-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("half.ppm"), "P3\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        // The texture coordinates run the image backwards across the quad.
+        fs::write(
+            dir.join("quad.obj"),
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 1 0\nvt 0 0\nvt 0 1\nvt 1 1\nf 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+        let text = format!(
+            "{}- add: obj\n  file: quad.obj\n  material:\n    texture:\n      file: half.ppm\n      mapping: planar\n      filter: nearest\n",
+            CAMERA
+        );
+        let s = parse_in(&text, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let left = surface_color(&s, &s.shapes[0], &point3::point3(0.2, 0.1, 0.0));
+        assert!(color::equals(&left, &color::color(0.0, 0.0, 1.0)));
+        let right = surface_color(&s, &s.shapes[0], &point3::point3(0.8, 0.1, 0.0));
+        assert!(color::equals(&right, &color::color(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn should_orient_normal_maps_by_obj_coordinates() {
+        let dir = std::env::temp_dir().join(format!("scene-obj-normals-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Every normal leans fully along u.
+        fs::write(dir.join("lean.ppm"), "P3\n1 1\n255\n255 128 128\n").unwrap();
+        // u runs up the quad and v across it, which a planar map never does.
+        fs::write(
+            dir.join("quad.obj"),
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 0 1\nvt 1 1\nvt 1 0\nf 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+        let text = format!(
+            "{}- add: obj\n  file: quad.obj\n  material:\n    normal-map:\n      file: lean.ppm\n      mapping: planar\n",
+            CAMERA
+        );
+        let s = parse_in(&text, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        for shape in s.shapes.iter() {
+            let n = shading_normal(&s, shape, &point3::point3(0.5, 0.5, 0.0));
+            assert!(n.y > 0.999);
+        }
+    }
+
+    #[test]
+    fn should_load_bump() {
+        let text = format!(
+            "{}- add: sphere\n  material:\n    bump:\n      type: waves\n      frequency: 1\n      amplitude: 0.1\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        assert!(shape::material(&s.shapes[0]).bump == Some(bump::waves(1.0, 0.1)));
+        let p = point3::point3(0.0, 1.0, 0.0);
+        let n = shading_normal(&s, &s.shapes[0], &p);
+        let expected = normal3::normalize(&normal3::normal3(-0.1, 1.0, -0.1));
+        assert!(normal3::equals(&n, &expected));
+
+        let text = format!(
+            "{}- add: sphere\n  material:\n    bump:\n      type: ripples\n      amplitude: 1\n",
+            CAMERA
+        );
+        let e = parse(&text).err().unwrap();
+        assert!(e.to_string() == "line 11: unknown bump type 'ripples'");
+    }
+
+    #[test]
+    fn should_report_errors_with_line_numbers() {
+        let missing = parse("- add: sphere\n").err().unwrap();
+        assert!(missing.to_string() == "line 1: scene has no camera");
+
+        let text = format!("{}- add: sphere\n  material: nothing\n", CAMERA);
+        let e = parse(&text).err().unwrap();
+        assert!(e.to_string() == "line 9: 'nothing' is not defined");
+
+        let text = format!(
+            "{}- add: sphere\n  transform:\n    - [ scale, 1, 2 ]\n",
+            CAMERA
+        );
+        let e = parse(&text).err().unwrap();
+        assert!(e.to_string() == "line 10: 'scale' takes 3 numbers, found 2");
+
+        let text = format!(
+            "{}- add: sphere\n  material:\n    colour: [ 1, 0, 0 ]\n",
+            CAMERA
+        );
+        let e = parse(&text).err().unwrap();
+        assert!(e.line == 10);
+    }
+
+    #[test]
+    fn should_shade_hits_with_material() {
+        let text = format!(
+            "{}- add: light\n  at: [ 0, 0, -10 ]\n  intensity: [ 1, 1, 1 ]\n- add: sphere\n  material:\n    color: [ 0, 1, 0 ]\n    specular: 0\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let mut rng = random::random(0);
+        let hit = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let miss = ray::ray(
+            &point3::point3(0.0, 2.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        assert!(color::equals(
+            &color_at(&s, &hit, 5, &mut rng),
+            &color::color(0.0, 1.0, 0.0)
+        ));
+        assert!(color::equals(
+            &color_at(&s, &miss, 5, &mut rng),
+            &color::color(0.0, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_shade_hits_in_shadow() {
+        let text = format!(
+            "{}- add: light\n  at: [ 0, 0, -10 ]\n  intensity: [ 1, 1, 1 ]\n- add: sphere\n- add: sphere\n  transform:\n    - [ translate, 0, 0, 10 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, 5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let c = color_at(&s, &r, 5, &mut random::random(0));
+        assert!(color::equals(&c, &color::color(0.1, 0.1, 0.1)));
+    }
+
+    #[test]
+    fn should_reflect_until_depth_runs_out() {
+        let text = format!(
+            "{}- add: background\n  color: [ 0.5, 0.5, 0.5 ]\n- add: sphere\n  material:\n    color: [ 0, 0, 0 ]\n    ambient: 0\n    reflective: 1\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let mut rng = random::random(0);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.0, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let grey = color::color(0.5, 0.5, 0.5);
+        assert!(color::equals(&color_at(&s, &r, 1, &mut rng), &grey));
+        let black = color::color(0.0, 0.0, 0.0);
+        assert!(color::equals(&color_at(&s, &r, 0, &mut rng), &black));
+    }
+
+    // Rays leave a surface a distance relative to the coordinates there, which
+    // has to stay small next to the scene even a long way from the origin.
+    // f32 has too few digits to place anything at 1e6.
+    #[cfg(not(feature = "f32"))]
+    #[test]
+    fn should_render_the_same_far_from_the_origin() {
+        use crate::canvas;
+        let render = |x: Float| {
+            let text = format!(
+                "- add: camera\n  width: 20\n  height: 20\n  field-of-view: 0.785\n  from: [ {x}, 1, -5 ]\n  to: [ {x}, 0, 0 ]\n  up: [ 0, 1, 0 ]\n- add: light\n  at: [ {l}, 10, -10 ]\n  intensity: [ 1, 1, 1 ]\n- add: sphere\n  transform:\n    - [ translate, {x}, 0, 0 ]\n",
+                x = x,
+                l = x - 10.0
+            );
+            let s = parse(&text).unwrap();
+            return camera::render(&s.camera, |r, rng| color_at(&s, r, 5, rng));
+        };
+        let near = render(0.0);
+        let far = render(1e6);
+        for y in 0..20 {
+            for x in 0..20 {
+                let a = canvas::pixel_at(&near, x, y);
+                let b = canvas::pixel_at(&far, x, y);
+                assert!((a.red - b.red).abs() < 0.002);
+                assert!((a.green - b.green).abs() < 0.002);
+                assert!((a.blue - b.blue).abs() < 0.002);
+            }
+        }
+    }
+
+    #[test]
+    fn should_refract_through_transparent_shapes() {
+        // Light bends towards the normal going into glass and away from it
+        // coming out, so a glass ball focuses what is behind it.
+        let text = format!(
+            "{}- add: background\n  type: gradient\n  bottom: [ 0, 0, 0 ]\n  top: [ 1, 1, 1 ]\n- add: sphere\n  material:\n    color: [ 0, 0, 0 ]\n    ambient: 0\n    transparency: 1\n    refractive-index: 1.5\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let mut rng = random::random(0);
+        let r = ray::ray(
+            &point3::point3(0.0, 0.5, -5.0),
+            &vector3::vector3(0.0, 0.0, 1.0),
+        );
+        let through = color_at(&s, &r, 5, &mut rng);
+        // Bent downwards, so it sees less of the bright sky than straight on.
+        let straight = background::radiance(&s.background, &vector3::vector3(0.0, 0.0, 1.0));
+        assert!(through.red < straight.red - 0.1);
+        // Inside the ball with no depth left, nothing comes through.
+        assert!(color::equals(
+            &color_at(&s, &r, 1, &mut rng),
+            &color::color(0.0, 0.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_load_area_lights() {
+        let text = format!(
+            "{}\
+             - add: light\n  type: rectangle\n  corner: [ -1, 2, 4 ]\n  uvec: [ 2, 0, 0 ]\n  vvec: [ 0, 2, 0 ]\n  intensity: [ 1, 1, 1 ]\n  samples: 16\n\
+             - add: light\n  type: sphere\n  at: [ 0, 5, 0 ]\n  radius: 0.5\n  intensity: [ 1, 1, 1 ]\n  samples: 8\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        assert!(matches!(
+            s.lights[0],
+            light::Light::Rectangle { samples: 16, .. }
+        ));
+        assert!(matches!(
+            s.lights[1],
+            light::Light::Sphere { samples: 8, .. }
+        ));
+
+        let text = format!(
+            "{}- add: light\n  type: tube\n  intensity: [ 1, 1, 1 ]\n",
+            CAMERA
+        );
+        assert!(parse(&text).err().unwrap().line == 9);
+    }
+
+    #[test]
+    fn should_load_spot_and_directional_lights() {
+        let text = format!(
+            "{}\
+             - add: light\n  type: spot\n  at: [ 0, 5, 0 ]\n  direction: [ 0, -1, 0 ]\n  inner-angle: 0.3\n  outer-angle: 0.5\n  intensity: [ 1, 1, 1 ]\n  attenuation: [ 1, 0, 0.5 ]\n\
+             - add: light\n  type: directional\n  direction: [ 1, -1, 0 ]\n  intensity: [ 1, 1, 1 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        match s.lights[0] {
+            light::Light::Spot {
+                inner_angle,
+                attenuation,
+                ..
+            } => {
+                assert!(float::equals(inner_angle, 0.3));
+                assert!(float::equals(attenuation.quadratic, 0.5));
+            }
+            _ => panic!("expected a spot light"),
+        }
+        assert!(matches!(s.lights[1], light::Light::Directional { .. }));
+    }
+
+    #[test]
+    fn should_cast_parallel_shadows_from_directional_light() {
+        let text = format!("{}- add: sphere\n", CAMERA);
+        let s = parse(&text).unwrap();
+        let sun = light::directional_light(
+            &vector3::vector3(0.0, -1.0, 0.0),
+            &color::color(1.0, 1.0, 1.0),
+        );
+        let mut rng = random::random(0);
+        let below = point3::point3(0.5, -1000.0, 0.0);
+        let beside = point3::point3(1.5, -1000.0, 0.0);
+        assert!(float::equals(
+            visibility(&s, &sun, &below, 0.0, &mut rng),
+            0.0
+        ));
+        assert!(float::equals(
+            visibility(&s, &sun, &beside, 0.0, &mut rng),
+            1.0
+        ));
+    }
+
+    // Page 111
+    #[test]
+    fn should_find_shadows() {
+        let text = format!("{}- add: sphere\n", CAMERA);
+        let s = parse(&text).unwrap();
+        let light_point = point3::point3(-10.0, 10.0, -10.0);
+        assert!(!is_shadowed(
+            &s,
+            &point3::point3(0.0, 10.0, 0.0),
+            &light_point,
+            0.0
+        ));
+        assert!(is_shadowed(
+            &s,
+            &point3::point3(10.0, -10.0, 10.0),
+            &light_point,
+            0.0
+        ));
+        assert!(!is_shadowed(
+            &s,
+            &point3::point3(-20.0, 20.0, -20.0),
+            &light_point,
+            0.0
+        ));
+        assert!(!is_shadowed(
+            &s,
+            &point3::point3(-2.0, 2.0, -2.0),
+            &light_point,
+            0.0
+        ));
+    }
+
+    #[test]
+    fn should_measure_partial_visibility_of_area_light() {
+        let text = format!("{}- add: sphere\n", CAMERA);
+        let s = parse(&text).unwrap();
+        let mut rng = random::random(5);
+        // A wide light above the unit sphere: points straight below the
+        // sphere see only the light's edges, points far to the side see all
+        // of it.
+        let l = light::rectangle_light(
+            &point3::point3(-4.0, 5.0, -4.0),
+            &vector3::vector3(8.0, 0.0, 0.0),
+            &vector3::vector3(0.0, 0.0, 8.0),
+            &color::color(1.0, 1.0, 1.0),
+            64,
+        );
+        let under = visibility(&s, &l, &point3::point3(0.0, -2.0, 0.0), 0.0, &mut rng);
+        let beside = visibility(&s, &l, &point3::point3(10.0, -2.0, 0.0), 0.0, &mut rng);
+        assert!(under > 0.0 && under < 1.0);
+        assert!(float::equals(beside, 1.0));
+
+        let p = light::point_light(&point3::point3(0.0, 5.0, 0.0), &color::color(1.0, 1.0, 1.0));
+        assert!(float::equals(
+            visibility(&s, &p, &point3::point3(0.0, -2.0, 0.0), 0.0, &mut rng),
+            0.0
+        ));
+    }
+
+    #[test]
+    fn should_reject_lights_that_cannot_shine() {
+        for light in [
+            "type: directional\n  direction: [ 0, 0, 0 ]",
+            "type: spot\n  direction: [ 0, 0, 0 ]\n  at: [ 0, 0, 0 ]\n  inner-angle: 0.1\n  outer-angle: 0.2",
+        ] {
+            let text = format!("{}- add: light\n  {}\n  intensity: [ 1, 1, 1 ]\n", CAMERA, light);
+            let e = parse(&text).err().unwrap();
+            assert!(e.to_string() == "line 10: direction must not be zero");
+        }
+        for terms in ["[ 0, 0, 0 ]", "[ 1, -0.5, 0 ]"] {
+            let text = format!(
+                "{}- add: light\n  at: [ 0, 0, 0 ]\n  intensity: [ 1, 1, 1 ]\n  attenuation: {}\n",
+                CAMERA, terms
+            );
+            let e = parse(&text).err().unwrap();
+            assert!(e.to_string() == "line 11: attenuation must be non-negative and not all zero");
+        }
+    }
+
+    #[test]
+    fn should_reject_camera_looking_at_itself() {
+        let text = CAMERA.replace("to: [ 0, 0, 0 ]", "to: [ 0, 0, -5 ]");
+        let e = parse(&text).err().unwrap();
+        assert!(e.line == 1);
+        assert!(e.message == "camera has no direction to look in");
+    }
+
+    #[test]
+    fn should_reject_camera_looking_along_up() {
+        // Rounding leaves the view a sliver of width, which is still none.
+        let text = CAMERA.replace("up: [ 0, 1, 0 ]", "up: [ 0.1, 0.2, 0.3 ]");
+        let text = text.replace("to: [ 0, 0, 0 ]", "to: [ 1, 2, -2 ]");
+        let e = parse(&text).err().unwrap();
+        assert!(e.message == "camera has no direction to look in");
+    }
+
+    #[test]
+    fn should_load_rotations() {
+        let text = format!(
+            "{}- add: sphere\n  transform:\n    - [ rotate, 0, 2, 0, 0.5 ]\n\
+             - add: sphere\n  transform:\n    - [ quaternion, 2, 0, 0, 0 ]\n\
+             - add: sphere\n  transform:\n    - [ rotate-zyx, 0.1, 0.2, 0.3 ]\n\
+             - add: sphere\n  transform:\n    - [ look-along, 0, 0, -1, 0, 1, 0 ]\n",
+            CAMERA
+        );
+        let s = parse(&text).unwrap();
+        let euler = transform::rotation_euler(0.1, 0.2, 0.3, transform::EulerOrder::Zyx);
+        let expected = [
+            transform::rotation_y(0.5),
+            matrix4::IDENTITY,
+            euler,
+            transform::rotation_y(float::consts::PI),
+        ];
+        for (shape, m) in s.shapes.iter().zip(expected.iter()) {
+            assert!(matrix::equals(&shape::transform(shape), m));
+        }
+
+        let text = format!(
+            "{}- add: sphere\n  transform:\n    - [ rotate, 0, 0, 0, 1 ]\n",
+            CAMERA
+        );
+        let e = parse(&text).err().unwrap();
+        assert!(e.line == 10);
+        assert!(e.message == "rotation axis is zero");
+    }
+}
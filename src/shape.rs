@@ -1,31 +1,580 @@
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::material;
+use crate::matrix;
+use crate::matrix4;
+use crate::normal3;
+use crate::point3;
+use crate::random;
+use crate::transform;
+use crate::vector3;
 use std::sync::atomic::AtomicI32;
 use std::sync::atomic::Ordering;
-static shapeid: AtomicI32 = AtomicI32::new(1);
+static SHAPEID: AtomicI32 = AtomicI32::new(1);
 
+// Shapes are small enough to copy around, so the triangle's extra corner
+// data is kept inline rather than boxed.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Copy, Debug)]
 pub enum Shape {
-    Sphere { id: i32 },
+    Sphere {
+        id: i32,
+        transform: matrix4::Matrix4,
+        // Kept with the transform, as it is needed for every ray; None if the
+        // transform has no inverse.
+        inverse: Option<matrix4::Matrix4>,
+        // From transform at time 0 to another at time 1, for shapes in
+        // motion; see transform_at.
+        motion: Option<transform::Motion>,
+        material: material::Material,
+    },
+    // Corners in object space. The face is in front where they run counter
+    // clockwise. Smooth triangles blend a normal per corner, and textured
+    // ones a pair of texture coordinates.
+    Triangle {
+        id: i32,
+        transform: matrix4::Matrix4,
+        inverse: Option<matrix4::Matrix4>,
+        motion: Option<transform::Motion>,
+        material: material::Material,
+        p1: point3::Point3,
+        p2: point3::Point3,
+        p3: point3::Point3,
+        normals: Option<[normal3::Normal3; 3]>,
+        uvs: Option<[(Float, Float); 3]>,
+    },
 }
 
 pub fn sphere() -> Shape {
     return Shape::Sphere {
-        id: (shapeid.fetch_add(1, Ordering::Relaxed)),
+        id: (SHAPEID.fetch_add(1, Ordering::Relaxed)),
+        transform: matrix4::IDENTITY,
+        inverse: Some(matrix4::IDENTITY),
+        motion: None,
+        material: material::material(),
     };
 }
 
+pub fn triangle(p1: &point3::Point3, p2: &point3::Point3, p3: &point3::Point3) -> Shape {
+    return Shape::Triangle {
+        id: (SHAPEID.fetch_add(1, Ordering::Relaxed)),
+        transform: matrix4::IDENTITY,
+        inverse: Some(matrix4::IDENTITY),
+        motion: None,
+        material: material::material(),
+        p1: *p1,
+        p2: *p2,
+        p3: *p3,
+        normals: None,
+        uvs: None,
+    };
+}
+
+pub fn smooth_triangle(
+    p1: &point3::Point3,
+    p2: &point3::Point3,
+    p3: &point3::Point3,
+    n1: &normal3::Normal3,
+    n2: &normal3::Normal3,
+    n3: &normal3::Normal3,
+) -> Shape {
+    let mut s = triangle(p1, p2, p3);
+    if let Shape::Triangle { normals, .. } = &mut s {
+        *normals = Some([*n1, *n2, *n3]);
+    }
+    return s;
+}
+
+pub fn set_uvs(shape: &mut Shape, corner_uvs: &[(Float, Float); 3]) {
+    if let Shape::Triangle { uvs, .. } = shape {
+        *uvs = Some(*corner_uvs);
+    }
+}
+
+// Texture coordinates the shape carries itself at object_point, blended
+// from its corners. Shapes without them are textured through a mapping.
+pub fn uv_at(shape: &Shape, object_point: &point3::Point3) -> Option<(Float, Float)> {
+    match shape {
+        Shape::Triangle {
+            p1,
+            p2,
+            p3,
+            uvs: Some([t1, t2, t3]),
+            ..
+        } => {
+            let (u, v) = barycentric(p1, p2, p3, object_point);
+            let w = 1.0 - u - v;
+            return Some((
+                t1.0 * w + t2.0 * u + t3.0 * v,
+                t1.1 * w + t2.1 * u + t3.1 * v,
+            ));
+        }
+        _ => return None,
+    }
+}
+
+// How the object space point moves per unit of u and v across a triangle
+// with texture coordinates, for building a tangent frame. None for other
+// shapes, or where the coordinates do not span the face.
+pub fn uv_tangents(shape: &Shape) -> Option<(vector3::Vector3, vector3::Vector3)> {
+    match shape {
+        Shape::Triangle {
+            p1,
+            p2,
+            p3,
+            uvs: Some([t1, t2, t3]),
+            ..
+        } => {
+            let (e1, e2) = (*p2 - *p1, *p3 - *p1);
+            let (du1, dv1) = (t2.0 - t1.0, t2.1 - t1.1);
+            let (du2, dv2) = (t3.0 - t1.0, t3.1 - t1.1);
+            let det = du1 * dv2 - du2 * dv1;
+            if det == 0.0 {
+                return None;
+            }
+            return Some(((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det));
+        }
+        _ => return None,
+    }
+}
+
+fn id(shape: &Shape) -> i32 {
+    match shape {
+        Shape::Sphere { id, .. } | Shape::Triangle { id, .. } => return *id,
+    }
+}
+
 pub fn equals(a: &Shape, b: &Shape) -> bool {
-    match (a, b) {
-        (Shape::Sphere { id }, Shape::Sphere { .. }) => {
-            let aid = id;
-            match (b) {
-                Shape::Sphere { id } => return aid == id,
+    return id(a) == id(b);
+}
+
+pub fn transform(shape: &Shape) -> matrix4::Matrix4 {
+    match shape {
+        Shape::Sphere { transform, .. } | Shape::Triangle { transform, .. } => return *transform,
+    }
+}
+
+// Shapes squashed flat by a scale of 0 have no inverse. They are never hit,
+// and path tracing leaves them out as emitters, so everything that works
+// from a hit or a sampled emitter can rely on the inverse being there.
+pub fn is_invertible(shape: &Shape) -> bool {
+    return inverse(shape).is_some();
+}
+
+// A triangle is a bare surface with no inside, so it emits from both faces
+// whichever way its corners wind. A sphere only emits outwards.
+pub fn is_two_sided(shape: &Shape) -> bool {
+    match shape {
+        Shape::Sphere { .. } => return false,
+        Shape::Triangle { .. } => return true,
+    }
+}
+
+pub fn inverse(shape: &Shape) -> Option<matrix4::Matrix4> {
+    match shape {
+        Shape::Sphere { inverse, .. } | Shape::Triangle { inverse, .. } => return *inverse,
+    }
+}
+
+pub fn inverse_transform(shape: &Shape) -> matrix4::Matrix4 {
+    return inverse(shape).expect("shape transform is not invertible");
+}
+
+// A shape in motion keeps its end transform and starts from m.
+pub fn set_transform(shape: &mut Shape, m: &matrix4::Matrix4) {
+    match shape {
+        Shape::Sphere {
+            transform,
+            inverse,
+            motion,
+            ..
+        }
+        | Shape::Triangle {
+            transform,
+            inverse,
+            motion,
+            ..
+        } => {
+            *transform = *m;
+            *inverse = matrix4::inverse(m);
+            if let Some(moving) = motion {
+                *moving = transform::motion(m, &moving.end);
             }
         }
-        _ => false,
     }
 }
 
+pub fn set_motion(shape: &mut Shape, end: &matrix4::Matrix4) {
+    match shape {
+        Shape::Sphere {
+            transform, motion, ..
+        }
+        | Shape::Triangle {
+            transform, motion, ..
+        } => *motion = Some(transform::motion(transform, end)),
+    }
+}
+
+// The transform at time, moving from transform (at 0) to the end transform
+// (at 1) for shapes in motion.
+pub fn transform_at(shape: &Shape, time: Float) -> matrix4::Matrix4 {
+    match shape {
+        Shape::Sphere {
+            motion: Some(m), ..
+        }
+        | Shape::Triangle {
+            motion: Some(m), ..
+        } => return transform::motion_at(m, time),
+        _ => return transform(shape),
+    }
+}
+
+// The shape standing still where it is at time.
+pub fn at_time(shape: &Shape, time: Float) -> Shape {
+    let mut s = *shape;
+    match &mut s {
+        Shape::Sphere { motion, .. } | Shape::Triangle { motion, .. } => {
+            if motion.is_none() {
+                return s;
+            }
+            *motion = None;
+        }
+    }
+    set_transform(&mut s, &transform_at(shape, time));
+    return s;
+}
+
+pub fn material(shape: &Shape) -> material::Material {
+    match shape {
+        Shape::Sphere { material, .. } | Shape::Triangle { material, .. } => return *material,
+    }
+}
+
+pub fn set_material(shape: &mut Shape, m: &material::Material) {
+    match shape {
+        Shape::Sphere { material, .. } | Shape::Triangle { material, .. } => *material = *m,
+    }
+}
+
+pub fn normal_at(shape: &Shape, world_point: &point3::Point3) -> normal3::Normal3 {
+    let inv = inverse_transform(shape);
+    let object_point = matrix4::multiply_point(&inv, world_point);
+    return normal_to_world(shape, &local_normal_at(shape, &object_point));
+}
+
+// The unnormalized normal at a point in object space.
+pub fn local_normal_at(shape: &Shape, object_point: &point3::Point3) -> normal3::Normal3 {
+    match shape {
+        Shape::Sphere { .. } => {
+            return normal3::normal3(object_point.x, object_point.y, object_point.z);
+        }
+        Shape::Triangle {
+            p1,
+            p2,
+            p3,
+            normals: Some([n1, n2, n3]),
+            ..
+        } => {
+            let (u, v) = barycentric(p1, p2, p3, object_point);
+            let [n1, n2, n3] = [n1, n2, n3].map(normal3::to_vector);
+            return normal3::from_vector(&(n2 * u + n3 * v + n1 * (1.0 - u - v)));
+        }
+        Shape::Triangle { p1, p2, p3, .. } => return face_normal(p1, p2, p3),
+    }
+}
+
+pub fn normal_to_world(shape: &Shape, object_normal: &normal3::Normal3) -> normal3::Normal3 {
+    let inv = inverse_transform(shape);
+    return normal3::normalize(&matrix4::multiply_normal(&inv, object_normal));
+}
+
+// Facing the side the corners run counter clockwise on, and as long as
+// twice the triangle's area.
+fn face_normal(p1: &point3::Point3, p2: &point3::Point3, p3: &point3::Point3) -> normal3::Normal3 {
+    return normal3::from_vector(&vector3::cross(&(*p3 - *p1), &(*p2 - *p1)));
+}
+
+// How far point lies towards p2 and towards p3, so that it is
+// p1 + u (p2 - p1) + v (p3 - p1). Points off the plane are projected onto it.
+pub fn barycentric(
+    p1: &point3::Point3,
+    p2: &point3::Point3,
+    p3: &point3::Point3,
+    point: &point3::Point3,
+) -> (Float, Float) {
+    let (e1, e2, d) = (*p2 - *p1, *p3 - *p1, *point - *p1);
+    let (d11, d12, d22) = (
+        vector3::dot(&e1, &e1),
+        vector3::dot(&e1, &e2),
+        vector3::dot(&e2, &e2),
+    );
+    let (d1, d2) = (vector3::dot(&d, &e1), vector3::dot(&d, &e2));
+    let denom = d11 * d22 - d12 * d12;
+    return ((d22 * d1 - d12 * d2) / denom, (d11 * d2 - d12 * d1) / denom);
+}
+
+// A random point on the surface, uniform in object space, and its normal.
+// Use surface_pdf for the density, which a non-uniform scale makes uneven.
+pub fn sample_surface(
+    shape: &Shape,
+    rng: &mut random::Random,
+) -> (point3::Point3, normal3::Normal3) {
+    let object_point = match shape {
+        Shape::Sphere { .. } => {
+            let z = 1.0 - 2.0 * random::next_float(rng);
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * PI * random::next_float(rng);
+            point3::point3(r * phi.cos(), r * phi.sin(), z)
+        }
+        Shape::Triangle { p1, p2, p3, .. } => {
+            // Folding the unit square onto the triangle would bunch samples
+            // up at p1; the square root spreads them evenly.
+            let r = random::next_float(rng).sqrt();
+            let v = random::next_float(rng);
+            *p1 + (*p2 - *p1) * (r * (1.0 - v)) + (*p3 - *p1) * (r * v)
+        }
+    };
+    let world_point = matrix4::multiply_point(&transform(shape), &object_point);
+    return (world_point, normal_at(shape, &world_point));
+}
+
+// Density of sample_surface per unit of world space area at world_point.
+pub fn surface_pdf(shape: &Shape, world_point: &point3::Point3) -> Float {
+    let m = transform(shape);
+    let inv = inverse_transform(shape);
+    let object_point = matrix4::multiply_point(&inv, world_point);
+    let (object_normal, object_area) = match shape {
+        Shape::Sphere { .. } => (local_normal_at(shape, &object_point), 4.0 * PI),
+        Shape::Triangle { p1, p2, p3, .. } => {
+            let n = face_normal(p1, p2, p3);
+            let length = vector3::magnitude(&normal3::to_vector(&n));
+            (normal3::normalize(&n), length / 2.0)
+        }
+    };
+    // An object space patch dA maps to |det| * |M^-T n| dA in world space.
+    let scaled = normal3::to_vector(&matrix4::multiply_normal(&inv, &object_normal));
+    let stretch = matrix::determinant(&m).abs() * vector3::magnitude(&scaled);
+    return 1.0 / (object_area * stretch);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::color;
+    use crate::float;
+    use crate::transform;
+
+    // Page 69
+    #[test]
+    fn should_have_default_transformation() {
+        let s = sphere();
+        assert!(matrix::equals(&transform(&s), &matrix4::IDENTITY));
+    }
+
+    // Page 69
+    #[test]
+    fn should_change_transformation() {
+        let mut s = sphere();
+        let t = transform::translation(2.0, 3.0, 4.0);
+        set_transform(&mut s, &t);
+        assert!(matrix::equals(&transform(&s), &t));
+    }
+
+    // Page 85
+    #[test]
+    fn should_have_default_material() {
+        let s = sphere();
+        let m = material(&s);
+        assert!(color::equals(&m.color, &color::color(1.0, 1.0, 1.0)));
+        assert!(float::equals(m.ambient, 0.1));
+    }
+
+    // Page 85
+    #[test]
+    fn should_assign_material() {
+        let mut s = sphere();
+        let mut m = material::material();
+        m.ambient = 1.0;
+        set_material(&mut s, &m);
+        assert!(float::equals(material(&s).ambient, 1.0));
+    }
+
+    // Page 78
+    #[test]
+    fn should_compute_normal_on_sphere() {
+        let s = sphere();
+        let third = Float::sqrt(3.0) / 3.0;
+        assert!(normal3::equals(
+            &normal_at(&s, &point3::point3(1.0, 0.0, 0.0)),
+            &normal3::normal3(1.0, 0.0, 0.0)
+        ));
+        assert!(normal3::equals(
+            &normal_at(&s, &point3::point3(0.0, 0.0, 1.0)),
+            &normal3::normal3(0.0, 0.0, 1.0)
+        ));
+        let n = normal_at(&s, &point3::point3(third, third, third));
+        assert!(normal3::equals(&n, &normal3::normal3(third, third, third)));
+        assert!(normal3::equals(&n, &normal3::normalize(&n)));
+    }
+
+    // Page 80
+    #[test]
+    fn should_compute_normal_on_translated_sphere() {
+        let mut s = sphere();
+        set_transform(&mut s, &transform::translation(0.0, 1.0, 0.0));
+        let half = Float::sqrt(2.0) / 2.0;
+        let n = normal_at(&s, &point3::point3(0.0, 1.0 + half, -half));
+        assert!(normal3::equals(&n, &normal3::normal3(0.0, half, -half)));
+    }
+
+    // Page 80
+    #[test]
+    fn should_compute_normal_on_transformed_sphere() {
+        let mut s = sphere();
+        let m = matrix::multiply(
+            &transform::scaling(1.0, 0.5, 1.0),
+            &transform::rotation_z(float::consts::PI / 5.0),
+        );
+        set_transform(&mut s, &m);
+        let half = Float::sqrt(2.0) / 2.0;
+        let n = normal_at(&s, &point3::point3(0.0, half, -half));
+        assert!(normal3::equals(
+            &n,
+            &normal3::normal3(0.0, 0.97014, -0.24254)
+        ));
+    }
+
+    #[test]
+    fn should_sample_points_on_surface() {
+        let mut s = sphere();
+        let m = matrix::multiply(
+            &transform::translation(1.0, 2.0, 3.0),
+            &transform::scaling(2.0, 2.0, 2.0),
+        );
+        set_transform(&mut s, &m);
+        let mut rng = random::random(4);
+        for _ in 0..20 {
+            let (p, n) = sample_surface(&s, &mut rng);
+            let offset = p - point3::point3(1.0, 2.0, 3.0);
+            assert!(float::equals(vector3::magnitude(&offset), 2.0));
+            let outward = normal3::from_vector(&vector3::normalize(&offset));
+            assert!(normal3::equals(&n, &outward));
+            assert!(float::equals(surface_pdf(&s, &p), 1.0 / (16.0 * PI)));
+        }
+    }
+
+    #[test]
+    fn should_integrate_surface_pdf_to_one() {
+        // Sampling uniformly in object space, the expected value of
+        // 1 / pdf is the world space area; an ellipsoid's is known.
+        let mut s = sphere();
+        set_transform(&mut s, &transform::scaling(1.0, 1.0, 3.0));
+        let mut rng = random::random(8);
+        let mut area = 0.0;
+        for _ in 0..20000 {
+            let (p, _) = sample_surface(&s, &mut rng);
+            area += 1.0 / surface_pdf(&s, &p) / 20000.0;
+        }
+        // Prolate spheroid with a = 1, c = 3.
+        let e = Float::sqrt(1.0 - 1.0 / 9.0);
+        let exact = 2.0 * PI * (1.0 + 3.0 * e.asin() / e);
+        assert!((area - exact).abs() / exact < 0.02);
+    }
+
+    #[test]
+    fn should_compute_triangle_normal() {
+        let t = triangle(
+            &point3::point3(0.0, 1.0, 0.0),
+            &point3::point3(-1.0, 0.0, 0.0),
+            &point3::point3(1.0, 0.0, 0.0),
+        );
+        for p in [
+            point3::point3(0.0, 0.5, 0.0),
+            point3::point3(-0.5, 0.75, 0.0),
+            point3::point3(0.5, 0.25, 0.0),
+        ] {
+            assert!(normal3::equals(
+                &normal_at(&t, &p),
+                &normal3::normal3(0.0, 0.0, -1.0)
+            ));
+        }
+    }
+
+    #[test]
+    fn should_blend_smooth_triangle_normals() {
+        let t = smooth_triangle(
+            &point3::point3(0.0, 1.0, 0.0),
+            &point3::point3(-1.0, 0.0, 0.0),
+            &point3::point3(1.0, 0.0, 0.0),
+            &normal3::normal3(0.0, 1.0, 0.0),
+            &normal3::normal3(-1.0, 0.0, 0.0),
+            &normal3::normal3(1.0, 0.0, 0.0),
+        );
+        // u = 0.45 towards p2 and v = 0.25 towards p3.
+        let n = normal_at(&t, &point3::point3(-0.2, 0.3, 0.0));
+        assert!(normal3::equals(
+            &n,
+            &normal3::normal3(-0.5547, 0.83205, 0.0)
+        ));
+    }
+
+    #[test]
+    fn should_sample_points_on_triangle() {
+        let mut t = triangle(
+            &point3::point3(0.0, 0.0, 0.0),
+            &point3::point3(0.0, 1.0, 0.0),
+            &point3::point3(1.0, 0.0, 0.0),
+        );
+        set_transform(&mut t, &transform::scaling(2.0, 3.0, 1.0));
+        let mut rng = random::random(5);
+        let mut sum = (0.0, 0.0);
+        for _ in 0..4000 {
+            let (p, n) = sample_surface(&t, &mut rng);
+            assert!(p.x >= 0.0 && p.y >= 0.0 && p.x / 2.0 + p.y / 3.0 <= 1.0);
+            assert!(normal3::equals(&n, &normal3::normal3(0.0, 0.0, 1.0)));
+            assert!(float::equals(surface_pdf(&t, &p), 1.0 / 3.0));
+            sum = (sum.0 + p.x / 4000.0, sum.1 + p.y / 4000.0);
+        }
+        // Uniform points average out at the centroid.
+        assert!((sum.0 - 2.0 / 3.0).abs() < 0.02);
+        assert!((sum.1 - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn should_keep_inverse_with_transform() {
+        let mut s = sphere();
+        let m = transform::translation(1.0, 2.0, 3.0).scale(2.0, 2.0, 2.0);
+        set_transform(&mut s, &m);
+        assert!(matrix::equals(
+            &inverse_transform(&s),
+            &matrix4::inverse(&m).unwrap()
+        ));
+        set_motion(&mut s, &transform::translation(4.0, 0.0, 0.0));
+        let moved = at_time(&s, 1.0);
+        assert!(matrix::equals(
+            &inverse_transform(&moved),
+            &transform::translation(-4.0, 0.0, 0.0)
+        ));
+        set_transform(&mut s, &transform::scaling(0.0, 1.0, 1.0));
+        assert!(!is_invertible(&s));
+    }
+
+    #[test]
+    fn should_move_between_transforms() {
+        let mut s = sphere();
+        set_transform(&mut s, &transform::translation(0.0, 1.0, 0.0));
+        assert!(matrix::equals(&transform_at(&s, 0.5), &transform(&s)));
+        set_motion(&mut s, &transform::translation(4.0, 1.0, 0.0));
+        let halfway = transform::translation(2.0, 1.0, 0.0);
+        assert!(matrix::equals(&transform_at(&s, 0.5), &halfway));
+        let frozen = at_time(&s, 0.5);
+        assert!(equals(&frozen, &s));
+        assert!(matrix::equals(&transform_at(&frozen, 1.0), &halfway));
+        let n = normal_at(&frozen, &point3::point3(3.0, 1.0, 0.0));
+        assert!(normal3::equals(&n, &normal3::normal3(1.0, 0.0, 0.0)));
+        // A new start keeps the end.
+        set_transform(&mut s, &transform::translation(0.0, 3.0, 0.0));
+        let halfway = transform::translation(2.0, 2.0, 0.0);
+        assert!(matrix::equals(&transform_at(&s, 0.5), &halfway));
+    }
 }
@@ -0,0 +1,405 @@
+// SIMD versions of the tuple and matrix arithmetic every ray goes through.
+// On x86_64, SSE2 is always there and works two lanes at a time, so tuple
+// and matrix4 use it in every f64 build. AVX does all four at once, but is
+// only used when it is on for the whole build, with -C target-cpu=native
+// say. Choosing it at run time would mean a call through a pointer for
+// every tuple, which costs more than the arithmetic it saves. Other targets
+// and f32 builds use the scalar code, which is also what the vector code is
+// tested against.
+//
+// There is no BVH yet, so there is no code for testing packets of rays
+// against bounding boxes either.
+//
+// Lanes hold x, y, z and w in that order, so Tuple is repr(C).
+
+use crate::float::Float;
+use crate::matrix4;
+use crate::tuple;
+
+// Whether this build has vector code for tuple and matrix4 to call.
+pub const INLINE: bool = cfg!(all(target_arch = "x86_64", not(feature = "f32")));
+
+pub fn add(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+    return unsafe { x86::add_avx(a, b) };
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx"),
+        not(feature = "f32")
+    ))]
+    return unsafe { x86::add_sse2(a, b) };
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    return scalar::add(a, b);
+}
+
+pub fn subtract(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+    return unsafe { x86::subtract_avx(a, b) };
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx"),
+        not(feature = "f32")
+    ))]
+    return unsafe { x86::subtract_sse2(a, b) };
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    return scalar::subtract(a, b);
+}
+
+pub fn multiply(t: &tuple::Tuple, scalar: Float) -> tuple::Tuple {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+    return unsafe { x86::multiply_avx(t, scalar) };
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx"),
+        not(feature = "f32")
+    ))]
+    return unsafe { x86::multiply_sse2(t, scalar) };
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    return scalar::multiply(t, scalar);
+}
+
+pub fn dot(a: &tuple::Tuple, b: &tuple::Tuple) -> Float {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+    return unsafe { x86::dot_avx(a, b) };
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx"),
+        not(feature = "f32")
+    ))]
+    return unsafe { x86::dot_sse2(a, b) };
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    return scalar::dot(a, b);
+}
+
+pub fn multiply_tuple(m: &matrix4::Matrix4, t: &tuple::Tuple) -> tuple::Tuple {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx", not(feature = "f32")))]
+    return unsafe { x86::multiply_tuple_avx(m, t) };
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(target_feature = "avx"),
+        not(feature = "f32")
+    ))]
+    return unsafe { x86::multiply_tuple_sse2(m, t) };
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    return scalar::multiply_tuple(m, t);
+}
+
+// Only the tests need this where there is vector code.
+#[cfg(any(test, not(all(target_arch = "x86_64", not(feature = "f32")))))]
+mod scalar {
+    use crate::float::Float;
+    use crate::matrix4;
+    use crate::tuple;
+
+    pub fn add(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        return tuple::tuple(a.x + b.x, a.y + b.y, a.z + b.z, a.w + b.w);
+    }
+
+    pub fn subtract(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        return tuple::tuple(a.x - b.x, a.y - b.y, a.z - b.z, a.w - b.w);
+    }
+
+    pub fn multiply(t: &tuple::Tuple, scalar: Float) -> tuple::Tuple {
+        return tuple::tuple(t.x * scalar, t.y * scalar, t.z * scalar, t.w * scalar);
+    }
+
+    pub fn dot(a: &tuple::Tuple, b: &tuple::Tuple) -> Float {
+        return a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+    }
+
+    // Not matrix4::multiply_tuple, which may itself be vectorized.
+    pub fn multiply_tuple(a: &matrix4::Matrix4, b: &tuple::Tuple) -> tuple::Tuple {
+        let x = a[0][0] * b.x + a[0][1] * b.y + a[0][2] * b.z + a[0][3] * b.w;
+        let y = a[1][0] * b.x + a[1][1] * b.y + a[1][2] * b.z + a[1][3] * b.w;
+        let z = a[2][0] * b.x + a[2][1] * b.y + a[2][2] * b.z + a[2][3] * b.w;
+        let w = a[3][0] * b.x + a[3][1] * b.y + a[3][2] * b.z + a[3][3] * b.w;
+        return tuple::tuple(x, y, z, w);
+    }
+}
+
+// Only called where the build or, in tests, the CPU has been checked for the
+// feature, which is what makes the target_feature functions safe to call.
+// Loads and stores are unaligned. Builds only keep the AVX or the SSE2
+// versions, whichever they use; the tests need both.
+#[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use crate::matrix4;
+    use crate::tuple;
+
+    fn lanes(t: &tuple::Tuple) -> *const f64 {
+        return t as *const tuple::Tuple as *const f64;
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    #[target_feature(enable = "avx")]
+    unsafe fn store_avx(v: __m256d) -> tuple::Tuple {
+        let mut out = [0.0; 4];
+        _mm256_storeu_pd(out.as_mut_ptr(), v);
+        return tuple::tuple(out[0], out[1], out[2], out[3]);
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    fn store_sse2(lo: __m128d, hi: __m128d) -> tuple::Tuple {
+        let mut out = [0.0; 4];
+        unsafe {
+            _mm_storeu_pd(out.as_mut_ptr(), lo);
+            _mm_storeu_pd(out.as_mut_ptr().add(2), hi);
+        }
+        return tuple::tuple(out[0], out[1], out[2], out[3]);
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn add_avx(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        return store_avx(_mm256_add_pd(
+            _mm256_loadu_pd(lanes(a)),
+            _mm256_loadu_pd(lanes(b)),
+        ));
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn subtract_avx(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        return store_avx(_mm256_sub_pd(
+            _mm256_loadu_pd(lanes(a)),
+            _mm256_loadu_pd(lanes(b)),
+        ));
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn multiply_avx(t: &tuple::Tuple, scalar: f64) -> tuple::Tuple {
+        return store_avx(_mm256_mul_pd(
+            _mm256_loadu_pd(lanes(t)),
+            _mm256_set1_pd(scalar),
+        ));
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn dot_avx(a: &tuple::Tuple, b: &tuple::Tuple) -> f64 {
+        let p = _mm256_mul_pd(_mm256_loadu_pd(lanes(a)), _mm256_loadu_pd(lanes(b)));
+        let s = _mm_add_pd(_mm256_castpd256_pd128(p), _mm256_extractf128_pd(p, 1));
+        return _mm_cvtsd_f64(_mm_add_sd(s, _mm_unpackhi_pd(s, s)));
+    }
+
+    #[cfg(any(test, target_feature = "avx"))]
+    // Multiplies every row by t, then adds each product across in pairs
+    // and lines the four sums up in order.
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn multiply_tuple_avx(
+        m: &matrix4::Matrix4,
+        t: &tuple::Tuple,
+    ) -> tuple::Tuple {
+        let v = _mm256_loadu_pd(lanes(t));
+        let r0 = _mm256_mul_pd(_mm256_loadu_pd(m[0].as_ptr()), v);
+        let r1 = _mm256_mul_pd(_mm256_loadu_pd(m[1].as_ptr()), v);
+        let r2 = _mm256_mul_pd(_mm256_loadu_pd(m[2].as_ptr()), v);
+        let r3 = _mm256_mul_pd(_mm256_loadu_pd(m[3].as_ptr()), v);
+        // [0 low, 1 low, 0 high, 1 high] and the same for rows 2 and 3.
+        let h01 = _mm256_hadd_pd(r0, r1);
+        let h23 = _mm256_hadd_pd(r2, r3);
+        let crossed = _mm256_permute2f128_pd(h01, h23, 0x21);
+        let kept = _mm256_blend_pd(h01, h23, 0b1100);
+        return store_avx(_mm256_add_pd(crossed, kept));
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn add_sse2(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        let (a, b) = (lanes(a), lanes(b));
+        let lo = _mm_add_pd(_mm_loadu_pd(a), _mm_loadu_pd(b));
+        let hi = _mm_add_pd(_mm_loadu_pd(a.add(2)), _mm_loadu_pd(b.add(2)));
+        return store_sse2(lo, hi);
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn subtract_sse2(a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        let (a, b) = (lanes(a), lanes(b));
+        let lo = _mm_sub_pd(_mm_loadu_pd(a), _mm_loadu_pd(b));
+        let hi = _mm_sub_pd(_mm_loadu_pd(a.add(2)), _mm_loadu_pd(b.add(2)));
+        return store_sse2(lo, hi);
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn multiply_sse2(t: &tuple::Tuple, scalar: f64) -> tuple::Tuple {
+        let (t, s) = (lanes(t), _mm_set1_pd(scalar));
+        let lo = _mm_mul_pd(_mm_loadu_pd(t), s);
+        let hi = _mm_mul_pd(_mm_loadu_pd(t.add(2)), s);
+        return store_sse2(lo, hi);
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    #[target_feature(enable = "sse2")]
+    unsafe fn dot_sse2_raw(a: *const f64, b: *const f64) -> f64 {
+        let lo = _mm_mul_pd(_mm_loadu_pd(a), _mm_loadu_pd(b));
+        let hi = _mm_mul_pd(_mm_loadu_pd(a.add(2)), _mm_loadu_pd(b.add(2)));
+        let s = _mm_add_pd(lo, hi);
+        return _mm_cvtsd_f64(_mm_add_sd(s, _mm_unpackhi_pd(s, s)));
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn dot_sse2(a: &tuple::Tuple, b: &tuple::Tuple) -> f64 {
+        return dot_sse2_raw(lanes(a), lanes(b));
+    }
+
+    #[cfg(any(test, not(target_feature = "avx")))]
+    // Without a horizontal add, each row is a dot product of its own.
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn multiply_tuple_sse2(
+        m: &matrix4::Matrix4,
+        t: &tuple::Tuple,
+    ) -> tuple::Tuple {
+        let v = lanes(t);
+        return tuple::tuple(
+            dot_sse2_raw(m[0].as_ptr(), v),
+            dot_sse2_raw(m[1].as_ptr(), v),
+            dot_sse2_raw(m[2].as_ptr(), v),
+            dot_sse2_raw(m[3].as_ptr(), v),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+    use crate::matrix;
+    use crate::random;
+
+    #[derive(Clone, Copy)]
+    enum Level {
+        Scalar,
+        #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+        Sse2,
+        #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+        Avx,
+    }
+
+    // Every version this CPU can run, whichever one the build uses.
+    #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+    fn levels() -> Vec<Level> {
+        if is_x86_feature_detected!("avx") {
+            return vec![Level::Scalar, Level::Sse2, Level::Avx];
+        }
+        return vec![Level::Scalar, Level::Sse2];
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "f32"))))]
+    fn levels() -> Vec<Level> {
+        return vec![Level::Scalar];
+    }
+
+    fn add_at(l: Level, a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        match l {
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Avx => unsafe { return x86::add_avx(a, b) },
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Sse2 => unsafe { return x86::add_sse2(a, b) },
+            _ => return scalar::add(a, b),
+        }
+    }
+
+    fn subtract_at(l: Level, a: &tuple::Tuple, b: &tuple::Tuple) -> tuple::Tuple {
+        match l {
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Avx => unsafe { return x86::subtract_avx(a, b) },
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Sse2 => unsafe { return x86::subtract_sse2(a, b) },
+            _ => return scalar::subtract(a, b),
+        }
+    }
+
+    fn multiply_at(l: Level, t: &tuple::Tuple, scalar: Float) -> tuple::Tuple {
+        match l {
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Avx => unsafe { return x86::multiply_avx(t, scalar) },
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Sse2 => unsafe { return x86::multiply_sse2(t, scalar) },
+            _ => return scalar::multiply(t, scalar),
+        }
+    }
+
+    fn dot_at(l: Level, a: &tuple::Tuple, b: &tuple::Tuple) -> Float {
+        match l {
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Avx => unsafe { return x86::dot_avx(a, b) },
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Sse2 => unsafe { return x86::dot_sse2(a, b) },
+            _ => return scalar::dot(a, b),
+        }
+    }
+
+    fn multiply_tuple_at(l: Level, m: &matrix4::Matrix4, t: &tuple::Tuple) -> tuple::Tuple {
+        match l {
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Avx => unsafe { return x86::multiply_tuple_avx(m, t) },
+            #[cfg(all(target_arch = "x86_64", not(feature = "f32")))]
+            Level::Sse2 => unsafe { return x86::multiply_tuple_sse2(m, t) },
+            _ => return scalar::multiply_tuple(m, t),
+        }
+    }
+
+    fn random_tuple(rng: &mut random::Random) -> tuple::Tuple {
+        let mut next = || 20.0 * random::next_float(rng) - 10.0;
+        return tuple::tuple(next(), next(), next(), next());
+    }
+
+    #[test]
+    fn should_match_scalar_tuple_math() {
+        let mut rng = random::random(3);
+        for _ in 0..200 {
+            let a = random_tuple(&mut rng);
+            let b = random_tuple(&mut rng);
+            let s = random::next_float(&mut rng) * 4.0 - 2.0;
+            for l in levels() {
+                assert!(tuple::equals(&add_at(l, &a, &b), &scalar::add(&a, &b)));
+                assert!(tuple::equals(
+                    &subtract_at(l, &a, &b),
+                    &scalar::subtract(&a, &b)
+                ));
+                assert!(tuple::equals(
+                    &multiply_at(l, &a, s),
+                    &scalar::multiply(&a, s)
+                ));
+                assert!(float::equals(dot_at(l, &a, &b), scalar::dot(&a, &b)));
+            }
+            assert!(tuple::equals(&add(&a, &b), &scalar::add(&a, &b)));
+            assert!(float::equals(dot(&a, &b), scalar::dot(&a, &b)));
+        }
+    }
+
+    #[test]
+    fn should_match_scalar_matrix_multiply() {
+        let mut rng = random::random(5);
+        for _ in 0..200 {
+            let mut m = matrix4::matrix4();
+            for i in 0..4 {
+                let row = random_tuple(&mut rng);
+                m[i] = [row.x, row.y, row.z, row.w];
+            }
+            let t = random_tuple(&mut rng);
+            let expected = scalar::multiply_tuple(&m, &t);
+            for l in levels() {
+                assert!(tuple::equals(&multiply_tuple_at(l, &m, &t), &expected));
+            }
+        }
+        // The book's example, page 31.
+        let a = matrix::Matrix([
+            [1.0, 2.0, 3.0, 4.0],
+            [2.0, 4.0, 4.0, 2.0],
+            [8.0, 6.0, 4.0, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let b = tuple::tuple(1.0, 2.0, 3.0, 1.0);
+        assert!(tuple::equals(
+            &multiply_tuple(&a, &b),
+            &tuple::tuple(18.0, 24.0, 33.0, 1.0)
+        ));
+    }
+}
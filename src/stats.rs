@@ -0,0 +1,193 @@
+// Render statistics. Counting goes to a per-thread tally so the hot path
+// never touches shared memory; call flush() to fold it into the totals.
+// There is no BVH yet, so there are no node visits to count.
+
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::shape;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub primary_rays: u64,
+    // Mirror reflection and refraction rays from Whitted shading.
+    pub reflection_rays: u64,
+    pub refraction_rays: u64,
+    // Rays continuing a path tracer's path in a direction its BSDF chose.
+    pub scatter_rays: u64,
+    pub shadow_rays: u64,
+    pub sphere_tests: u64,
+    pub triangle_tests: u64,
+}
+
+thread_local! {
+    static LOCAL: Cell<Stats> = Cell::new(Stats::default());
+}
+
+static TOTAL: Mutex<Stats> = Mutex::new(Stats {
+    primary_rays: 0,
+    reflection_rays: 0,
+    refraction_rays: 0,
+    scatter_rays: 0,
+    shadow_rays: 0,
+    sphere_tests: 0,
+    triangle_tests: 0,
+});
+
+pub fn primary_ray() {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        s.primary_rays += 1;
+        l.set(s);
+    });
+}
+
+pub fn reflection_ray() {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        s.reflection_rays += 1;
+        l.set(s);
+    });
+}
+
+pub fn refraction_ray() {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        s.refraction_rays += 1;
+        l.set(s);
+    });
+}
+
+pub fn scatter_ray() {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        s.scatter_rays += 1;
+        l.set(s);
+    });
+}
+
+pub fn shadow_ray() {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        s.shadow_rays += 1;
+        l.set(s);
+    });
+}
+
+pub fn intersection_test(shape: &shape::Shape) {
+    LOCAL.with(|l| {
+        let mut s = l.get();
+        match shape {
+            shape::Shape::Sphere { .. } => s.sphere_tests += 1,
+            shape::Shape::Triangle { .. } => s.triangle_tests += 1,
+        }
+        l.set(s);
+    });
+}
+
+pub fn flush() {
+    let local = LOCAL.with(|l| l.replace(Stats::default()));
+    let mut total = TOTAL.lock().unwrap();
+    total.primary_rays += local.primary_rays;
+    total.reflection_rays += local.reflection_rays;
+    total.refraction_rays += local.refraction_rays;
+    total.scatter_rays += local.scatter_rays;
+    total.shadow_rays += local.shadow_rays;
+    total.sphere_tests += local.sphere_tests;
+    total.triangle_tests += local.triangle_tests;
+}
+
+pub fn total() -> Stats {
+    return *TOTAL.lock().unwrap();
+}
+
+pub fn rays(s: &Stats) -> u64 {
+    return s.primary_rays + s.reflection_rays + s.refraction_rays + s.scatter_rays + s.shadow_rays;
+}
+
+pub fn difference(after: &Stats, before: &Stats) -> Stats {
+    return Stats {
+        primary_rays: after.primary_rays - before.primary_rays,
+        reflection_rays: after.reflection_rays - before.reflection_rays,
+        refraction_rays: after.refraction_rays - before.refraction_rays,
+        scatter_rays: after.scatter_rays - before.scatter_rays,
+        shadow_rays: after.shadow_rays - before.shadow_rays,
+        sphere_tests: after.sphere_tests - before.sphere_tests,
+        triangle_tests: after.triangle_tests - before.triangle_tests,
+    };
+}
+
+pub fn summary(s: &Stats, elapsed: Duration) -> Vec<String> {
+    let seconds = elapsed.as_secs_f64();
+    let mut v = Vec::new();
+    v.push(format!("Render time:        {:.2}s", seconds));
+    v.push(format!("Primary rays:       {}", s.primary_rays));
+    v.push(format!("Reflection rays:    {}", s.reflection_rays));
+    v.push(format!("Refraction rays:    {}", s.refraction_rays));
+    v.push(format!("Scatter rays:       {}", s.scatter_rays));
+    v.push(format!("Shadow rays:        {}", s.shadow_rays));
+    v.push(format!("Sphere tests:       {}", s.sphere_tests));
+    v.push(format!("Triangle tests:     {}", s.triangle_tests));
+    if seconds > 0.0 {
+        v.push(format!(
+            "Rays per second:    {:.0}",
+            rays(s) as f64 / seconds
+        ));
+    }
+    return v;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_count_locally_until_flushed() {
+        // Other tests render in parallel, so only compare this thread's
+        // contribution.
+        flush();
+        let s = shape::sphere();
+        primary_ray();
+        reflection_ray();
+        refraction_ray();
+        scatter_ray();
+        shadow_ray();
+        shadow_ray();
+        intersection_test(&s);
+        intersection_test(&s);
+        let local = LOCAL.with(|l| l.get());
+        assert!(local.primary_rays == 1);
+        assert!(rays(&local) == 6);
+        assert!(local.refraction_rays == 1);
+        assert!(local.sphere_tests == 2);
+        let before = total();
+        flush();
+        let after = total();
+        assert!(after.primary_rays > before.primary_rays);
+        assert!(after.sphere_tests >= before.sphere_tests + 2);
+        assert!(LOCAL.with(|l| l.get()) == Stats::default());
+    }
+
+    #[test]
+    fn should_summarize() {
+        let s = Stats {
+            primary_rays: 2000,
+            reflection_rays: 500,
+            refraction_rays: 300,
+            scatter_rays: 200,
+            shadow_rays: 1000,
+            sphere_tests: 6000,
+            triangle_tests: 500,
+        };
+        let lines = summary(&s, Duration::from_secs(2));
+        assert!(lines[1] == "Primary rays:       2000");
+        assert!(lines[2] == "Reflection rays:    500");
+        assert!(lines[3] == "Refraction rays:    300");
+        assert!(lines[4] == "Scatter rays:       200");
+        assert!(lines[5] == "Shadow rays:        1000");
+        assert!(lines[6] == "Sphere tests:       6000");
+        assert!(lines[7] == "Triangle tests:     500");
+        assert!(lines[8] == "Rays per second:    2000");
+    }
+}
@@ -0,0 +1,243 @@
+// Image textures: a loaded canvas looked up through one of the uv mappings.
+// Texture space has u to the right and v up, so v = 1 is the top row of the
+// image. Cube maps expect the six faces laid out as a horizontal cross, four
+// faces wide and three high:
+//
+//          up
+//   left  front  right  back
+//         down
+
+use crate::canvas;
+use crate::color;
+use crate::float::Float;
+use crate::hdr;
+use crate::tuple;
+use crate::uv;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mapping {
+    Spherical,
+    Planar,
+    Cylindrical,
+    Cube,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+// What happens to coordinates outside [0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+}
+
+pub struct Texture {
+    pub image: canvas::Canvas,
+    pub mapping: Mapping,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+pub fn texture(image: canvas::Canvas, mapping: Mapping) -> Texture {
+    return Texture {
+        image,
+        mapping,
+        filter: Filter::Bilinear,
+        wrap: Wrap::Repeat,
+    };
+}
+
+// Reads a Radiance .hdr or a PPM image, going by its first bytes.
+pub fn load(bytes: &[u8]) -> Result<canvas::Canvas, String> {
+    if bytes.starts_with(b"#?") {
+        return hdr::parse(bytes);
+    }
+    return canvas::from_ppm(bytes);
+}
+
+// Texture coordinates of a point in object space. For cube maps they are
+// relative to the face the point lies on.
+pub fn uv_at(mapping: Mapping, object_point: &tuple::Tuple) -> (Float, Float) {
+    match mapping {
+        Mapping::Spherical => return uv::spherical_map(object_point),
+        Mapping::Planar => return uv::planar_map(object_point),
+        Mapping::Cylindrical => return uv::cylindrical_map(object_point),
+        Mapping::Cube => {
+            let (_, u, v) = uv::cube_map(object_point);
+            return (u, v);
+        }
+    }
+}
+
+// The texture's color at a point in the shape's object space.
+pub fn color_at(t: &Texture, object_point: &tuple::Tuple) -> color::Color {
+    let (w, h) = (t.image.width(), t.image.height());
+    match t.mapping {
+        Mapping::Spherical | Mapping::Planar | Mapping::Cylindrical => {
+            let (u, v) = uv_at(t.mapping, object_point);
+            return sample(t, u, v, (0, 0, w, h));
+        }
+        Mapping::Cube => {
+            let (face, u, v) = uv::cube_map(object_point);
+            let (column, row) = match face {
+                uv::Face::Up => (1, 0),
+                uv::Face::Left => (0, 1),
+                uv::Face::Front => (1, 1),
+                uv::Face::Right => (2, 1),
+                uv::Face::Back => (3, 1),
+                uv::Face::Down => (1, 2),
+            };
+            let (fw, fh) = (w / 4, h / 3);
+            return sample(t, u, v, (column * fw, row * fh, fw.max(1), fh.max(1)));
+        }
+    }
+}
+
+// The texture's color at coordinates the shape supplies itself, such as
+// those of an OBJ mesh, bypassing the mapping.
+pub fn color_at_uv(t: &Texture, u: Float, v: Float) -> color::Color {
+    return sample(t, u, v, (0, 0, t.image.width(), t.image.height()));
+}
+
+// Looks up (u, v) within the region (x, y, width, height) of the image.
+// Pixel centers sit at half texel offsets, so bilinear filtering blends the
+// four nearest centers. Cube faces always clamp to keep neighbouring faces
+// and the empty corners of the cross from bleeding in.
+fn sample(t: &Texture, u: Float, v: Float, region: (usize, usize, usize, usize)) -> color::Color {
+    let (x0, y0, w, h) = region;
+    let wrap = if t.mapping == Mapping::Cube {
+        Wrap::Clamp
+    } else {
+        t.wrap
+    };
+    let at = |x: i64, y: i64| {
+        let x = address(x, w, wrap);
+        let y = address(y, h, wrap);
+        return canvas::pixel_at(&t.image, x0 + x, y0 + y);
+    };
+    let px = u * w as Float;
+    let py = (1.0 - v) * h as Float;
+    match t.filter {
+        Filter::Nearest => return at(px.floor() as i64, py.floor() as i64),
+        Filter::Bilinear => {
+            let (px, py) = (px - 0.5, py - 0.5);
+            let (x, y) = (px.floor(), py.floor());
+            let (fx, fy) = (px - x, py - y);
+            let (x, y) = (x as i64, y as i64);
+            let top = lerp(&at(x, y), &at(x + 1, y), fx);
+            let bottom = lerp(&at(x, y + 1), &at(x + 1, y + 1), fx);
+            return lerp(&top, &bottom, fy);
+        }
+    }
+}
+
+fn address(i: i64, size: usize, wrap: Wrap) -> usize {
+    match wrap {
+        Wrap::Repeat => return i.rem_euclid(size as i64) as usize,
+        Wrap::Clamp => return i.clamp(0, size as i64 - 1) as usize,
+    }
+}
+
+fn lerp(a: &color::Color, b: &color::Color, t: Float) -> color::Color {
+    return color::add(&color::multiply(a, 1.0 - t), &color::multiply(b, t));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: Float) -> color::Color {
+        return color::color(v, v, v);
+    }
+
+    // A 2x2 image: black and white on top, red and green below.
+    fn checker(mapping: Mapping) -> Texture {
+        let mut image = canvas::canvas(2, 2);
+        canvas::write_pixel(&mut image, 1, 0, &gray(1.0));
+        canvas::write_pixel(&mut image, 0, 1, &color::color(1.0, 0.0, 0.0));
+        canvas::write_pixel(&mut image, 1, 1, &color::color(0.0, 1.0, 0.0));
+        return texture(image, mapping);
+    }
+
+    #[test]
+    fn should_sample_nearest_texel() {
+        let mut t = checker(Mapping::Planar);
+        t.filter = Filter::Nearest;
+        let top_right = color_at(&t, &tuple::point(0.75, 0.0, 0.75));
+        assert!(color::equals(&top_right, &gray(1.0)));
+        let bottom_left = color_at(&t, &tuple::point(0.25, 0.0, 0.25));
+        assert!(color::equals(&bottom_left, &color::color(1.0, 0.0, 0.0)));
+        // Planar tiles repeat.
+        let tiled = color_at(&t, &tuple::point(-0.25, 0.0, 2.25));
+        assert!(color::equals(&tiled, &color::color(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn should_blend_bilinearly() {
+        let t = checker(Mapping::Planar);
+        // Texel centers come back exactly.
+        let center = color_at(&t, &tuple::point(0.75, 0.0, 0.75));
+        assert!(color::equals(&center, &gray(1.0)));
+        // Halfway between the two top texels.
+        let between = color_at(&t, &tuple::point(0.5, 0.0, 0.75));
+        assert!(color::equals(&between, &gray(0.5)));
+        // Repeat blends across the edge into the opposite column...
+        let edge = color_at(&t, &tuple::point(0.0, 0.0, 0.75));
+        assert!(color::equals(&edge, &gray(0.5)));
+        // ...while clamp holds the edge texel.
+        let mut clamped = checker(Mapping::Planar);
+        clamped.wrap = Wrap::Clamp;
+        let edge = color_at(&clamped, &tuple::point(0.0, 0.0, 0.75));
+        assert!(color::equals(&edge, &gray(0.0)));
+    }
+
+    #[test]
+    fn should_pick_cube_face_from_cross() {
+        let mut image = canvas::canvas(8, 6);
+        let faces = [
+            (2, 0, color::color(1.0, 1.0, 0.0)), // up
+            (0, 2, color::color(1.0, 0.0, 0.0)), // left
+            (2, 2, color::color(0.0, 1.0, 1.0)), // front
+            (4, 2, color::color(0.0, 1.0, 0.0)), // right
+            (6, 2, color::color(1.0, 0.0, 1.0)), // back
+            (2, 4, color::color(1.0, 1.0, 1.0)), // down
+        ];
+        for (x, y, c) in faces.iter() {
+            for dx in 0..2 {
+                for dy in 0..2 {
+                    canvas::write_pixel(&mut image, x + dx, y + dy, c);
+                }
+            }
+        }
+        let t = texture(image, Mapping::Cube);
+        let points = [
+            tuple::point(0.0, 1.0, 0.0),
+            tuple::point(-1.0, 0.0, 0.0),
+            tuple::point(0.0, 0.0, 1.0),
+            tuple::point(1.0, 0.0, 0.0),
+            tuple::point(0.0, 0.0, -1.0),
+            tuple::point(0.0, -1.0, 0.0),
+        ];
+        for (p, (_, _, c)) in points.iter().zip(faces.iter()) {
+            assert!(color::equals(&color_at(&t, p), c));
+        }
+        // Near a face edge clamping keeps the empty corners out.
+        let corner = color_at(&t, &tuple::point(0.99, 1.0, 0.99));
+        assert!(color::equals(&corner, &color::color(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn should_detect_image_format() {
+        let ppm = load(b"P3\n1 1\n255\n255 0 0\n").ok().unwrap();
+        assert!(color::equals(
+            &canvas::pixel_at(&ppm, 0, 0),
+            &color::color(1.0, 0.0, 0.0)
+        ));
+        assert!(load(b"#?RADIANCE\n").is_err());
+        assert!(load(b"GIF89a").is_err());
+    }
+}
@@ -1,68 +1,407 @@
+use crate::float;
+use crate::float::Float;
+use crate::matrix;
 use crate::matrix4;
+use crate::quaternion;
 use crate::ray;
 use crate::tuple;
 
-pub fn translation(x: f64, y: f64, z: f64) -> matrix4::Matrix4 {
-    return [
+pub fn translation(x: Float, y: Float, z: Float) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [1.0, 0.0, 0.0, x],
         [0.0, 1.0, 0.0, y],
         [0.0, 0.0, 1.0, z],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
 }
 
-pub fn scaling(x: f64, y: f64, z: f64) -> matrix4::Matrix4 {
-    return [
+pub fn scaling(x: Float, y: Float, z: Float) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [x, 0.0, 0.0, 0.0],
         [0.0, y, 0.0, 0.0],
         [0.0, 0.0, z, 0.0],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
 }
 
-pub fn rotation_x(rad: f64) -> matrix4::Matrix4 {
-    return [
+pub fn rotation_x(rad: Float) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [1.0, 0.0, 0.0, 0.0],
         [0.0, rad.cos(), -rad.sin(), 0.0],
         [0.0, rad.sin(), rad.cos(), 0.0],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
 }
 
-pub fn rotation_y(rad: f64) -> matrix4::Matrix4 {
-    return [
+pub fn rotation_y(rad: Float) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [rad.cos(), 0.0, rad.sin(), 0.0],
         [0.0, 1.0, 0.0, 0.0],
         [-rad.sin(), 0.0, rad.cos(), 0.0],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
 }
 
-pub fn rotation_z(rad: f64) -> matrix4::Matrix4 {
-    return [
+pub fn rotation_z(rad: Float) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [rad.cos(), -rad.sin(), 0.0, 0.0],
         [rad.sin(), rad.cos(), 0.0, 0.0],
         [0.0, 0.0, 1.0, 0.0],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
 }
 
-pub fn shearing(xmy: f64, xmz: f64, ymx: f64, ymz: f64, zmx: f64, zmy: f64) -> matrix4::Matrix4 {
-    return [
+pub fn shearing(
+    xmy: Float,
+    xmz: Float,
+    ymx: Float,
+    ymz: Float,
+    zmx: Float,
+    zmy: Float,
+) -> matrix4::Matrix4 {
+    return matrix::Matrix([
         [1.0, xmy, xmz, 0.0],
         [ymx, 1.0, ymz, 0.0],
         [zmx, zmy, 1.0, 0.0],
         [0.0, 0.0, 0.0, 1.0],
-    ];
+    ]);
+}
+
+pub fn rotation_axis_angle(axis: &tuple::Tuple, rad: Float) -> matrix4::Matrix4 {
+    return quaternion::to_matrix(&quaternion::from_axis_angle(axis, rad));
+}
+
+// The order Euler angle rotations are applied in: Xyz turns about x first
+// and z last.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EulerOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+pub fn rotation_euler(x: Float, y: Float, z: Float, order: EulerOrder) -> matrix4::Matrix4 {
+    let (rx, ry, rz) = (rotation_x(x), rotation_y(y), rotation_z(z));
+    let (first, second, third) = match order {
+        EulerOrder::Xyz => (rx, ry, rz),
+        EulerOrder::Xzy => (rx, rz, ry),
+        EulerOrder::Yxz => (ry, rx, rz),
+        EulerOrder::Yzx => (ry, rz, rx),
+        EulerOrder::Zxy => (rz, rx, ry),
+        EulerOrder::Zyx => (rz, ry, rx),
+    };
+    return third * second * first;
+}
+
+// Angles (x, y, z) for which rotation_euler(x, y, z, order) gives rotation.
+// When the middle turn is a quarter turn the first and last axes line up
+// (gimbal lock) and only their sum matters, so the last angle is taken as 0.
+pub fn euler_angles(rotation: &matrix4::Matrix4, order: EulerOrder) -> (Float, Float, Float) {
+    let m = rotation;
+    // Axes in the order they are applied, and whether that is a cyclic
+    // order like xyz (1) or a reversed one like zyx (-1).
+    let (i, j, k, s) = match order {
+        EulerOrder::Xyz => (0, 1, 2, 1.0),
+        EulerOrder::Yzx => (1, 2, 0, 1.0),
+        EulerOrder::Zxy => (2, 0, 1, 1.0),
+        EulerOrder::Xzy => (0, 2, 1, -1.0),
+        EulerOrder::Zyx => (2, 1, 0, -1.0),
+        EulerOrder::Yxz => (1, 0, 2, -1.0),
+    };
+    let second = (-s * m[k][i]).clamp(-1.0, 1.0).asin();
+    let (first, third) = if m[k][i].abs() < 1.0 - 1e-9 {
+        ((s * m[k][j]).atan2(m[k][k]), (s * m[j][i]).atan2(m[i][i]))
+    } else {
+        ((-s * m[j][k]).atan2(m[j][j]), 0.0)
+    };
+    let mut angles = [0.0; 3];
+    angles[i] = first;
+    angles[j] = second;
+    angles[k] = third;
+    return (angles[0], angles[1], angles[2]);
+}
+
+// Turns +z to face direction, with +y leaning as close to up as it can.
+// An up parallel to direction says nothing about the roll, so any
+// perpendicular will do.
+pub fn look_rotation(direction: &tuple::Tuple, up: &tuple::Tuple) -> matrix4::Matrix4 {
+    let forward = tuple::normalize(direction);
+    let mut side = tuple::cross(up, &forward);
+    if tuple::magnitude(&side) < 1e-9 {
+        let other = if forward.x.abs() < 0.9 {
+            tuple::vector(1.0, 0.0, 0.0)
+        } else {
+            tuple::vector(0.0, 1.0, 0.0)
+        };
+        side = tuple::cross(&other, &forward);
+    }
+    let side = tuple::normalize(&side);
+    let true_up = tuple::cross(&forward, &side);
+    return matrix::Matrix([
+        [side.x, true_up.x, forward.x, 0.0],
+        [side.y, true_up.y, forward.y, 0.0],
+        [side.z, true_up.z, forward.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+}
+
+// An affine transform taken apart into the builders that make it again:
+// translation * rotation * shearing(xy, xz, 0, yz, 0, 0) * scaling, so
+// scale is applied first. A mirror shows up as a negative x scale.
+#[derive(Clone, Copy, Debug)]
+pub struct Decomposition {
+    pub translation: [Float; 3],
+    pub rotation: quaternion::Quaternion,
+    pub scale: [Float; 3],
+    // x by y, x by z and y by z, as for shearing.
+    pub shear: [Float; 3],
+}
+
+// Errors for a bottom row other than 0 0 0 1, which no builder but a
+// perspective projection produces, and for flattening transforms, which
+// have no rotation to recover.
+pub fn decompose(m: &matrix4::Matrix4) -> Result<Decomposition, String> {
+    for i in 0..4 {
+        for j in 0..4 {
+            if !m[i][j].is_finite() {
+                return Err(String::from("matrix has a non-finite entry"));
+            }
+        }
+    }
+    if !(float::equals(m[3][0], 0.0)
+        && float::equals(m[3][1], 0.0)
+        && float::equals(m[3][2], 0.0)
+        && float::equals(m[3][3], 1.0))
+    {
+        return Err(String::from("matrix is not affine"));
+    }
+    // Gram-Schmidt on the columns gives rotation * upper triangle, whose
+    // diagonal is the scale and the rest the shear.
+    let column = |j: usize| tuple::vector(m[0][j], m[1][j], m[2][j]);
+    let (a0, a1, a2) = (column(0), column(1), column(2));
+    let size = tuple::magnitude(&a0)
+        .max(tuple::magnitude(&a1))
+        .max(tuple::magnitude(&a2));
+    let singular = |v: Float| v <= 1e-12 * size;
+    let mut sx = tuple::magnitude(&a0);
+    if singular(sx) {
+        return Err(String::from("matrix is singular"));
+    }
+    let mut q0 = a0 / sx;
+    let mut u01 = tuple::dot(&q0, &a1);
+    let rest = a1 - q0 * u01;
+    let sy = tuple::magnitude(&rest);
+    if singular(sy) {
+        return Err(String::from("matrix is singular"));
+    }
+    let q1 = rest / sy;
+    let mut u02 = tuple::dot(&q0, &a2);
+    let u12 = tuple::dot(&q1, &a2);
+    let rest = a2 - q0 * u02 - q1 * u12;
+    let sz = tuple::magnitude(&rest);
+    if singular(sz) {
+        return Err(String::from("matrix is singular"));
+    }
+    let q2 = rest / sz;
+    if tuple::dot(&tuple::cross(&q0, &q1), &q2) < 0.0 {
+        q0 = -q0;
+        sx = -sx;
+        u01 = -u01;
+        u02 = -u02;
+    }
+    let rotation = matrix::Matrix([
+        [q0.x, q1.x, q2.x, 0.0],
+        [q0.y, q1.y, q2.y, 0.0],
+        [q0.z, q1.z, q2.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    return Ok(Decomposition {
+        translation: [m[0][3], m[1][3], m[2][3]],
+        rotation: quaternion::from_matrix(&rotation),
+        scale: [sx, sy, sz],
+        shear: [u01 / sy, u02 / sz, u12 / sz],
+    });
+}
+
+pub fn compose(d: &Decomposition) -> matrix4::Matrix4 {
+    let [tx, ty, tz] = d.translation;
+    let [sx, sy, sz] = d.scale;
+    let [xy, xz, yz] = d.shear;
+    return translation(tx, ty, tz)
+        * quaternion::to_matrix(&d.rotation)
+        * shearing(xy, xz, 0.0, yz, 0.0, 0.0)
+        * scaling(sx, sy, sz);
+}
+
+pub fn view_transform(
+    from: &tuple::Tuple,
+    to: &tuple::Tuple,
+    up: &tuple::Tuple,
+) -> matrix4::Matrix4 {
+    let forward = tuple::normalize(&tuple::subtract(to, from));
+    let left = tuple::cross(&forward, &tuple::normalize(up));
+    let true_up = tuple::cross(&left, &forward);
+    let orientation = matrix::Matrix([
+        [left.x, left.y, left.z, 0.0],
+        [true_up.x, true_up.y, true_up.z, 0.0],
+        [-forward.x, -forward.y, -forward.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    return matrix::multiply(&orientation, &translation(-from.x, -from.y, -from.z));
 }
 
 pub fn transform(ray: &ray::Ray, matrix: &matrix4::Matrix4) -> ray::Ray {
-    return ray::ray(
-        &matrix4::multiply_tuple(matrix, &ray.origin),
-        &matrix4::multiply_tuple(matrix, &ray.direction),
+    return ray::ray_at_time(
+        &matrix4::multiply_point(matrix, &ray.origin),
+        &matrix4::multiply_vector(matrix, &ray.direction),
+        ray.time,
     );
 }
 
+// Blends two affine transforms for t from 0 (a) to 1 (b). Blending the
+// matrices entry by entry would squash objects midway through a turn, so
+// each is decomposed; translation, scale and shear blend linearly and
+// rotation turns at a constant rate.
+pub fn interpolate(a: &matrix4::Matrix4, b: &matrix4::Matrix4, t: Float) -> matrix4::Matrix4 {
+    return motion_at(&motion(a, b), t);
+}
+
+// A transform moving from start at time 0 to end at time 1, decomposed once
+// so that finding it at a given time only has to blend the pieces.
+#[derive(Clone, Copy, Debug)]
+pub struct Motion {
+    pub start: matrix4::Matrix4,
+    pub end: matrix4::Matrix4,
+    // None when an end is flattened by a scale of 0: there is no rotation
+    // to find, so the matrices blend as they are.
+    pieces: Option<(Decomposition, Decomposition)>,
+}
+
+pub fn motion(start: &matrix4::Matrix4, end: &matrix4::Matrix4) -> Motion {
+    let pieces = match (decompose(start), decompose(end)) {
+        (Ok(a), Ok(b)) => Some((a, b)),
+        _ => None,
+    };
+    return Motion {
+        start: *start,
+        end: *end,
+        pieces,
+    };
+}
+
+pub fn motion_at(m: &Motion, t: Float) -> matrix4::Matrix4 {
+    match &m.pieces {
+        Some((a, b)) => return compose(&blend(a, b, t)),
+        None => return lerp(&m.start, &m.end, t),
+    }
+}
+
+pub fn blend(a: &Decomposition, b: &Decomposition, t: Float) -> Decomposition {
+    let mix = |x: &[Float; 3], y: &[Float; 3]| {
+        [
+            (1.0 - t) * x[0] + t * y[0],
+            (1.0 - t) * x[1] + t * y[1],
+            (1.0 - t) * x[2] + t * y[2],
+        ]
+    };
+    return Decomposition {
+        translation: mix(&a.translation, &b.translation),
+        rotation: quaternion::slerp(&a.rotation, &b.rotation, t),
+        scale: mix(&a.scale, &b.scale),
+        shear: mix(&a.shear, &b.shear),
+    };
+}
+
+fn lerp(a: &matrix4::Matrix4, b: &matrix4::Matrix4, t: Float) -> matrix4::Matrix4 {
+    let mut m = matrix4::matrix4();
+    for i in 0..4 {
+        for j in 0..4 {
+            m[i][j] = (1.0 - t) * a[i][j] + t * b[i][j];
+        }
+    }
+    return m;
+}
+
+// Fluent composition, applied in reading order:
+//
+//   identity().rotate_x(a).scale(5.0, 5.0, 5.0).translate(10.0, 5.0, 7.0)
+//
+// rotates first and translates last, the same as
+// translation(..) * scaling(..) * rotation_x(a). Each step multiplies on
+// the left, which is the part that is easy to get backwards by hand.
+pub fn identity() -> matrix4::Matrix4 {
+    return matrix4::IDENTITY;
+}
+
+impl matrix::Matrix<4> {
+    // Applies m after everything so far.
+    pub fn then(self, m: &matrix4::Matrix4) -> matrix4::Matrix4 {
+        return *m * self;
+    }
+
+    pub fn translate(self, x: Float, y: Float, z: Float) -> matrix4::Matrix4 {
+        return self.then(&translation(x, y, z));
+    }
+
+    pub fn scale(self, x: Float, y: Float, z: Float) -> matrix4::Matrix4 {
+        return self.then(&scaling(x, y, z));
+    }
+
+    pub fn rotate_x(self, rad: Float) -> matrix4::Matrix4 {
+        return self.then(&rotation_x(rad));
+    }
+
+    pub fn rotate_y(self, rad: Float) -> matrix4::Matrix4 {
+        return self.then(&rotation_y(rad));
+    }
+
+    pub fn rotate_z(self, rad: Float) -> matrix4::Matrix4 {
+        return self.then(&rotation_z(rad));
+    }
+
+    pub fn rotate(self, axis: &tuple::Tuple, rad: Float) -> matrix4::Matrix4 {
+        return self.then(&rotation_axis_angle(axis, rad));
+    }
+
+    pub fn rotate_euler(self, x: Float, y: Float, z: Float, order: EulerOrder) -> matrix4::Matrix4 {
+        return self.then(&rotation_euler(x, y, z, order));
+    }
+
+    pub fn rotate_quaternion(self, q: &quaternion::Quaternion) -> matrix4::Matrix4 {
+        return self.then(&quaternion::to_matrix(q));
+    }
+
+    pub fn shear(
+        self,
+        xmy: Float,
+        xmz: Float,
+        ymx: Float,
+        ymz: Float,
+        zmx: Float,
+        zmy: Float,
+    ) -> matrix4::Matrix4 {
+        return self.then(&shearing(xmy, xmz, ymx, ymz, zmx, zmy));
+    }
+
+    pub fn look_along(self, direction: &tuple::Tuple, up: &tuple::Tuple) -> matrix4::Matrix4 {
+        return self.then(&look_rotation(direction, up));
+    }
+
+    // Moves the world as seen by an eye at from looking at to, so this is
+    // normally the last step.
+    pub fn view(
+        self,
+        from: &tuple::Tuple,
+        to: &tuple::Tuple,
+        up: &tuple::Tuple,
+    ) -> matrix4::Matrix4 {
+        return self.then(&view_transform(from, to, up));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,7 +420,7 @@ mod tests {
     #[test]
     fn should_translate_in_reverse() {
         let transform = translation(5.0, -3.0, 2.0);
-        let inv = matrix4::inverse(&transform);
+        let inv = matrix4::inverse(&transform).unwrap();
         let p = tuple::point(-3.0, 4.0, 5.0);
         let result = matrix4::multiply_tuple(&inv, &p);
         let expected = tuple::point(-8.0, 7.0, 3.0);
@@ -121,7 +460,7 @@ mod tests {
     #[test]
     fn should_scale_inversely() {
         let transform = scaling(2.0, 3.0, 4.0);
-        let inv = matrix4::inverse(&transform);
+        let inv = matrix4::inverse(&transform).unwrap();
         let v = tuple::vector(-4.0, 6.0, 8.0);
         let result = matrix4::multiply_tuple(&inv, &v);
         let expected = tuple::vector(-2.0, 2.0, 2.0);
@@ -142,14 +481,14 @@ mod tests {
     #[test]
     fn should_rotate_around_x_axis() {
         let p = tuple::point(0.0, 1.0, 0.0);
-        let two: f64 = 2.0;
+        let two: Float = 2.0;
 
-        let half_quarter = rotation_x(std::f64::consts::PI / 4.0);
+        let half_quarter = rotation_x(float::consts::PI / 4.0);
         let r1 = matrix4::multiply_tuple(&half_quarter, &p);
         let e1 = tuple::point(0.0, two.sqrt() / 2.0, two.sqrt() / 2.0);
         assert!(tuple::equals(&r1, &e1));
 
-        let full_quarter = rotation_x(std::f64::consts::PI / 2.0);
+        let full_quarter = rotation_x(float::consts::PI / 2.0);
         let r2 = matrix4::multiply_tuple(&full_quarter, &p);
         let e2 = tuple::point(0.0, 0.0, 1.0);
         assert!(tuple::equals(&r2, &e2));
@@ -158,10 +497,10 @@ mod tests {
     //  Page 49
     #[test]
     fn should_inverse_rotate_around_x_axis() {
-        let two: f64 = 2.0;
+        let two: Float = 2.0;
         let p = tuple::point(0.0, 1.0, 0.0);
-        let half_quarter = rotation_x(std::f64::consts::PI / 4.0);
-        let inv = matrix4::inverse(&half_quarter);
+        let half_quarter = rotation_x(float::consts::PI / 4.0);
+        let inv = matrix4::inverse(&half_quarter).unwrap();
         let result = matrix4::multiply_tuple(&inv, &p);
         let expected = tuple::point(0.0, two.sqrt() / 2.0, -two.sqrt() / 2.0);
         assert!(tuple::equals(&result, &expected));
@@ -171,14 +510,14 @@ mod tests {
     #[test]
     fn should_rotate_around_y_axis() {
         let p = tuple::point(0.0, 0.0, 1.0);
-        let two: f64 = 2.0;
+        let two: Float = 2.0;
 
-        let half_quarter = rotation_y(std::f64::consts::PI / 4.0);
+        let half_quarter = rotation_y(float::consts::PI / 4.0);
         let r1 = matrix4::multiply_tuple(&half_quarter, &p);
         let e1 = tuple::point(two.sqrt() / 2.0, 0.0, two.sqrt() / 2.0);
         assert!(tuple::equals(&r1, &e1));
 
-        let full_quarter = rotation_y(std::f64::consts::PI / 2.0);
+        let full_quarter = rotation_y(float::consts::PI / 2.0);
         let r2 = matrix4::multiply_tuple(&full_quarter, &p);
         let e2 = tuple::point(1.0, 0.0, 0.0);
         assert!(tuple::equals(&r2, &e2));
@@ -188,14 +527,14 @@ mod tests {
     #[test]
     fn should_rotate_around_z_axis() {
         let p = tuple::point(0.0, 1.0, 0.0);
-        let two: f64 = 2.0;
+        let two: Float = 2.0;
 
-        let half_quarter = rotation_z(std::f64::consts::PI / 4.0);
+        let half_quarter = rotation_z(float::consts::PI / 4.0);
         let r1 = matrix4::multiply_tuple(&half_quarter, &p);
         let e1 = tuple::point(-two.sqrt() / 2.0, two.sqrt() / 2.0, 0.0);
         assert!(tuple::equals(&r1, &e1));
 
-        let full_quarter = rotation_z(std::f64::consts::PI / 2.0);
+        let full_quarter = rotation_z(float::consts::PI / 2.0);
         let r2 = matrix4::multiply_tuple(&full_quarter, &p);
         let e2 = tuple::point(-1.0, 0.0, 0.0);
         assert!(tuple::equals(&r2, &e2));
@@ -260,7 +599,7 @@ mod tests {
     #[test]
     fn should_transform_in_sequence() {
         let p = tuple::point(1.0, 0.0, 1.0);
-        let a = rotation_x(std::f64::consts::PI / 2.0);
+        let a = rotation_x(float::consts::PI / 2.0);
         let b = scaling(5.0, 5.0, 5.0);
         let c = translation(10.0, 5.0, 7.0);
         let p2 = matrix4::multiply_tuple(&a, &p);
@@ -278,12 +617,262 @@ mod tests {
     #[test]
     fn should_transform_chained() {
         let p = tuple::point(1.0, 0.0, 1.0);
-        let a = rotation_x(std::f64::consts::PI / 2.0);
+        let a = rotation_x(float::consts::PI / 2.0);
         let b = scaling(5.0, 5.0, 5.0);
         let c = translation(10.0, 5.0, 7.0);
-        let t = matrix4::multiply(&matrix4::multiply(&c, &b), &a);
+        let t = matrix::multiply(&matrix::multiply(&c, &b), &a);
         let result = matrix4::multiply_tuple(&t, &p);
         let expected = tuple::point(15.0, 0.0, 7.0);
         assert!(tuple::equals(&result, &expected));
     }
+
+    //  Page 98
+    #[test]
+    fn should_view_default_orientation() {
+        let from = tuple::point(0.0, 0.0, 0.0);
+        let to = tuple::point(0.0, 0.0, -1.0);
+        let up = tuple::vector(0.0, 1.0, 0.0);
+        let t = view_transform(&from, &to, &up);
+        assert!(matrix::equals(&t, &matrix4::IDENTITY));
+    }
+
+    //  Page 98
+    #[test]
+    fn should_view_positive_z() {
+        let from = tuple::point(0.0, 0.0, 0.0);
+        let to = tuple::point(0.0, 0.0, 1.0);
+        let up = tuple::vector(0.0, 1.0, 0.0);
+        let t = view_transform(&from, &to, &up);
+        assert!(matrix::equals(&t, &scaling(-1.0, 1.0, -1.0)));
+    }
+
+    //  Page 99
+    #[test]
+    fn should_move_the_world() {
+        let from = tuple::point(0.0, 0.0, 8.0);
+        let to = tuple::point(0.0, 0.0, 0.0);
+        let up = tuple::vector(0.0, 1.0, 0.0);
+        let t = view_transform(&from, &to, &up);
+        assert!(matrix::equals(&t, &translation(0.0, 0.0, -8.0)));
+    }
+
+    //  Page 99
+    #[test]
+    fn should_view_arbitrary_direction() {
+        let from = tuple::point(1.0, 3.0, 2.0);
+        let to = tuple::point(4.0, -2.0, 8.0);
+        let up = tuple::vector(1.0, 1.0, 0.0);
+        let t = view_transform(&from, &to, &up);
+        let expected = matrix::Matrix([
+            [-0.50709, 0.50709, 0.67612, -2.36643],
+            [0.76772, 0.60609, 0.12122, -2.82843],
+            [-0.35857, 0.59761, -0.71714, 0.00000],
+            [0.00000, 0.00000, 0.00000, 1.00000],
+        ]);
+        assert!(matrix::equals(&t, &expected));
+    }
+
+    #[test]
+    fn should_interpolate_decomposed_transforms() {
+        let a = matrix::multiply(&translation(1.0, 0.0, 0.0), &rotation_y(0.0));
+        let b = matrix::multiply(
+            &translation(3.0, 2.0, 0.0),
+            &matrix::multiply(
+                &rotation_y(float::consts::PI / 2.0),
+                &scaling(3.0, 1.0, 1.0),
+            ),
+        );
+        assert!(matrix::equals(&interpolate(&a, &b, 0.0), &a));
+        assert!(matrix::equals(&interpolate(&a, &b, 1.0), &b));
+        let expected = matrix::multiply(
+            &translation(2.0, 1.0, 0.0),
+            &matrix::multiply(
+                &rotation_y(float::consts::PI / 4.0),
+                &scaling(2.0, 1.0, 1.0),
+            ),
+        );
+        assert!(matrix::equals(&interpolate(&a, &b, 0.5), &expected));
+    }
+
+    #[test]
+    fn should_interpolate_mirrored_and_sheared_transforms() {
+        let a = scaling(-1.0, 1.0, 1.0);
+        let b = matrix::multiply(&rotation_z(1.0), &shearing(0.5, 0.0, 0.0, 0.0, 0.0, 0.0));
+        assert!(matrix::equals(&interpolate(&a, &b, 0.0), &a));
+        assert!(matrix::equals(&interpolate(&a, &b, 1.0), &b));
+        let same = interpolate(&b, &b, 0.3);
+        assert!(matrix::equals(&same, &b));
+    }
+
+    #[test]
+    fn should_interpolate_from_flattened_transform() {
+        let a = matrix::multiply(&translation(0.0, 2.0, 0.0), &scaling(0.0, 0.0, 0.0));
+        let b = rotation_z(0.5);
+        assert!(matrix4::inverse(&a).is_none());
+        // Halfway between nothing and the turn is the turn at half size.
+        let expected = matrix::multiply(
+            &translation(0.0, 1.0, 0.0),
+            &matrix::multiply(&rotation_z(0.5), &scaling(0.5, 0.5, 0.5)),
+        );
+        assert!(matrix::equals(&interpolate(&a, &b, 0.5), &expected));
+    }
+
+    #[test]
+    fn should_rotate_about_arbitrary_axis() {
+        let x = tuple::vector(1.0, 0.0, 0.0);
+        assert!(matrix::equals(
+            &rotation_axis_angle(&x, 0.4),
+            &rotation_x(0.4)
+        ));
+        let z = tuple::vector(0.0, 0.0, -3.0);
+        assert!(matrix::equals(
+            &rotation_axis_angle(&z, 0.4),
+            &rotation_z(-0.4)
+        ));
+        let m = rotation_axis_angle(&tuple::vector(1.0, 1.0, 0.0), float::consts::PI);
+        let p = matrix4::multiply_tuple(&m, &tuple::point(1.0, 0.0, 0.0));
+        assert!(tuple::equals(&p, &tuple::point(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn should_apply_euler_angles_in_order() {
+        let (x, y, z) = (0.3, -1.1, 2.0);
+        let xyz = rotation_euler(x, y, z, EulerOrder::Xyz);
+        assert!(xyz == rotation_z(z) * rotation_y(y) * rotation_x(x));
+        let zyx = rotation_euler(x, y, z, EulerOrder::Zyx);
+        assert!(zyx == rotation_x(x) * rotation_y(y) * rotation_z(z));
+        let yzx = rotation_euler(x, y, z, EulerOrder::Yzx);
+        assert!(yzx == rotation_x(x) * rotation_z(z) * rotation_y(y));
+        assert!(xyz != zyx);
+    }
+
+    #[test]
+    fn should_look_along_direction() {
+        let direction = tuple::vector(1.0, 0.0, 1.0);
+        let m = look_rotation(&direction, &tuple::vector(0.0, 1.0, 0.0));
+        let forward = matrix4::multiply_tuple(&m, &tuple::vector(0.0, 0.0, 1.0));
+        assert!(tuple::equals(&forward, &tuple::normalize(&direction)));
+        let up = matrix4::multiply_tuple(&m, &tuple::vector(0.0, 1.0, 0.0));
+        assert!(tuple::equals(&up, &tuple::vector(0.0, 1.0, 0.0)));
+        assert!(float::equals(matrix::determinant(&m), 1.0));
+        // Looking straight up still gives a rotation.
+        let m = look_rotation(&tuple::vector(0.0, 2.0, 0.0), &tuple::vector(0.0, 1.0, 0.0));
+        let forward = matrix4::multiply_tuple(&m, &tuple::vector(0.0, 0.0, 1.0));
+        assert!(tuple::equals(&forward, &tuple::vector(0.0, 1.0, 0.0)));
+        assert!(float::equals(matrix::determinant(&m), 1.0));
+    }
+
+    #[test]
+    fn should_recover_euler_angles() {
+        let orders = [
+            (EulerOrder::Xyz, 1),
+            (EulerOrder::Xzy, 2),
+            (EulerOrder::Yxz, 0),
+            (EulerOrder::Yzx, 2),
+            (EulerOrder::Zxy, 0),
+            (EulerOrder::Zyx, 1),
+        ];
+        // The second axis is the one that can lock the other two together.
+        for (order, middle) in orders.iter() {
+            for lock in [
+                None,
+                Some(float::consts::FRAC_PI_2),
+                Some(-float::consts::FRAC_PI_2),
+            ]
+            .iter()
+            {
+                let mut angles = [0.3, -1.1, 2.0];
+                if let Some(a) = lock {
+                    angles[*middle] = *a;
+                }
+                let m = rotation_euler(angles[0], angles[1], angles[2], *order);
+                let (x, y, z) = euler_angles(&m, *order);
+                assert!(rotation_euler(x, y, z, *order) == m);
+            }
+        }
+        let (x, y, z) = euler_angles(
+            &rotation_euler(0.3, -1.1, 0.2, EulerOrder::Zxy),
+            EulerOrder::Zxy,
+        );
+        assert!(float::equals(x, 0.3) && float::equals(y, -1.1) && float::equals(z, 0.2));
+    }
+
+    #[test]
+    fn should_decompose_into_builders() {
+        let m = translation(1.0, -2.0, 3.0)
+            * rotation_axis_angle(&tuple::vector(1.0, 2.0, -0.5), 1.2)
+            * shearing(0.5, -0.25, 0.0, 0.75, 0.0, 0.0)
+            * scaling(2.0, 0.5, 3.0);
+        let d = decompose(&m).unwrap();
+        assert!(d.translation == [1.0, -2.0, 3.0]);
+        let expected = quaternion::from_axis_angle(&tuple::vector(1.0, 2.0, -0.5), 1.2);
+        assert!(quaternion::equals(&d.rotation, &expected));
+        for (a, b) in d.scale.iter().zip([2.0, 0.5, 3.0].iter()) {
+            assert!(float::equals(*a, *b));
+        }
+        for (a, b) in d.shear.iter().zip([0.5, -0.25, 0.75].iter()) {
+            assert!(float::equals(*a, *b));
+        }
+        assert!(compose(&d) == m);
+        // Mirrors go into the x scale.
+        let mirrored = decompose(&scaling(-1.0, 2.0, 1.0)).unwrap();
+        assert!(float::equals(mirrored.scale[0], -1.0));
+        assert!(quaternion::equals(
+            &mirrored.rotation,
+            &quaternion::IDENTITY
+        ));
+        // Any order of builders comes back as the same matrix.
+        let m =
+            shearing(0.0, 0.0, 1.0, 0.0, 0.5, 0.0) * rotation_euler(0.1, 0.2, 0.3, EulerOrder::Yzx);
+        assert!(compose(&decompose(&m).unwrap()) == m);
+    }
+
+    #[test]
+    fn should_refuse_singular_and_projective_matrices() {
+        let flat = rotation_y(0.3) * scaling(1.0, 0.0, 1.0);
+        assert!(decompose(&flat).err().unwrap() == "matrix is singular");
+        let mut projective = translation(1.0, 0.0, 0.0);
+        projective[3][2] = 1.0;
+        assert!(decompose(&projective).err().unwrap() == "matrix is not affine");
+    }
+
+    #[test]
+    fn should_build_in_reading_order() {
+        // The same as should_transform_chained.
+        let p = tuple::point(1.0, 0.0, 1.0);
+        let t = identity()
+            .rotate_x(float::consts::PI / 2.0)
+            .scale(5.0, 5.0, 5.0)
+            .translate(10.0, 5.0, 7.0);
+        assert!(t * p == tuple::point(15.0, 0.0, 7.0));
+        let axis = tuple::vector(0.0, 1.0, 1.0);
+        let q = quaternion::from_axis_angle(&axis, -0.3);
+        let built = identity()
+            .shear(1.0, 0.0, 0.0, 0.5, 0.0, 0.0)
+            .rotate(&axis, 0.3)
+            .rotate_quaternion(&q)
+            .rotate_euler(0.1, 0.2, 0.3, EulerOrder::Xyz)
+            .rotate_y(0.2)
+            .rotate_z(0.4);
+        let expected = rotation_z(0.4)
+            * rotation_y(0.2)
+            * rotation_euler(0.1, 0.2, 0.3, EulerOrder::Xyz)
+            * shearing(1.0, 0.0, 0.0, 0.5, 0.0, 0.0);
+        assert!(built == expected);
+    }
+
+    #[test]
+    fn should_build_look_and_view() {
+        let from = tuple::point(1.0, 3.0, 2.0);
+        let to = tuple::point(4.0, -2.0, 8.0);
+        let up = tuple::vector(1.0, 1.0, 0.0);
+        let camera = identity().translate(0.0, 1.0, 0.0).view(&from, &to, &up);
+        assert!(camera == view_transform(&from, &to, &up) * translation(0.0, 1.0, 0.0));
+        // Pointing an object at a target from where it stands.
+        let object = identity()
+            .look_along(&(to - from), &up)
+            .translate(from.x, from.y, from.z);
+        let ahead = object * tuple::point(0.0, 0.0, tuple::magnitude(&(to - from)));
+        assert!(ahead == to);
+    }
 }
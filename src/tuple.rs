@@ -1,18 +1,28 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
 use crate::float;
+use crate::float::Float;
+use crate::simd;
 
+// repr(C) keeps x, y, z and w in order for the SIMD code, which add,
+// subtract, multiply and dot use in builds where simd::INLINE is set.
 #[derive(Clone, Copy, Debug)]
+#[repr(C)]
 pub struct Tuple {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub w: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub w: Float,
 }
 
-pub fn tuple(x: f64, y: f64, z: f64, w: f64) -> Tuple {
+pub fn tuple(x: Float, y: Float, z: Float, w: Float) -> Tuple {
     return Tuple { x, y, z, w };
 }
 
 pub fn add(a: &Tuple, b: &Tuple) -> Tuple {
+    if simd::INLINE {
+        return simd::add(a, b);
+    }
     Tuple {
         x: a.x + b.x,
         y: a.y + b.y,
@@ -22,6 +32,9 @@ pub fn add(a: &Tuple, b: &Tuple) -> Tuple {
 }
 
 pub fn subtract(a: &Tuple, b: &Tuple) -> Tuple {
+    if simd::INLINE {
+        return simd::subtract(a, b);
+    }
     Tuple {
         x: a.x - b.x,
         y: a.y - b.y,
@@ -30,7 +43,10 @@ pub fn subtract(a: &Tuple, b: &Tuple) -> Tuple {
     }
 }
 
-pub fn multiply(tuple: &Tuple, scalar: f64) -> Tuple {
+pub fn multiply(tuple: &Tuple, scalar: Float) -> Tuple {
+    if simd::INLINE {
+        return simd::multiply(tuple, scalar);
+    }
     Tuple {
         x: tuple.x * scalar,
         y: tuple.y * scalar,
@@ -39,7 +55,7 @@ pub fn multiply(tuple: &Tuple, scalar: f64) -> Tuple {
     }
 }
 
-pub fn divide(tuple: &Tuple, scalar: f64) -> Tuple {
+pub fn divide(tuple: &Tuple, scalar: Float) -> Tuple {
     Tuple {
         x: tuple.x / scalar,
         y: tuple.y / scalar,
@@ -57,7 +73,10 @@ pub fn negate(tuple: &Tuple) -> Tuple {
     }
 }
 
-pub fn dot(a: &Tuple, b: &Tuple) -> f64 {
+pub fn dot(a: &Tuple, b: &Tuple) -> Float {
+    if simd::INLINE {
+        return simd::dot(a, b);
+    }
     return a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
 }
 
@@ -70,8 +89,8 @@ pub fn cross(a: &Tuple, b: &Tuple) -> Tuple {
     };
 }
 
-pub fn magnitude(tuple: &Tuple) -> f64 {
-    return f64::sqrt(
+pub fn magnitude(tuple: &Tuple) -> Float {
+    return Float::sqrt(
         tuple.x * tuple.x + tuple.y * tuple.y + tuple.z * tuple.z + tuple.w * tuple.w,
     );
 }
@@ -86,11 +105,16 @@ pub fn normalize(tuple: &Tuple) -> Tuple {
     };
 }
 
-pub fn point(x: f64, y: f64, z: f64) -> Tuple {
+// v bounced off a surface with the given normal.
+pub fn reflect(v: &Tuple, normal: &Tuple) -> Tuple {
+    return subtract(v, &multiply(normal, 2.0 * dot(v, normal)));
+}
+
+pub fn point(x: Float, y: Float, z: Float) -> Tuple {
     return tuple(x, y, z, 1.0);
 }
 
-pub fn vector(x: f64, y: f64, z: f64) -> Tuple {
+pub fn vector(x: Float, y: Float, z: Float) -> Tuple {
     return tuple(x, y, z, 0.0);
 }
 
@@ -109,6 +133,81 @@ pub fn equals(a: &Tuple, b: &Tuple) -> bool {
         && float::equals(a.w, b.w);
 }
 
+// Operators, for when the free functions above get hard to read. They
+// follow the same w arithmetic, so point + point is still not a point.
+
+impl PartialEq for Tuple {
+    fn eq(&self, other: &Tuple) -> bool {
+        return equals(self, other);
+    }
+}
+
+impl Add for Tuple {
+    type Output = Tuple;
+    fn add(self, other: Tuple) -> Tuple {
+        return add(&self, &other);
+    }
+}
+
+impl Sub for Tuple {
+    type Output = Tuple;
+    fn sub(self, other: Tuple) -> Tuple {
+        return subtract(&self, &other);
+    }
+}
+
+impl Mul<Float> for Tuple {
+    type Output = Tuple;
+    fn mul(self, scalar: Float) -> Tuple {
+        return multiply(&self, scalar);
+    }
+}
+
+impl Mul<Tuple> for Float {
+    type Output = Tuple;
+    fn mul(self, t: Tuple) -> Tuple {
+        return multiply(&t, self);
+    }
+}
+
+impl Div<Float> for Tuple {
+    type Output = Tuple;
+    fn div(self, scalar: Float) -> Tuple {
+        return divide(&self, scalar);
+    }
+}
+
+impl Neg for Tuple {
+    type Output = Tuple;
+    fn neg(self) -> Tuple {
+        return negate(&self);
+    }
+}
+
+impl AddAssign for Tuple {
+    fn add_assign(&mut self, other: Tuple) {
+        *self = add(self, &other);
+    }
+}
+
+impl SubAssign for Tuple {
+    fn sub_assign(&mut self, other: Tuple) {
+        *self = subtract(self, &other);
+    }
+}
+
+impl MulAssign<Float> for Tuple {
+    fn mul_assign(&mut self, scalar: Float) {
+        *self = multiply(self, scalar);
+    }
+}
+
+impl DivAssign<Float> for Tuple {
+    fn div_assign(&mut self, scalar: Float) {
+        *self = divide(self, scalar);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,7 +218,7 @@ mod tests {
         let x = 4.3;
         let y = -4.2;
         let z = 3.1;
-        let w: f64 = 1.0;
+        let w: Float = 1.0;
         let point = tuple(x, y, z, w);
         assert!(float::equals(point.x, x));
         assert!(float::equals(point.y, y));
@@ -135,7 +234,7 @@ mod tests {
         let x = 4.3;
         let y = -4.2;
         let z = 3.1;
-        let w: f64 = 0.0;
+        let w: Float = 0.0;
         let vector = tuple(x, y, z, w);
         assert!(float::equals(vector.x, x));
         assert!(float::equals(vector.y, y));
@@ -151,7 +250,7 @@ mod tests {
         let x = 4.3;
         let y = -4.2;
         let z = 3.1;
-        let w: f64 = 1.0;
+        let w: Float = 1.0;
         let point = point(x, y, z);
         let tuple = tuple(x, y, z, w);
         assert!(float::equals(tuple.x, point.x));
@@ -167,7 +266,7 @@ mod tests {
         let x = 4.3;
         let y = -4.2;
         let z = 3.1;
-        let w: f64 = 0.0;
+        let w: Float = 0.0;
         let vector = vector(x, y, z);
         let tuple = tuple(x, y, z, w);
         assert!(float::equals(tuple.x, vector.x));
@@ -198,7 +297,7 @@ mod tests {
             for y in 0..2 {
                 for z in 0..2 {
                     for w in 0..2 {
-                        let value = tuple(f64::from(x), f64::from(y), f64::from(z), f64::from(w));
+                        let value = tuple(x as Float, y as Float, z as Float, w as Float);
                         if x + y + z + w > 0 {
                             assert!(!equals(&expected, &value));
                         } else {
@@ -279,11 +378,11 @@ mod tests {
         assert!(float::equals(magnitude(&vector(0.0, 0.0, 1.0)), 1.0));
         assert!(float::equals(
             magnitude(&vector(1.0, 2.0, 3.0)),
-            f64::sqrt(14.0)
+            Float::sqrt(14.0)
         ));
         assert!(float::equals(
             magnitude(&vector(-1.0, -2.0, -3.0)),
-            f64::sqrt(14.0)
+            Float::sqrt(14.0)
         ));
     }
 
@@ -311,4 +410,44 @@ mod tests {
         assert!(equals(&cross(&a, &b), &vector(-1.0, 2.0, -1.0)));
         assert!(equals(&cross(&b, &a), &vector(1.0, -2.0, 1.0)));
     }
+
+    // Page 83
+    #[test]
+    fn should_reflect_vector_at_45_degrees() {
+        let v = vector(1.0, -1.0, 0.0);
+        let n = vector(0.0, 1.0, 0.0);
+        assert!(equals(&reflect(&v, &n), &vector(1.0, 1.0, 0.0)));
+    }
+
+    // Page 83
+    #[test]
+    fn should_reflect_vector_off_slanted_surface() {
+        let v = vector(0.0, -1.0, 0.0);
+        let half = Float::sqrt(2.0) / 2.0;
+        let n = vector(half, half, 0.0);
+        assert!(equals(&reflect(&v, &n), &vector(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn should_apply_operators() {
+        let p = point(3.0, -2.0, 5.0);
+        let v = vector(-2.0, 3.0, 1.0);
+        assert!(p + v == point(1.0, 1.0, 6.0));
+        assert!(p - v == point(5.0, -5.0, 4.0));
+        assert!(-v == vector(2.0, -3.0, -1.0));
+        assert!(v * 2.0 == vector(-4.0, 6.0, 2.0));
+        assert!(2.0 * v == v * 2.0);
+        assert!(v / 2.0 == vector(-1.0, 1.5, 0.5));
+        let mut t = p;
+        t += v;
+        t -= vector(1.0, 1.0, 1.0);
+        assert!(t == point(0.0, 0.0, 5.0));
+        let mut u = v;
+        u *= 4.0;
+        u /= 2.0;
+        assert!(u == vector(-4.0, 6.0, 2.0));
+        // Equality is approximate, like equals.
+        assert!(p == point(3.000001, -2.0, 5.0));
+        assert!(p != point(3.001, -2.0, 5.0));
+    }
 }
@@ -0,0 +1,154 @@
+// UV mappings from object space points to texture coordinates, with u to
+// the right and v up, both in [0, 1). They follow the shapes they are named
+// after but work on any point, so a sphere can also take a planar or
+// cylindrical projection.
+
+use crate::float::consts::PI;
+use crate::float::Float;
+
+use crate::tuple;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Face {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+pub fn spherical_map(p: &tuple::Tuple) -> (Float, Float) {
+    let theta = p.x.atan2(p.z);
+    let radius = tuple::magnitude(&tuple::vector(p.x, p.y, p.z));
+    let phi = (p.y / radius).clamp(-1.0, 1.0).acos();
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    let v = 1.0 - phi / PI;
+    return (u, v);
+}
+
+// Tiles the xz plane with unit squares.
+pub fn planar_map(p: &tuple::Tuple) -> (Float, Float) {
+    return (p.x.rem_euclid(1.0), p.z.rem_euclid(1.0));
+}
+
+// Wraps once around the y axis, repeating every unit of height.
+pub fn cylindrical_map(p: &tuple::Tuple) -> (Float, Float) {
+    let theta = p.x.atan2(p.z);
+    let raw_u = theta / (2.0 * PI);
+    let u = 1.0 - (raw_u + 0.5);
+    return (u, p.y.rem_euclid(1.0));
+}
+
+pub fn face_from_point(p: &tuple::Tuple) -> Face {
+    let coord = p.x.abs().max(p.y.abs()).max(p.z.abs());
+    if coord == p.x {
+        return Face::Right;
+    }
+    if coord == -p.x {
+        return Face::Left;
+    }
+    if coord == p.y {
+        return Face::Up;
+    }
+    if coord == -p.y {
+        return Face::Down;
+    }
+    if coord == p.z {
+        return Face::Front;
+    }
+    return Face::Back;
+}
+
+// For the axis aligned cube from -1 to 1; each face gets the whole square.
+pub fn cube_map(p: &tuple::Tuple) -> (Face, Float, Float) {
+    let face = face_from_point(p);
+    let half = |a: Float| (a.rem_euclid(2.0)) / 2.0;
+    let (u, v) = match face {
+        Face::Front => (half(p.x + 1.0), half(p.y + 1.0)),
+        Face::Back => (half(1.0 - p.x), half(p.y + 1.0)),
+        Face::Left => (half(p.z + 1.0), half(p.y + 1.0)),
+        Face::Right => (half(1.0 - p.z), half(p.y + 1.0)),
+        Face::Up => (half(p.x + 1.0), half(1.0 - p.z)),
+        Face::Down => (half(p.x + 1.0), half(p.z + 1.0)),
+    };
+    return (face, u, v);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    fn check(uv: (Float, Float), u: Float, v: Float) {
+        assert!(float::equals(uv.0, u), "u was {}", uv.0);
+        assert!(float::equals(uv.1, v), "v was {}", uv.1);
+    }
+
+    #[test]
+    fn should_map_sphere() {
+        let half = Float::sqrt(2.0) / 2.0;
+        check(spherical_map(&tuple::point(0.0, 0.0, -1.0)), 0.0, 0.5);
+        check(spherical_map(&tuple::point(1.0, 0.0, 0.0)), 0.25, 0.5);
+        check(spherical_map(&tuple::point(0.0, 0.0, 1.0)), 0.5, 0.5);
+        check(spherical_map(&tuple::point(-1.0, 0.0, 0.0)), 0.75, 0.5);
+        check(spherical_map(&tuple::point(0.0, 1.0, 0.0)), 0.5, 1.0);
+        check(spherical_map(&tuple::point(0.0, -1.0, 0.0)), 0.5, 0.0);
+        check(spherical_map(&tuple::point(half, half, 0.0)), 0.25, 0.75);
+    }
+
+    #[test]
+    fn should_map_plane() {
+        check(planar_map(&tuple::point(0.25, 0.0, 0.5)), 0.25, 0.5);
+        check(planar_map(&tuple::point(0.25, 0.0, -0.25)), 0.25, 0.75);
+        check(planar_map(&tuple::point(0.25, 0.5, -0.25)), 0.25, 0.75);
+        check(planar_map(&tuple::point(1.25, 0.0, 0.5)), 0.25, 0.5);
+        check(planar_map(&tuple::point(0.25, 0.0, -1.75)), 0.25, 0.25);
+        check(planar_map(&tuple::point(1.0, 0.0, -1.0)), 0.0, 0.0);
+        check(planar_map(&tuple::point(0.0, 0.0, 0.0)), 0.0, 0.0);
+    }
+
+    #[test]
+    fn should_map_cylinder() {
+        let half = Float::sqrt(2.0) / 2.0;
+        check(cylindrical_map(&tuple::point(0.0, 0.0, -1.0)), 0.0, 0.0);
+        check(cylindrical_map(&tuple::point(0.0, 0.5, -1.0)), 0.0, 0.5);
+        check(cylindrical_map(&tuple::point(0.0, 1.0, -1.0)), 0.0, 0.0);
+        check(cylindrical_map(&tuple::point(half, 0.5, -half)), 0.125, 0.5);
+        check(cylindrical_map(&tuple::point(1.0, 0.5, 0.0)), 0.25, 0.5);
+        check(
+            cylindrical_map(&tuple::point(-half, 0.5, -half)),
+            0.875,
+            0.5,
+        );
+    }
+
+    #[test]
+    fn should_pick_cube_faces() {
+        assert!(face_from_point(&tuple::point(-1.0, 0.5, -0.25)) == Face::Left);
+        assert!(face_from_point(&tuple::point(1.1, -0.75, 0.8)) == Face::Right);
+        assert!(face_from_point(&tuple::point(0.1, 0.6, 0.9)) == Face::Front);
+        assert!(face_from_point(&tuple::point(-0.7, 0.0, -2.0)) == Face::Back);
+        assert!(face_from_point(&tuple::point(0.5, 1.0, 0.9)) == Face::Up);
+        assert!(face_from_point(&tuple::point(-0.2, -1.3, 1.1)) == Face::Down);
+    }
+
+    #[test]
+    fn should_map_cube_faces() {
+        let (face, u, v) = cube_map(&tuple::point(-0.5, 0.5, 1.0));
+        assert!(face == Face::Front);
+        check((u, v), 0.25, 0.75);
+        check(uv(&tuple::point(0.5, -0.5, 1.0)), 0.75, 0.25);
+        check(uv(&tuple::point(0.5, 0.5, -1.0)), 0.25, 0.75);
+        check(uv(&tuple::point(-1.0, 0.5, -0.5)), 0.25, 0.75);
+        check(uv(&tuple::point(1.0, -0.5, -0.5)), 0.75, 0.25);
+        check(uv(&tuple::point(-0.5, 1.0, -0.5)), 0.25, 0.75);
+        check(uv(&tuple::point(0.5, -1.0, 0.5)), 0.75, 0.75);
+    }
+
+    fn uv(p: &tuple::Tuple) -> (Float, Float) {
+        let (_, u, v) = cube_map(p);
+        return (u, v);
+    }
+}
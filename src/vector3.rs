@@ -0,0 +1,159 @@
+// Directions and offsets, with no position. Unlike tuple::Tuple there is no
+// w to get wrong: a vector can only be combined with other vectors, and
+// adding one to a point is point3::add.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::float;
+use crate::float::Float;
+use crate::tuple;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Vector3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+pub fn vector3(x: Float, y: Float, z: Float) -> Vector3 {
+    return Vector3 { x, y, z };
+}
+
+pub fn add(a: &Vector3, b: &Vector3) -> Vector3 {
+    return vector3(a.x + b.x, a.y + b.y, a.z + b.z);
+}
+
+pub fn subtract(a: &Vector3, b: &Vector3) -> Vector3 {
+    return vector3(a.x - b.x, a.y - b.y, a.z - b.z);
+}
+
+pub fn multiply(v: &Vector3, scalar: Float) -> Vector3 {
+    return vector3(v.x * scalar, v.y * scalar, v.z * scalar);
+}
+
+pub fn divide(v: &Vector3, scalar: Float) -> Vector3 {
+    return vector3(v.x / scalar, v.y / scalar, v.z / scalar);
+}
+
+pub fn negate(v: &Vector3) -> Vector3 {
+    return vector3(-v.x, -v.y, -v.z);
+}
+
+pub fn dot(a: &Vector3, b: &Vector3) -> Float {
+    return a.x * b.x + a.y * b.y + a.z * b.z;
+}
+
+pub fn cross(a: &Vector3, b: &Vector3) -> Vector3 {
+    return vector3(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    );
+}
+
+pub fn magnitude(v: &Vector3) -> Float {
+    return dot(v, v).sqrt();
+}
+
+pub fn normalize(v: &Vector3) -> Vector3 {
+    return divide(v, magnitude(v));
+}
+
+pub fn equals(a: &Vector3, b: &Vector3) -> bool {
+    return float::equals(a.x, b.x) && float::equals(a.y, b.y) && float::equals(a.z, b.z);
+}
+
+// Compatibility with code still using tuple::Tuple. t must be a vector.
+pub fn from_tuple(t: &tuple::Tuple) -> Vector3 {
+    debug_assert!(tuple::is_vector(t), "{:?} is not a vector", t);
+    return vector3(t.x, t.y, t.z);
+}
+
+pub fn to_tuple(v: &Vector3) -> tuple::Tuple {
+    return tuple::vector(v.x, v.y, v.z);
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+    fn add(self, other: Vector3) -> Vector3 {
+        return add(&self, &other);
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+    fn sub(self, other: Vector3) -> Vector3 {
+        return subtract(&self, &other);
+    }
+}
+
+impl Mul<Float> for Vector3 {
+    type Output = Vector3;
+    fn mul(self, scalar: Float) -> Vector3 {
+        return multiply(&self, scalar);
+    }
+}
+
+impl Div<Float> for Vector3 {
+    type Output = Vector3;
+    fn div(self, scalar: Float) -> Vector3 {
+        return divide(&self, scalar);
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        return negate(&self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Page 7
+    #[test]
+    fn should_subtract_vector_from_vector() {
+        let v1 = vector3(3.0, 2.0, 1.0);
+        let v2 = vector3(5.0, 6.0, 7.0);
+        assert!(equals(&subtract(&v1, &v2), &vector3(-2.0, -4.0, -6.0)));
+        assert!(equals(&add(&v1, &v2), &vector3(8.0, 8.0, 8.0)));
+        assert!(equals(&negate(&v1), &vector3(-3.0, -2.0, -1.0)));
+    }
+
+    // Page 8
+    #[test]
+    fn should_scale_and_measure() {
+        let v = vector3(1.0, -2.0, 3.0);
+        assert!(equals(&multiply(&v, 3.5), &vector3(3.5, -7.0, 10.5)));
+        assert!(equals(&divide(&v, 2.0), &vector3(0.5, -1.0, 1.5)));
+        assert!(float::equals(magnitude(&v), Float::sqrt(14.0)));
+        let n = normalize(&vector3(1.0, 2.0, 3.0));
+        assert!(equals(&n, &vector3(0.26726, 0.53452, 0.80178)));
+        assert!(float::equals(magnitude(&n), 1.0));
+    }
+
+    // Page 10
+    #[test]
+    fn should_calculate_dot_and_cross_products() {
+        let a = vector3(1.0, 2.0, 3.0);
+        let b = vector3(2.0, 3.0, 4.0);
+        assert!(float::equals(dot(&a, &b), 20.0));
+        assert!(equals(&cross(&a, &b), &vector3(-1.0, 2.0, -1.0)));
+        assert!(equals(&cross(&b, &a), &vector3(1.0, -2.0, 1.0)));
+    }
+
+    #[test]
+    fn should_convert_tuples() {
+        let v = from_tuple(&tuple::vector(1.0, 2.0, 3.0));
+        assert!(equals(&v, &vector3(1.0, 2.0, 3.0)));
+        assert!(tuple::equals(&to_tuple(&v), &tuple::vector(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_points_as_vectors() {
+        from_tuple(&tuple::point(1.0, 2.0, 3.0));
+    }
+}
@@ -0,0 +1,400 @@
+// A small, indentation based subset of YAML, enough for scene files: block
+// maps, block lists, one-line flow lists ([ a, b, c ]), numbers, plain and
+// quoted strings, and # comments. Every node remembers its line number so
+// that later stages can report errors precisely.
+
+use std::fmt;
+
+use crate::float::Float;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(Float),
+    Text(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub line: usize,
+    pub value: Value,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "line {}: {}", self.line, self.message);
+    }
+}
+
+pub fn error(line: usize, message: &str) -> Error {
+    return Error {
+        line,
+        message: String::from(message),
+    };
+}
+
+pub fn get<'a>(node: &'a Node, key: &str) -> Option<&'a Node> {
+    match &node.value {
+        Value::Map(entries) => {
+            for (k, v) in entries {
+                if k == key {
+                    return Some(v);
+                }
+            }
+            return None;
+        }
+        _ => return None,
+    }
+}
+
+struct Line {
+    number: usize,
+    indent: usize,
+    content: String,
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+pub fn parse(text: &str) -> Result<Node, Error> {
+    let mut p = Parser {
+        lines: lex(text)?,
+        pos: 0,
+    };
+    if p.lines.is_empty() {
+        return Ok(Node {
+            line: 1,
+            value: Value::List(Vec::new()),
+        });
+    }
+    let indent = p.lines[0].indent;
+    let node = parse_block(&mut p, indent)?;
+    if p.pos < p.lines.len() {
+        return Err(error(p.lines[p.pos].number, "unexpected indentation"));
+    }
+    return Ok(node);
+}
+
+fn lex(text: &str) -> Result<Vec<Line>, Error> {
+    let mut lines = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let number = i + 1;
+        let content = strip_comment(raw).trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed == "---" {
+            continue;
+        }
+        let leading = &content[..content.len() - trimmed.len()];
+        if leading.contains('\t') {
+            return Err(error(number, "tabs are not allowed for indentation"));
+        }
+        lines.push(Line {
+            number,
+            indent: leading.len(),
+            content: String::from(trimmed),
+        });
+    }
+    return Ok(lines);
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == '#' && !quoted && previous.is_whitespace() {
+            return &line[..i];
+        }
+        previous = c;
+    }
+    return line;
+}
+
+fn is_list_item(content: &str) -> bool {
+    return content == "-" || content.starts_with("- ");
+}
+
+fn split_key(content: &str) -> Option<(&str, &str)> {
+    if content.starts_with('[') || content.starts_with('"') {
+        return None;
+    }
+    if let Some(key) = content.strip_suffix(':') {
+        if !key.contains(": ") {
+            return Some((key.trim(), ""));
+        }
+    }
+    let i = content.find(": ")?;
+    return Some((content[..i].trim(), content[i + 2..].trim()));
+}
+
+fn parse_block(p: &mut Parser, indent: usize) -> Result<Node, Error> {
+    if is_list_item(&p.lines[p.pos].content) {
+        return parse_list(p, indent);
+    }
+    return parse_map(p, indent);
+}
+
+fn parse_list(p: &mut Parser, indent: usize) -> Result<Node, Error> {
+    let line = p.lines[p.pos].number;
+    let mut items = Vec::new();
+    while p.pos < p.lines.len()
+        && p.lines[p.pos].indent == indent
+        && is_list_item(&p.lines[p.pos].content)
+    {
+        let number = p.lines[p.pos].number;
+        let content = p.lines[p.pos].content.clone();
+        let rest = content[1..].trim_start();
+        if rest.is_empty() {
+            p.pos += 1;
+            if p.pos < p.lines.len() && p.lines[p.pos].indent > indent {
+                let nested = p.lines[p.pos].indent;
+                items.push(parse_block(p, nested)?);
+            } else {
+                return Err(error(number, "empty list item"));
+            }
+        } else if split_key(rest).is_some() {
+            // "- key: value" opens a map whose keys line up with "key".
+            let nested = indent + content.len() - rest.len();
+            p.lines[p.pos] = Line {
+                number,
+                indent: nested,
+                content: String::from(rest),
+            };
+            items.push(parse_map(p, nested)?);
+        } else {
+            p.pos += 1;
+            items.push(parse_inline(rest, number)?);
+        }
+    }
+    return Ok(Node {
+        line,
+        value: Value::List(items),
+    });
+}
+
+fn parse_map(p: &mut Parser, indent: usize) -> Result<Node, Error> {
+    let line = p.lines[p.pos].number;
+    let mut entries: Vec<(String, Node)> = Vec::new();
+    while p.pos < p.lines.len()
+        && p.lines[p.pos].indent == indent
+        && !is_list_item(&p.lines[p.pos].content)
+    {
+        let number = p.lines[p.pos].number;
+        let content = p.lines[p.pos].content.clone();
+        let (key, rest) = match split_key(&content) {
+            Some(pair) => pair,
+            None => return Err(error(number, "expected 'key: value'")),
+        };
+        p.pos += 1;
+        let value = if !rest.is_empty() {
+            parse_inline(rest, number)?
+        } else if p.pos < p.lines.len() && p.lines[p.pos].indent > indent {
+            let nested = p.lines[p.pos].indent;
+            parse_block(p, nested)?
+        } else if p.pos < p.lines.len()
+            && p.lines[p.pos].indent == indent
+            && is_list_item(&p.lines[p.pos].content)
+        {
+            parse_list(p, indent)?
+        } else {
+            return Err(error(number, &format!("missing value for '{}'", key)));
+        };
+        if entries.iter().any(|(k, _)| k == key) {
+            return Err(error(number, &format!("duplicate key '{}'", key)));
+        }
+        entries.push((String::from(key), value));
+    }
+    return Ok(Node {
+        line,
+        value: Value::Map(entries),
+    });
+}
+
+fn parse_inline(text: &str, line: usize) -> Result<Node, Error> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    let node = parse_flow(&chars, &mut pos, line)?;
+    skip_spaces(&chars, &mut pos);
+    if pos < chars.len() {
+        return Err(error(line, &format!("unexpected '{}'", chars[pos])));
+    }
+    return Ok(node);
+}
+
+fn skip_spaces(chars: &[char], pos: &mut usize) {
+    while *pos < chars.len() && chars[*pos].is_whitespace() {
+        *pos += 1;
+    }
+}
+
+fn parse_flow(chars: &[char], pos: &mut usize, line: usize) -> Result<Node, Error> {
+    skip_spaces(chars, pos);
+    if *pos >= chars.len() {
+        return Err(error(line, "missing value"));
+    }
+    if chars[*pos] == '[' {
+        *pos += 1;
+        let mut items = Vec::new();
+        skip_spaces(chars, pos);
+        if *pos < chars.len() && chars[*pos] == ']' {
+            *pos += 1;
+            return Ok(Node {
+                line,
+                value: Value::List(items),
+            });
+        }
+        loop {
+            items.push(parse_flow(chars, pos, line)?);
+            skip_spaces(chars, pos);
+            if *pos >= chars.len() {
+                return Err(error(line, "missing ']'"));
+            }
+            let c = chars[*pos];
+            *pos += 1;
+            if c == ']' {
+                return Ok(Node {
+                    line,
+                    value: Value::List(items),
+                });
+            }
+            if c != ',' {
+                return Err(error(
+                    line,
+                    &format!("expected ',' or ']' but found '{}'", c),
+                ));
+            }
+        }
+    }
+    if chars[*pos] == '"' {
+        *pos += 1;
+        let start = *pos;
+        while *pos < chars.len() && chars[*pos] != '"' {
+            *pos += 1;
+        }
+        if *pos >= chars.len() {
+            return Err(error(line, "missing closing '\"'"));
+        }
+        let text: String = chars[start..*pos].iter().collect();
+        *pos += 1;
+        return Ok(Node {
+            line,
+            value: Value::Text(text),
+        });
+    }
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos] != ',' && chars[*pos] != ']' {
+        *pos += 1;
+    }
+    let text: String = chars[start..*pos].iter().collect();
+    let text = text.trim();
+    let value = match text.parse::<Float>() {
+        Ok(n) => Value::Number(n),
+        Err(_) => Value::Text(String::from(text)),
+    };
+    return Ok(Node { line, value });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float;
+
+    fn number(node: &Node) -> Float {
+        match node.value {
+            Value::Number(n) => return n,
+            _ => panic!("expected a number"),
+        }
+    }
+
+    fn text(node: &Node) -> &str {
+        match &node.value {
+            Value::Text(s) => return s,
+            _ => panic!("expected text"),
+        }
+    }
+
+    fn list(node: &Node) -> &Vec<Node> {
+        match &node.value {
+            Value::List(items) => return items,
+            _ => panic!("expected a list"),
+        }
+    }
+
+    #[test]
+    fn should_parse_list_of_maps() {
+        let doc = parse(
+            "# a comment\n\
+             - add: camera\n\
+             \x20 width: 100\n\
+             \x20 from: [ 0, 1.5, -5 ]  # trailing\n\
+             \n\
+             - add: \"light\"\n",
+        )
+        .unwrap();
+        let items = list(&doc);
+        assert!(items.len() == 2);
+        assert!(items[0].line == 2);
+        assert!(text(get(&items[0], "add").unwrap()) == "camera");
+        assert!(float::equals(
+            number(get(&items[0], "width").unwrap()),
+            100.0
+        ));
+        let from = list(get(&items[0], "from").unwrap());
+        assert!(from.len() == 3);
+        assert!(float::equals(number(&from[1]), 1.5));
+        assert!(text(get(&items[1], "add").unwrap()) == "light");
+        assert!(get(&items[1], "width").is_none());
+    }
+
+    #[test]
+    fn should_parse_nested_blocks() {
+        let doc = parse(
+            "- define: thing\n\
+             \x20 value:\n\
+             \x20   color: [ 1, 0, 0 ]\n\
+             \x20   steps:\n\
+             \x20     - [ scale, 2, 2, 2 ]\n\
+             \x20     - other\n\
+             \x20   same-indent:\n\
+             \x20   - [ ]\n",
+        )
+        .unwrap();
+        let value = get(&list(&doc)[0], "value").unwrap();
+        assert!(value.line == 3);
+        let steps = list(get(value, "steps").unwrap());
+        assert!(steps.len() == 2);
+        assert!(steps[1].line == 6);
+        assert!(text(&list(&steps[0])[0]) == "scale");
+        assert!(text(&steps[1]) == "other");
+        assert!(list(&list(get(value, "same-indent").unwrap())[0]).is_empty());
+    }
+
+    #[test]
+    fn should_report_line_numbers() {
+        let e = parse("- add: camera\n  width: [ 1, 2\n").unwrap_err();
+        assert!(e.line == 2);
+        assert!(e.to_string() == "line 2: missing ']'");
+
+        let e = parse("- add: camera\n  width: 1\n      height: 2\n").unwrap_err();
+        assert!(e.line == 3);
+
+        let e = parse("- add: camera\n  width: 1\n  width: 2\n").unwrap_err();
+        assert!(e.line == 3);
+
+        let e = parse("- add: camera\n\t width: 1\n").unwrap_err();
+        assert!(e.line == 2);
+
+        let e = parse("- add: camera\n  transform:\n").unwrap_err();
+        assert!(e.to_string() == "line 2: missing value for 'transform'");
+    }
+}